@@ -0,0 +1,364 @@
+//! Registry of employees and their earning limits.
+//!
+//! Caterers are listed by free text names in the project files,
+//! so "Hendrik" and "hendrik s." would be two different people.
+//! The registry in `extras/employees.yml` maps such aliases onto one canonical name
+//! and knows each employee's contract and earning limits:
+//!
+//! ```yaml
+//! contracts:
+//!   minijob: { monthly: 450, yearly: 5400 }
+//!
+//! employees:
+//!   Hendrik Sollich:
+//!     aliases: [Hendrik, hendrik s.]
+//!     contract: minijob
+//!   Maxime Muster:
+//!     contract: working_student
+//!     limits: { yearly: 9000 }
+//! ```
+//!
+//! Limits given with an employee take precedence over those of the contract.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bill::Currency;
+use chrono::prelude::*;
+use failure::Error;
+use log::warn;
+use yaml_rust::Yaml;
+
+use crate::project::Project;
+use crate::project::spec::*;
+use crate::storage::{Backend, Storable, Storage};
+use crate::util::{currency_to_string, yaml};
+use crate::util::decimal::Decimal;
+
+/// Name of the registry file inside the extras folder.
+pub const EMPLOYEES_FILE: &str = "employees.yml";
+
+/// Share of a limit from which on warnings are given, unless configured in `employees/limit_warning`.
+const DEFAULT_WARNING_THRESHOLD: f64 = 0.9;
+
+/// Maximum earnings of an employee.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Limits {
+    pub monthly: Option<Currency>,
+    pub yearly: Option<Currency>,
+}
+
+impl Limits {
+    fn from_yaml(yaml: &Yaml) -> Self {
+        Limits {
+            monthly: yaml::get_decimal(yaml, "monthly").map(Decimal::to_currency),
+            yearly: yaml::get_decimal(yaml, "yearly").map(Decimal::to_currency),
+        }
+    }
+
+    fn or(self, other: Limits) -> Self {
+        Limits {
+            monthly: self.monthly.or(other.monthly),
+            yearly: self.yearly.or(other.yearly),
+        }
+    }
+}
+
+/// An employee as listed in the registry.
+#[derive(Debug, Clone)]
+pub struct RegisteredEmployee {
+    pub name: String,
+    pub aliases: Vec<String>,
+    pub contract: Option<String>,
+    pub limits: Limits,
+}
+
+impl RegisteredEmployee {
+    fn is_called(&self, name: &str) -> bool {
+        let name = normalize(name);
+        normalize(&self.name) == name
+            || self.aliases.iter().any(|alias| normalize(alias) == name)
+    }
+}
+
+fn normalize(name: &str) -> String {
+    name.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+/// All known employees.
+#[derive(Debug, Clone, Default)]
+pub struct EmployeeRegistry {
+    employees: Vec<RegisteredEmployee>,
+}
+
+impl EmployeeRegistry {
+    pub fn from_yaml_str(content: &str) -> Result<Self, Error> {
+        let doc = yaml::parse(content)?;
+
+        let contracts = yaml::get_hash(&doc, "contracts")
+            .map(|contracts| contracts.iter()
+                 .filter_map(|(name, limits)| Some((name.as_str()?.to_owned(), Limits::from_yaml(limits))))
+                 .collect::<BTreeMap<String, Limits>>())
+            .unwrap_or_default();
+
+        let employees = yaml::get_hash(&doc, "employees")
+            .map(|employees| employees.iter()
+                 .filter_map(|(name, entry)| {
+                     let contract = yaml::get_string(entry, "contract");
+                     let contract_limits = contract.as_ref()
+                                                   .and_then(|c| contracts.get(c))
+                                                   .cloned()
+                                                   .unwrap_or_default();
+                     let limits = yaml::get(entry, "limits")
+                         .map(Limits::from_yaml)
+                         .unwrap_or_default()
+                         .or(contract_limits);
+                     let aliases = yaml::get(entry, "aliases")
+                         .and_then(Yaml::as_vec)
+                         .map(|aliases| aliases.iter().filter_map(Yaml::as_str).map(ToOwned::to_owned).collect())
+                         .unwrap_or_else(Vec::new);
+
+                     Some(RegisteredEmployee {
+                         name: name.as_str()?.to_owned(),
+                         aliases,
+                         contract,
+                         limits,
+                     })
+                 })
+                 .collect())
+            .unwrap_or_else(Vec::new);
+
+        Ok(EmployeeRegistry { employees })
+    }
+
+    /// Reads `extras/employees.yml`, the registry is empty if there is no such file.
+    pub fn load<L: Storable, B: Backend>(storage: &Storage<L, B>) -> Result<Self, Error> {
+        let path = storage.get_extra_file(EMPLOYEES_FILE)?;
+        if storage.backend().exists(&path) {
+            Self::from_yaml_str(&storage.backend().read(&path)?)
+        } else {
+            Ok(Self::default())
+        }
+    }
+
+    pub fn employees(&self) -> &[RegisteredEmployee] {
+        &self.employees
+    }
+
+    /// Finds an employee by name or alias, ignoring case and extra whitespace.
+    pub fn resolve(&self, name: &str) -> Option<&RegisteredEmployee> {
+        self.employees.iter().find(|e| e.is_called(name))
+    }
+
+    /// Canonical name of whoever is called `name`, or `name` itself if they are unknown.
+    pub fn canonical_name(&self, name: &str) -> String {
+        self.resolve(name)
+            .map(|e| e.name.clone())
+            .unwrap_or_else(|| name.to_owned())
+    }
+}
+
+/// What an employee earned in one year.
+#[derive(Debug, Clone)]
+pub struct Earnings {
+    pub name: String,
+    pub contract: Option<String>,
+    pub limits: Limits,
+    pub yearly: Currency,
+    /// Earnings per month, `1` is January
+    pub monthly: BTreeMap<u32, Currency>,
+}
+
+impl Earnings {
+    /// The month with the highest earnings.
+    pub fn peak_month(&self) -> Option<(u32, Currency)> {
+        self.monthly.iter()
+            .max_by_key(|&(_, wage)| wage.value())
+            .map(|(&month, &wage)| (month, wage))
+    }
+}
+
+/// What a limit applies to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Period {
+    Year(i32),
+    Month(i32, u32),
+}
+
+impl fmt::Display for Period {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Period::Year(year) => write!(f, "{}", year),
+            Period::Month(year, month) => write!(f, "{:02}/{}", month, year),
+        }
+    }
+}
+
+/// Somebody earned close to or more than they are allowed to.
+#[derive(Debug, Clone)]
+pub struct LimitWarning {
+    pub name: String,
+    pub period: Period,
+    pub earned: Currency,
+    pub limit: Currency,
+}
+
+impl LimitWarning {
+    pub fn is_exceeded(&self) -> bool {
+        self.earned.value() > self.limit.value()
+    }
+}
+
+impl fmt::Display for LimitWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_exceeded() {
+            write!(f, "{}", lformat!("{} exceeded the limit of {} in {} with {}",
+                                     self.name, currency_to_string(&self.limit), self.period, currency_to_string(&self.earned)))
+        } else {
+            write!(f, "{}", lformat!("{} is close to the limit of {} in {} with {}",
+                                     self.name, currency_to_string(&self.limit), self.period, currency_to_string(&self.earned)))
+        }
+    }
+}
+
+/// Sums up the wages of everybody who worked in `year`, by canonical name.
+///
+/// Canceled projects are not counted, the date of a project is the date of its event.
+pub fn earnings(registry: &EmployeeRegistry, projects: &[Project], year: i32) -> Vec<Earnings> {
+    let mut earnings: BTreeMap<String, Earnings> = BTreeMap::new();
+
+    for project in projects.iter().filter(|p| !p.canceled()) {
+        let date = match project.event_date() {
+            Some(date) if date.year() == year => date,
+            _ => continue
+        };
+
+        for employee in project.hours().employees().unwrap_or_else(Vec::new) {
+            let wage = home_wage(project, &employee);
+            let registered = registry.resolve(&employee.name);
+            let name = registered.map(|r| r.name.clone()).unwrap_or_else(|| employee.name.clone());
+            let entry = earnings.entry(name.clone())
+                .or_insert_with(|| Earnings {
+                    name,
+                    contract: registered.and_then(|r| r.contract.clone()),
+                    limits: registered.map(|r| r.limits).unwrap_or_default(),
+                    yearly: Currency { symbol: wage.symbol, value: 0 },
+                    monthly: BTreeMap::new(),
+                });
+            entry.yearly = entry.yearly + wage;
+            let month = entry.monthly.entry(date.month())
+                             .or_insert(Currency { symbol: wage.symbol, value: 0 });
+            *month = *month + wage;
+        }
+    }
+
+    earnings.into_iter().map(|(_, e)| e).collect()
+}
+
+/// Wage of `employee` in the home currency.
+///
+/// Stays unconverted if the project is in a foreign currency but has no exchange rate yet.
+pub fn home_wage(project: &Project, employee: &Employee) -> Currency {
+    project.to_home_currency(employee.wage)
+           .unwrap_or_else(|error| {
+               warn!("{}: {}", project.short_desc(), error);
+               employee.wage
+           })
+}
+
+/// Everybody who earned at least `threshold` (e.g. `0.9`) of one of their limits.
+pub fn limit_warnings(earnings: &[Earnings], year: i32, threshold: f64) -> Vec<LimitWarning> {
+    let close = |earned: &Currency, limit: &Currency| earned.value() as f64 >= limit.value() as f64 * threshold;
+    let mut warnings = Vec::new();
+
+    for e in earnings {
+        if let Some(limit) = e.limits.yearly {
+            if close(&e.yearly, &limit) {
+                warnings.push(LimitWarning { name: e.name.clone(), period: Period::Year(year), earned: e.yearly, limit });
+            }
+        }
+        if let Some(limit) = e.limits.monthly {
+            for (&month, earned) in &e.monthly {
+                if close(earned, &limit) {
+                    warnings.push(LimitWarning { name: e.name.clone(), period: Period::Month(year, month), earned: *earned, limit });
+                }
+            }
+        }
+    }
+
+    warnings
+}
+
+/// Share of a limit from which on warnings are given.
+pub fn warning_threshold() -> f64 {
+    crate::CONFIG.get_f64("employees/limit_warning").unwrap_or(DEFAULT_WARNING_THRESHOLD)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static REGISTRY: &str = r#"
+    contracts:
+      minijob: { monthly: 450, yearly: 5400 }
+    employees:
+      Hendrik Sollich:
+        aliases: [Hendrik, hendrik s.]
+        contract: minijob
+      Maxime Muster:
+        contract: working_student
+        limits: { yearly: 100 }
+    "#;
+
+    fn project(date: &str, caterers: &str) -> Project {
+        let doc = format!(r#"
+        event:
+          dates:
+            - begin: {}
+        hours:
+          salary: 10.0
+          caterers: {}
+        "#, date, caterers);
+        Project::from_file_content(&doc).unwrap()
+    }
+
+    #[test]
+    fn resolves_aliases() {
+        let registry = EmployeeRegistry::from_yaml_str(REGISTRY).unwrap();
+        assert_eq!(registry.canonical_name("Hendrik"), "Hendrik Sollich");
+        assert_eq!(registry.canonical_name("  Hendrik   S. "), "Hendrik Sollich");
+        assert_eq!(registry.canonical_name("Somebody"), "Somebody");
+
+        let hendrik = registry.resolve("hendrik").unwrap();
+        assert_eq!(hendrik.limits.monthly.map(|l| l.value()), Some(450_00));
+        let maxime = registry.resolve("Maxime Muster").unwrap();
+        assert_eq!(maxime.limits.yearly.map(|l| l.value()), Some(100_00));
+        assert_eq!(maxime.limits.monthly, None);
+    }
+
+    #[test]
+    fn warns_close_to_limits() {
+        let registry = EmployeeRegistry::from_yaml_str(REGISTRY).unwrap();
+        let projects = vec![
+            project("01.05.2019", "{ Hendrik: 20, Maxime Muster: 5 }"),
+            project("20.05.2019", "{ hendrik s.: 22 }"),
+            project("01.06.2019", "{ Maxime Muster: 5 }"),
+            project("01.06.2018", "{ Maxime Muster: 50 }"),
+        ];
+
+        let earnings = earnings(&registry, &projects, 2019);
+        assert_eq!(earnings.len(), 2);
+        assert_eq!(earnings[0].name, "Hendrik Sollich");
+        assert_eq!(earnings[0].yearly.value(), 420_00);
+        assert_eq!(earnings[1].yearly.value(), 100_00);
+
+        let warnings = limit_warnings(&earnings, 2019, 0.9);
+        assert_eq!(warnings.len(), 2);
+        assert_eq!(warnings[0].period, Period::Month(2019, 5));
+        assert!(!warnings[0].is_exceeded());
+        assert_eq!(warnings[1].period, Period::Year(2019));
+    }
+}
@@ -1,5 +1,7 @@
 use failure::Fail;
 
+use std::path::PathBuf;
+
 #[derive(Fail, Debug)]
 pub enum ActionError {
 
@@ -11,4 +13,13 @@ pub enum ActionError {
 
     #[fail(display = "Nothing found for {:?}", _0)]
     NothingFound(Vec<String>),
+
+    #[fail(display = "{:?} matches more than one project", _0)]
+    Ambiguous(Vec<String>),
+
+    #[fail(display = "Can't merge {:?}, please resolve manually", _0)]
+    Unmergeable(PathBuf),
+
+    #[fail(display = "Merge of {:?} is invalid: {}", _0, _1)]
+    InvalidMerge(PathBuf, String),
 }
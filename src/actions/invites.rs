@@ -0,0 +1,228 @@
+//! Creating projects from calendar invites.
+//!
+//! Every `VEVENT` of an iCalendar file becomes a date of a project,
+//! events with the same summary end up in the same project:
+//!
+//! * `SUMMARY` is the name of the project (`event/name`)
+//! * `DTSTART` and `DTEND` become `event/dates`, all day events spanning several days get an `end`,
+//!   events with a time get `times`, several events on the same day share one date
+//! * `LOCATION`, `DESCRIPTION` and the email address of the `ORGANIZER`
+//!   become `event/location`, `event/description` and `client/email`
+
+use chrono::prelude::*;
+use chrono::Duration;
+use failure::Error;
+use log::warn;
+
+use crate::project::spec::{Event, EventTime};
+use crate::util::{ical, yaml};
+
+/// One project worth of events.
+#[derive(Debug)]
+pub struct Invite {
+    pub name: String,
+    pub events: Vec<Event>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub email: Option<String>,
+}
+
+impl Invite {
+    fn new(name: String) -> Self {
+        Invite { name, events: Vec::new(), location: None, description: None, email: None }
+    }
+
+    fn add(&mut self, event: Event) {
+        let same_day = self.events.iter_mut()
+            .find(|e| e.begin == event.begin && e.end.is_none() && event.end.is_none()
+                      && !e.times.is_empty() && !event.times.is_empty());
+        match same_day {
+            Some(day) => {
+                day.times.extend(event.times);
+                day.times.sort_by_key(|t| t.start);
+            }
+            None => self.events.push(event),
+        }
+    }
+
+    /// `event/dates` as a yaml block.
+    fn dates(&self) -> String {
+        let mut dates = String::new();
+        for event in &self.events {
+            dates.push_str(&format!("\n- begin: {}", event.begin.format("%d.%m.%Y")));
+            if let Some(end) = event.end {
+                dates.push_str(&format!("\n  end: {}", end.format("%d.%m.%Y")));
+            }
+            if !event.times.is_empty() {
+                dates.push_str("\n  times:");
+                for time in &event.times {
+                    dates.push_str(&format!("\n  - {{ begin: \"{}\", end: \"{}\" }}",
+                                            time.start.format("%H:%M"),
+                                            time.end.format("%H:%M")));
+                }
+            }
+        }
+        dates
+    }
+
+    /// Writes the invite into the content of a freshly created project file.
+    pub fn fill_in(&self, content: &str) -> Result<String, Error> {
+        let mut content = content.to_owned();
+        if !self.events.is_empty() {
+            content = yaml::set_value(&content, "event/dates", &self.dates())?;
+        }
+        if let Some(ref location) = self.location {
+            content = yaml::set_value(&content, "event/location", &yaml::quote(location))?;
+        }
+        if let Some(ref description) = self.description {
+            content = yaml::set_value(&content, "event/description", &yaml::quote(description))?;
+        }
+        if let Some(ref email) = self.email {
+            content = yaml::set_value(&content, "client/email", &yaml::quote(email))?;
+        }
+        Ok(content)
+    }
+}
+
+fn to_date(date: NaiveDate) -> Date<Utc> {
+    Utc.from_utc_date(&date)
+}
+
+fn to_event(start: &str, end: Option<&str>) -> Option<Event> {
+    if let Some(begin) = ical::parse_date(start) {
+        // all day events end on the following day
+        let last_day = end.and_then(ical::parse_date)
+                          .map(|end| end - Duration::days(1))
+                          .filter(|&end| end > begin);
+        return Some(Event { begin: to_date(begin), end: last_day.map(to_date), times: Vec::new() });
+    }
+
+    let start = ical::parse_date_time(start)?;
+    let end = end.and_then(ical::parse_date_time).filter(|&end| end >= start).unwrap_or(start);
+    let time = EventTime { start: start.time(), end: end.time() };
+    let days = end.date().signed_duration_since(start.date()).num_days();
+
+    // ending after midnight still counts as one day
+    if days == 0 || (days == 1 && end.time() <= start.time()) {
+        Some(Event { begin: to_date(start.date()), end: None, times: vec![time] })
+    } else {
+        Some(Event { begin: to_date(start.date()), end: Some(to_date(end.date())), times: vec![time] })
+    }
+}
+
+fn non_empty(value: Option<&String>) -> Option<String> {
+    value.map(|v| ical::unescape(v).trim().to_owned())
+         .filter(|v| !v.is_empty())
+}
+
+/// Reads all events of an iCalendar file, grouped into invites by summary.
+pub fn parse(content: &str) -> Vec<Invite> {
+    let mut invites: Vec<Invite> = Vec::new();
+
+    for properties in ical::parse_components(content, "VEVENT") {
+        if properties.get("STATUS").map(String::as_str) == Some("CANCELLED") {
+            continue;
+        }
+        let name = match non_empty(properties.get("SUMMARY")) {
+            Some(name) => name,
+            None => { warn!("{}", lformat!("skipping an event without summary")); continue; }
+        };
+        let event = match properties.get("DTSTART")
+                                    .and_then(|start| to_event(start, properties.get("DTEND").map(String::as_str))) {
+            Some(event) => event,
+            None => { warn!("{}", lformat!("skipping {:?}, it has no start", name)); continue; }
+        };
+
+        let index = match invites.iter().position(|i| i.name == name) {
+            Some(index) => index,
+            None => { invites.push(Invite::new(name)); invites.len() - 1 }
+        };
+        let invite = &mut invites[index];
+        invite.add(event);
+        invite.location = invite.location.take().or_else(|| non_empty(properties.get("LOCATION")));
+        invite.description = invite.description.take().or_else(|| non_empty(properties.get("DESCRIPTION")));
+        invite.email = invite.email.take().or_else(|| {
+            properties.get("ORGANIZER")
+                      .map(|organizer| organizer.trim())
+                      .filter(|organizer| organizer.to_lowercase().starts_with("mailto:"))
+                      .map(|organizer| organizer[7..].to_owned())
+        });
+    }
+
+    for invite in &mut invites {
+        invite.events.sort_by_key(|e| e.begin);
+    }
+    invites
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::Project;
+    use crate::project::spec::*;
+
+    static INVITE: &str = "BEGIN:VCALENDAR\r
+BEGIN:VEVENT\r
+SUMMARY:Summer Party\r
+DTSTART:20190601T180000\r
+DTEND:20190602T020000\r
+LOCATION:Garden\\, behind the house\r
+DESCRIPTION:Drinks\\nand cake\r
+ORGANIZER;CN=Jane Doe:mailto:jane@example.com\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Summer Party\r
+DTSTART:20190601T120000\r
+DTEND:20190601T140000\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Conference\r
+DTSTART;VALUE=DATE:20190710\r
+DTEND;VALUE=DATE:20190713\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Canceled\r
+STATUS:CANCELLED\r
+DTSTART;VALUE=DATE:20190710\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    static TEMPLATE: &str = "client:\n  email:\n\nevent:\n  name: Summer Party\n  location: # might be a list\n  dates:\n  - begin: 01.06.2019\n    #end:\n    times:\n    -  begin: \"\"\n       end:   \"\"\n\n  description: |\n    \n\noffer:\n  appendix: 1\n";
+
+    #[test]
+    fn groups_events_by_summary() {
+        let invites = parse(INVITE);
+        assert_eq!(invites.len(), 2);
+
+        let party = &invites[0];
+        assert_eq!(party.name, "Summer Party");
+        assert_eq!(party.events.len(), 1);
+        assert_eq!(party.events[0].end, None);
+        assert_eq!(party.events[0].times.len(), 2);
+        assert_eq!(party.location.as_ref().unwrap(), "Garden, behind the house");
+        assert_eq!(party.email.as_ref().unwrap(), "jane@example.com");
+
+        let conference = &invites[1];
+        assert_eq!(conference.events[0].begin, Utc.ymd(2019, 7, 10));
+        assert_eq!(conference.events[0].end, Some(Utc.ymd(2019, 7, 12)));
+        assert!(conference.events[0].times.is_empty());
+    }
+
+    #[test]
+    fn fills_in_project() {
+        let invites = parse(INVITE);
+        let content = invites[0].fill_in(TEMPLATE).unwrap();
+        let project = Project::from_file_content(&content).unwrap();
+
+        assert_eq!(project.location(), Some("Garden, behind the house"));
+        assert_eq!(project.client().email(), Some("jane@example.com"));
+        assert_eq!(yaml::get_str(project.yaml(), "event/description"), Some("Drinks\nand cake"));
+
+        let events = project.events().unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].times[0].start, NaiveTime::from_hms(12, 0, 0));
+        assert_eq!(events[0].times[1].start, NaiveTime::from_hms(18, 0, 0));
+        assert_eq!(events[0].times[1].end, NaiveTime::from_hms(2, 0, 0));
+    }
+}
@@ -7,22 +7,36 @@ use icalendar::Calendar;
 #[cfg(feature = "meta")]
 use toml;
 use log::{info, trace};
-use failure::Error;
+use failure::{bail, format_err, Error};
 
 use std::fmt::Write;
-#[cfg(feature = "meta")] use std::fs;
+use std::fs;
 
-use std::path::PathBuf;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
 use std::collections::HashMap;
 use std::process::Command;
 
 use crate::util;
-use crate::storage::{self, StorageDir, Storable};
+use crate::util::decimal::Decimal;
+use crate::storage::{self, Backend, StorageDir, Storable};
 use crate::project::Project;
 use crate::project::spec::*;
+use crate::project::history::{self, HistoryEntry};
+use crate::project::reminders::{Reminder, Rules};
+use crate::project::exchange::{self, ExchangeRate, RateTable};
+use crate::project::state::State;
 
 pub mod error;
+pub mod payroll;
+pub mod employees;
+pub mod timesheet;
+pub mod invites;
+pub mod staffing;
+pub mod stock;
 use self::error::*;
+use self::employees::{EmployeeRegistry, Earnings, LimitWarning};
+use self::payroll::Payroll;
 
 /// Helper method that passes projects matching the `search_terms` to the passt closure `f`
 pub fn with_projects<F>(dir:StorageDir, search_terms: &[&str], f:F) -> Result<(), Error>
@@ -60,7 +74,10 @@ pub fn projects_to_csv(projects:&[Project]) -> Result<String, Error>{
              lformat!("Responsible"), //Verantwortlich
              lformat!("Payed on"), // Bezahlt am
              lformat!("Amount"), // Betrag
-             lformat!("Canceled") //Canceled
+             lformat!("Canceled"), //Canceled
+             lformat!("Cost"), // Kosten
+             lformat!("Wages"), // Löhne
+             lformat!("Margin") // Marge
              ]
              .join(splitter))?;
 
@@ -73,38 +90,97 @@ pub fn projects_to_csv(projects:&[Project]) -> Result<String, Error>{
                  project.field("Employees")                         .unwrap_or_else(|| String::from(r#""""#)),
                  project.field("Responsible")                       .unwrap_or_else(|| String::from(r#""""#)),
                  project.field("invoice/payed_date")                .unwrap_or_else(|| String::from(r#""""#)),
-                 project.sum_sold().map(|c|c.value().to_string()).unwrap_or_else(|_| String::from(r#""""#)),
-                 String::from(if project.canceled(){"canceled"} else {""})
+                 home(project, project.sum_final()),
+                 String::from(if project.canceled(){"canceled"} else {""}),
+                 home(project, project.cost()),
+                 home(project, project.hours().gross_wages().ok_or_else(|| format_err!("no wages"))),
+                 home(project, project.margin())
         ].join(splitter))?;
+
+        // deposits are documents of their own, the invoice only shows what is left
+        for (index, deposit) in project.deposits().unwrap_or_default().iter().enumerate().filter(|(_, d)| d.is_issued()) {
+            writeln!(&mut string, "{}", [
+                     deposit.number_str()                               .unwrap_or_else(|| String::from(r#""""#)),
+                     project.field("Name")                              .unwrap_or_else(|| String::from(r#""""#)),
+                     project.field("event/dates/0/begin")               .unwrap_or_else(|| String::from(r#""""#)),
+                     deposit.date.map(|d| d.format("%d.%m.%Y").to_string()).unwrap_or_else(|| String::from(r#""""#)),
+                     project.field("Employees")                         .unwrap_or_else(|| String::from(r#""""#)),
+                     project.field("Responsible")                       .unwrap_or_else(|| String::from(r#""""#)),
+                     deposit.payed_date.map(|d| d.format("%d.%m.%Y").to_string()).unwrap_or_else(|| String::from(r#""""#)),
+                     home(project, project.sum_deposit(index)),
+                     lformat!("deposit {}", index + 1),
+                     String::new(), String::new(), String::new()
+            ].join(splitter))?;
+        }
+
+        // the credit note is a document of its own, with a negative amount
+        if project.storno().is_issued() {
+            writeln!(&mut string, "{}", [
+                     project.storno().number_str()                      .unwrap_or_else(|| String::from(r#""""#)),
+                     project.field("Name")                              .unwrap_or_else(|| String::from(r#""""#)),
+                     project.field("event/dates/0/begin")               .unwrap_or_else(|| String::from(r#""""#)),
+                     project.field("storno/date")                       .unwrap_or_else(|| String::from(r#""""#)),
+                     project.field("Employees")                         .unwrap_or_else(|| String::from(r#""""#)),
+                     project.field("Responsible")                       .unwrap_or_else(|| String::from(r#""""#)),
+                     String::from(r#""""#),
+                     home(project, project.sum_credited()),
+                     lformat!("storno of {}", project.field("InvoiceNumber").unwrap_or_default()),
+                     String::new(), String::new(), String::new()
+            ].join(splitter))?;
+        }
     }
     Ok(string)
 }
 
+/// Cents in the home currency for the csv, projects in a foreign currency are converted
+fn home(project: &Project, amount: Result<Currency, Error>) -> String {
+    amount.and_then(|amount| project.to_home_currency(amount))
+          .map(|c|c.value().to_string())
+          .unwrap_or_else(|_| String::from(r#""""#))
+}
 
-fn open_payments(projects: &[Project]) -> Currency {
-   projects.iter()
-           .filter(|&p| !p.canceled() && !p.is_payed() && p.age().unwrap_or(0) > 0)
-           .filter_map(|p| p.sum_sold().ok())
-           .fold(Currency::default(), |acc, x| acc + x)
+/// Adds up what `amount` returns for each project in the home currency.
+///
+/// Projects in a foreign currency without exchange rate can't be added,
+/// they are left out and listed in `without_rate`.
+fn sum_in_home_currency<'a, I, F>(projects: I, amount: F, without_rate: &mut Vec<String>) -> Currency
+    where I: Iterator<Item = &'a Project>,
+          F: Fn(&Project) -> Option<Currency>
+{
+    let mut sum = Currency::default();
+    for project in projects {
+        if let Some(amount) = amount(project) {
+            match project.to_home_currency(amount) {
+                Ok(amount) => sum = sum + amount,
+                Err(_) => without_rate.push(project.short_desc())
+            }
+        }
+    }
+    sum
 }
 
-fn open_wages(projects: &[Project]) -> Currency {
-    projects.iter()
-            .filter(|p| !p.canceled() && p.age().unwrap_or(0) > 0)
-            .filter_map(|p| p.hours().net_wages())
-            .fold(Currency::default(), |acc, x| acc + x)
+fn open_payments(projects: &[Project], without_rate: &mut Vec<String>) -> Currency {
+    let open = projects.iter()
+                       .filter(|&p| p.is_billable() && !p.is_payed() && !p.storno().is_issued() && p.age().unwrap_or(0) > 0);
+    sum_in_home_currency(open, |p| p.sum_open().ok(), without_rate)
 }
 
-fn unpayed_employees(projects: &[Project]) -> HashMap<String, Currency> {
+fn open_wages(projects: &[Project], without_rate: &mut Vec<String>) -> Currency {
+    let open = projects.iter()
+                       .filter(|p| !p.canceled() && p.age().unwrap_or(0) > 0);
+    sum_in_home_currency(open, |p| p.hours().net_wages(), without_rate)
+}
+
+fn unpayed_employees(projects: &[Project], registry: &EmployeeRegistry) -> HashMap<String, Currency> {
     let mut buckets = HashMap::new();
     let employees = projects.iter()
                             .filter(|p| !p.canceled() && p.age().unwrap_or(0) > 0)
-                            .filter_map(|p| p.hours().employees())
-                            .flat_map(IntoIterator::into_iter);
+                            .filter_map(|p| Some((p, p.hours().employees()?)))
+                            .flat_map(|(p, employees)| employees.into_iter().map(move |e| (p, e)));
 
-    for employee in employees {
-        let bucket = buckets.entry(employee.name.clone()).or_insert_with(Currency::new);
-        *bucket = *bucket + employee.salary;
+    for (project, employee) in employees {
+        let bucket = buckets.entry(registry.canonical_name(&employee.name)).or_insert_with(Currency::new);
+        *bucket = *bucket + employees::home_wage(project, &employee);
     }
     buckets
 }
@@ -114,16 +190,50 @@ pub struct Dues {
     pub acc_sum_sold: Currency,
     pub acc_wages: Currency,
     pub unpayed_employees: HashMap<String, Currency>,
+    /// Employees close to their earning limits this year
+    pub limit_warnings: Vec<LimitWarning>,
+    /// Projects in a foreign currency that are missing from the sums, they have no exchange rate
+    pub without_rate: Vec<String>,
 }
 
 /// Command DUES
-pub fn dues() -> Result<Dues, Error> {
-    let projects = storage::setup::<Project>()?.open_projects(StorageDir::Working)?;
-    let acc_sum_sold: Currency = open_payments(&projects);
-    let acc_wages = open_wages(&projects);
-    let unpayed_employees = unpayed_employees(&projects);
+///
+/// Earning limits are only checked `with_wages`, against the projects of the current year.
+pub fn dues(with_wages: bool) -> Result<Dues, Error> {
+    let storage = storage::setup::<Project>()?;
+    let registry = EmployeeRegistry::load(&storage)?;
+    let projects = storage.open_projects(StorageDir::Working)?;
+    let mut without_rate = Vec::new();
+    let acc_sum_sold: Currency = open_payments(&projects, &mut without_rate);
+    let acc_wages = open_wages(&projects, &mut without_rate);
+    without_rate.sort();
+    without_rate.dedup();
+    let unpayed_employees = unpayed_employees(&projects, &registry);
+
+    let limit_warnings = if with_wages {
+        let year = Utc::today().year();
+        let this_year = storage.open_projects(StorageDir::Year(year))?;
+        employees::limit_warnings(&employees::earnings(&registry, &this_year, year),
+                                  year,
+                                  employees::warning_threshold())
+    } else {
+        Vec::new()
+    };
 
-    Ok(Dues{ acc_sum_sold, acc_wages, unpayed_employees})
+    Ok(Dues{ acc_sum_sold, acc_wages, unpayed_employees, limit_warnings, without_rate })
+}
+
+/// Command EARNINGS
+///
+/// What everybody earned in `year`, together with warnings about earning limits,
+/// see [`employees`](employees/index.html).
+pub fn earnings(year: i32) -> Result<(Vec<Earnings>, Vec<LimitWarning>), Error> {
+    let storage = storage::setup::<Project>()?;
+    let registry = EmployeeRegistry::load(&storage)?;
+    let projects = storage.open_projects(StorageDir::All)?;
+    let earnings = employees::earnings(&registry, &projects, year);
+    let warnings = employees::limit_warnings(&earnings, year, employees::warning_threshold());
+    Ok((earnings, warnings))
 }
 
 /// Testing only, tries to run complete spec on all projects.
@@ -194,6 +304,13 @@ pub fn unarchive_projects(year:i32, search_terms:&[&str]) -> Result<Vec<PathBuf>
     Ok( storage::setup_with_git::<Project>()?.unarchive_projects(year, search_terms) ?)
 }
 
+/// Command UNDO
+///
+/// Reverts the last archive, unarchive or delete.
+pub fn undo() -> Result<Vec<PathBuf>, Error> {
+    storage::setup_with_git::<Project>()?.undo()
+}
+
 /// Produces a calendar from the selected `StorageDir`
 pub fn calendar(dir: StorageDir) -> Result<String, Error> {
     calendar_with_tasks(dir, true)
@@ -210,14 +327,589 @@ pub fn calendar_with_tasks(dir: StorageDir, show_tasks:bool) -> Result<String, E
     let storage = storage::setup::<Project>()?;
     let mut cal = Calendar::new();
     if show_tasks {
+        let rules = Rules::from_config()?;
         for project in storage.open_projects(StorageDir::Working)?  {
-            cal.append(&mut project.to_tasks())
+            cal.append(&mut project.to_tasks(&rules))
         }
     }
     for project in storage.open_projects(dir)?{
         cal.append(&mut project.to_ical())
     }
-    Ok(cal.to_string())
+    Ok(util::ical::to_string(&cal))
+}
+
+/// Command REMIND
+///
+/// Checks all working projects against the configured reminder rules.
+pub fn reminders() -> Result<Vec<(Project, Vec<Reminder>)>, Error> {
+    let storage = storage::setup::<Project>()?;
+    let rules = Rules::from_config()?;
+    let today = Utc::today();
+    Ok(storage.open_projects(StorageDir::Working)?
+              .into_iter()
+              .map(|project| { let reminders = rules.check(&project, today); (project, reminders) })
+              .filter(|(_, reminders)| !reminders.is_empty())
+              .collect())
+}
+
+/// Command SYNC
+///
+/// Pulls from `origin` and merges conflicting project files field by field.
+/// `decide` is asked about every field that was changed differently on both sides.
+/// All merged files are validated before anything is written,
+/// if one of them is broken the merge is left unfinished for manual inspection.
+///
+/// Returns the files that had to be merged.
+pub fn sync<F>(decide: F) -> Result<Vec<PathBuf>, Error>
+    where F: Fn(&Path, &storage::merge::Conflict) -> storage::merge::Side
+{
+    use crate::storage::merge;
+
+    let storage = storage::setup_with_git::<Project>()?;
+    let repo = storage.get_repository()?;
+
+    if repo.pull_no_edit().success() {
+        return Ok(Vec::new());
+    }
+
+    let conflicted = repo.conflicted_files();
+    if conflicted.is_empty() {
+        bail!(format_err!("git pull did not exit successfully"));
+    }
+
+    let extension = Project::file_extension();
+    let mut merged_files = Vec::new();
+    for path in &conflicted {
+        if path.extension().and_then(OsStr::to_str) != Some(extension.as_str()) {
+            bail!(ActionError::Unmergeable(path.to_owned()));
+        }
+
+        let stages = (repo.show_stage(1, path), repo.show_stage(2, path), repo.show_stage(3, path));
+        let (base, ours_content, theirs) = match stages {
+            (Some(base), Some(ours), Some(theirs)) => (base, ours, theirs),
+            // deleted on one side or added on both
+            _ => bail!(ActionError::Unmergeable(path.to_owned()))
+        };
+
+        let mut merged = merge::merge(&util::yaml::parse(&base)?,
+                                      &util::yaml::parse(&ours_content)?,
+                                      &util::yaml::parse(&theirs)?);
+        for conflict in merged.conflicts.clone() {
+            let side = decide(path, &conflict);
+            merged.resolve(&conflict, side);
+        }
+
+        let content = merged.to_yaml_string(&ours_content)?;
+        let project = Project::from_file_content(&content)?;
+        project.validate()
+               .map_err(|errors| ActionError::InvalidMerge(path.to_owned(), errors.to_string()))?;
+        project.bills()
+               .map_err(|e| ActionError::InvalidMerge(path.to_owned(), e.to_string()))?;
+
+        merged_files.push((path.to_owned(), content));
+    }
+
+    for (path, content) in &merged_files {
+        info!("{}", lformat!("merged {}", path.display()));
+        fs::write(path, content)?;
+    }
+
+    let paths = merged_files.into_iter().map(|(path, _)| path).collect::<Vec<_>>();
+    if !repo.add(&paths).success() || !repo.commit_with_message("merged with asciii sync").success() {
+        bail!(format_err!("git commit did not exit successfully"));
+    }
+    Ok(paths)
+}
+
+/// Command HISTORY
+///
+/// Semantic changelog of every project matching `search_terms`, see [`project::history`](../project/history/index.html).
+pub fn history(dir: StorageDir, search_terms: &[&str]) -> Result<Vec<(Project, Vec<HistoryEntry>)>, Error> {
+    let storage = storage::setup_with_git::<Project>()?;
+    let repo = storage.get_repository()?;
+    storage.search_projects_any(dir, search_terms)?
+        .into_iter()
+        .map(|project| {
+            let entries = history::of_project(&project, repo)?;
+            Ok((project, entries))
+        })
+        .collect()
+}
+
+/// Command PAYROLL
+///
+/// Collects every working project whose wages are due, see [`payroll`](payroll/index.html).
+pub fn payroll() -> Result<Payroll, Error> {
+    let storage = storage::setup::<Project>()?;
+    let registry = EmployeeRegistry::load(&storage)?;
+    Ok(Payroll::from_projects(storage.open_projects(StorageDir::Working)?, &registry))
+}
+
+/// Sets `hours.wages_date` on every project of `payroll` and commits them together.
+///
+/// If any project can't be changed, the ones changed so far are restored.
+pub fn settle_payroll(payroll: &Payroll, date: Date<Utc>) -> Result<Vec<PathBuf>, Error> {
+    let storage = storage::setup_with_git::<Project>()?;
+    let wages_date = date.format("%d.%m.%Y").to_string();
+
+    let mut changed: Vec<(PathBuf, String)> = Vec::new();
+    for project in &payroll.projects {
+        let original = fs::read_to_string(project.file())?;
+        if let Err(e) = project.set_value("hours/wages_date", &wages_date) {
+            for (file, content) in &changed {
+                fs::write(file, content)?;
+            }
+            return Err(e);
+        }
+        changed.push((project.file(), original));
+    }
+
+    let files = changed.into_iter().map(|(file, _)| file).collect::<Vec<_>>();
+    if let Some(repo) = storage.repository() {
+        repo.add(&files);
+        if !repo.commit_paths_with_message(&files, &lformat!("payroll {}", wages_date)).success() {
+            bail!(format_err!("git commit did not exit successfully"));
+        }
+    }
+
+    Ok(files)
+}
+
+/// Command NEW --from-ics
+///
+/// Creates one project for every invite in an iCalendar file, see [`invites`](invites/index.html).
+pub fn new_from_ics(file: &Path, template_name: &str, fill_data: &HashMap<&str, String>) -> Result<Vec<Project>, Error> {
+    let invites = invites::parse(&fs::read_to_string(file)?);
+    if invites.is_empty() {
+        bail!(format_err!("{}", lformat!("{} contains no events", file.display())));
+    }
+
+    let storage = storage::setup::<Project>()?;
+    let mut projects = Vec::new();
+    for invite in &invites {
+        let mut fill_data = fill_data.clone();
+        if let Some(first) = invite.events.first() {
+            fill_data.insert("DATE-EVENT", first.begin.format("%d.%m.%Y").to_string());
+        }
+        let project = storage.create_project(&invite.name, template_name, &fill_data)?;
+        project.write_content(&invite.fill_in(project.file_content())?)?;
+        projects.push(Project::open(&project.file())?);
+    }
+    Ok(projects)
+}
+
+/// Command HOURS IMPORT
+///
+/// Reads a time sheet and matches its shifts against all working projects,
+/// nothing is written yet, see [`timesheet`](timesheet/index.html).
+pub fn import_hours(file: &Path, format: Option<timesheet::Format>, overwrite: bool) -> Result<timesheet::Import, Error> {
+    let format = format.unwrap_or_else(|| timesheet::Format::from_extension(file.extension().and_then(OsStr::to_str)));
+    let (shifts, mut problems) = timesheet::parse(&fs::read_to_string(file)?, format)?;
+
+    let storage = storage::setup::<Project>()?;
+    let registry = EmployeeRegistry::load(&storage)?;
+    let projects = storage.open_projects(StorageDir::Working)?;
+
+    let mut import = timesheet::import(&projects, &registry, &shifts, overwrite);
+    problems.append(&mut import.problems);
+    import.problems = problems;
+    Ok(import)
+}
+
+/// Writes new hours into project files and adds them to git.
+pub fn apply_hours(updates: &[timesheet::Update]) -> Result<Vec<PathBuf>, Error> {
+    let storage = storage::setup_with_git::<Project>()?;
+    let mut written = Vec::new();
+    for update in updates {
+        update.apply()?;
+        written.push(update.project.file());
+    }
+    if let Some(repo) = storage.repository() {
+        repo.add(&written);
+    }
+    Ok(written)
+}
+
+/// Command STAFF
+///
+/// Planned shifts of all working projects and the conflicts between them.
+pub fn staffing() -> Result<(Vec<staffing::Booking>, Vec<staffing::Conflict>), Error> {
+    let storage = storage::setup::<Project>()?;
+    let registry = EmployeeRegistry::load(&storage)?;
+    let projects = storage.open_projects(StorageDir::Working)?;
+    let bookings = staffing::bookings(&projects, &registry);
+    let conflicts = staffing::conflicts(&bookings);
+    Ok((bookings, conflicts))
+}
+
+/// Command STAFF PREFILL
+///
+/// Computes `hours.caterers` of the matching working projects from their planned staffing,
+/// nothing is written yet.
+pub fn prefill_hours(search_terms: &[&str], overwrite: bool) -> Result<(Vec<timesheet::Update>, Vec<String>), Error> {
+    let storage = storage::setup::<Project>()?;
+    let registry = EmployeeRegistry::load(&storage)?;
+    let projects = if search_terms.is_empty() {
+        storage.open_projects(StorageDir::Working)?
+    } else {
+        storage.search_projects_any(StorageDir::Working, search_terms)?
+    };
+
+    let mut updates = Vec::new();
+    let mut problems = Vec::new();
+    for project in projects.iter().filter(|p| !p.canceled()) {
+        let hours = staffing::planned_hours(project, &registry);
+        if hours.is_empty() {
+            continue;
+        }
+        let (update, mut project_problems) = timesheet::update_hours(project, &registry, hours, overwrite);
+        updates.extend(update);
+        problems.append(&mut project_problems);
+    }
+    Ok((updates, problems))
+}
+
+/// Command STOCK
+///
+/// Product usage of all past events, see [`stock`](stock/index.html).
+pub fn stock_history() -> Result<Vec<stock::Usage>, Error> {
+    let storage = storage::setup::<Project>()?;
+    let projects = storage.open_projects(StorageDir::All)?;
+    Ok(stock::usages(&projects, Utc::today()))
+}
+
+/// Command STOCK SUGGEST
+pub fn stock_suggestions(search_terms: &[&str]) -> Result<Vec<(Project, Vec<stock::Suggestion>)>, Error> {
+    let usages = stock_history()?;
+    let storage = storage::setup::<Project>()?;
+    let projects = storage.search_projects_any(StorageDir::Working, search_terms)?;
+    let mut suggestions = Vec::new();
+    for project in projects.projects {
+        let project_suggestions = stock::suggest(&project, &usages)?;
+        suggestions.push((project, project_suggestions));
+    }
+    Ok(suggestions)
+}
+
+/// Command STOCK SHOPPING
+pub fn shopping_list(days: i64) -> Result<Vec<stock::ShoppingItem>, Error> {
+    let storage = storage::setup::<Project>()?;
+    let projects = storage.open_projects(StorageDir::Working)?;
+    Ok(stock::shopping_list(&projects, Utc::today(), days))
+}
+
+/// Next number of the invoice sequence, credit notes and deposits take their numbers from it too.
+pub fn next_invoice_number(projects: &[Project]) -> i64 {
+    1 + projects.iter()
+                .flat_map(|p| {
+                    let deposits = p.deposits().unwrap_or_default().into_iter().map(|d| d.number);
+                    vec![p.invoice().number(), p.storno().number()].into_iter().chain(deposits).collect::<Vec<_>>()
+                })
+                .filter_map(|n| n)
+                .max()
+                .unwrap_or(0)
+}
+
+/// Command STORNO
+///
+/// Cancels the invoice of `project` by a credit note, numbered from the invoice sequence of the year of `date`.
+/// Returns the number of the credit note.
+pub fn storno(project: &Project, date: Date<Utc>, reason: Option<&str>) -> Result<i64, Error> {
+    if project.invoice().number().is_none() || project.invoice().date().is_none() {
+        bail!(format_err!("{} has no invoice that could be canceled", project.short_desc()));
+    }
+    if let Some(number) = project.storno().number_str() {
+        bail!(format_err!("{} was already canceled by {}", project.short_desc(), number));
+    }
+
+    let storage = storage::setup::<Project>()?;
+    let projects = storage.open_projects(StorageDir::Year(date.year()))?;
+    let number = next_invoice_number(&projects);
+
+    let mut content = util::yaml::set_value(project.file_content(), "storno/number", &number.to_string())?;
+    content = util::yaml::set_value(&content, "storno/date", &date.format("%d.%m.%Y").to_string())?;
+    if let Some(reason) = reason {
+        content = util::yaml::set_value(&content, "storno/reason", &util::yaml::quote(reason))?;
+    }
+    project.write_content(&content)?;
+    Ok(number)
+}
+
+/// Command CANCEL
+///
+/// Marks `project` as canceled by the client on `date`.
+/// Returns the percentage of the offer that is due as cancellation fee, if any.
+pub fn cancel(project: &Project, date: Date<Utc>, percent: Option<Decimal>, waived: bool) -> Result<Option<Decimal>, Error> {
+    if project.canceled() {
+        bail!(format_err!("{} is already canceled", project.short_desc()));
+    }
+    if let Some(number) = project.invoice().number_str() {
+        bail!(format_err!("{} was already invoiced with {}, cancel the invoice with `storno` first", project.short_desc(), number));
+    }
+
+    let mut content = util::yaml::set_value(project.file_content(), "canceled", "yes")?;
+    content = util::yaml::set_value(&content, "cancellation/date", &date.format("%d.%m.%Y").to_string())?;
+    if let Some(percent) = percent {
+        content = util::yaml::set_value(&content, "cancellation/percent", &percent.to_string())?;
+    }
+    if waived {
+        content = util::yaml::set_value(&content, "cancellation/waived", "yes")?;
+    }
+    project.write_content(&content)?;
+    Ok(Project::from_file_content(&content)?.cancellation_percent())
+}
+
+/// Command OFFER ACCEPT and OFFER REJECT
+///
+/// Notes that the client accepted or rejected the current appendix of the offer of `project` on `date`,
+/// optionally with a `note` why.
+/// Returns that appendix.
+pub fn answer_offer(project: &Project, accepted: bool, date: Date<Utc>, note: Option<&str>) -> Result<i64, Error> {
+    if project.offer().date().is_none() {
+        bail!(format_err!("{} has no offer yet", project.short_desc()));
+    }
+    if let Some(accepted_on) = project.offer().accepted() {
+        bail!(format_err!("the offer of {} was already accepted on {}", project.short_desc(), accepted_on.format("%d.%m.%Y")));
+    }
+    if accepted && !project.state().can_become(State::Accepted) {
+        bail!(format_err!("{} is {} and cannot become accepted", project.short_desc(), project.state()));
+    }
+
+    let appendix = project.offer().appendix().unwrap_or(1);
+    let (date_key, appendix_key) = if accepted {
+        ("offer/accepted", "offer/accepted_appendix")
+    } else {
+        ("offer/rejected", "offer/rejected_appendix")
+    };
+    let mut content = util::yaml::set_value(project.file_content(), date_key, &date.format("%d.%m.%Y").to_string())?;
+    content = util::yaml::set_value(&content, appendix_key, &appendix.to_string())?;
+    if let Some(note) = note {
+        content = util::yaml::set_value(&content, "offer/note", &util::yaml::quote(note))?;
+    }
+    project.write_content(&content)?;
+    Ok(appendix)
+}
+
+/// Command TRANSITION
+///
+/// Moves `project` on to the state `to` by writing the fields that state follows from, dated `date`.
+/// Invoices are numbered like credit notes, canceling works like `cancel` without a fee of its own.
+/// Returns the state the project is in afterwards.
+pub fn transition(project: &Project, to: State, date: Date<Utc>) -> Result<State, Error> {
+    let from = project.state();
+    if !from.can_become(to) {
+        let next = from.next().iter().map(|state| state.name()).collect::<Vec<_>>();
+        bail!(format_err!("{} is {} and cannot become {}, only {}",
+                          project.short_desc(), from, to,
+                          if next.is_empty() { String::from("nothing") } else { next.join(", ") }));
+    }
+
+    let dmy = date.format("%d.%m.%Y").to_string();
+    let content = match to {
+        State::Offered => {
+            let content = util::yaml::set_value(project.file_content(), "offer/date", &dmy)?;
+            if let Err(errors) = Project::from_file_content(&content)?.is_ready_for_offer() {
+                bail!(format_err!("the offer of {} is not complete: {}", project.short_desc(), errors));
+            }
+            content
+        }
+        State::Accepted => {
+            answer_offer(project, true, date, None)?;
+            return Ok(State::Accepted);
+        }
+        State::Invoiced => {
+            let storage = storage::setup::<Project>()?;
+            let projects = storage.open_projects(StorageDir::Year(date.year()))?;
+            let number = next_invoice_number(&projects);
+            let content = util::yaml::set_value(project.file_content(), "invoice/number", &number.to_string())?;
+            util::yaml::set_value(&content, "invoice/date", &dmy)?
+        }
+        State::PartiallyPaid => {
+            let index = project.deposits()?
+                               .iter()
+                               .position(|deposit| deposit.number.is_some() && deposit.payed_date.is_none())
+                               .ok_or_else(|| format_err!("{} has no issued deposit that is still unpayed", project.short_desc()))?;
+            deposit_payed(project, index, date)?;
+            return Ok(State::PartiallyPaid);
+        }
+        State::Paid => util::yaml::set_value(project.file_content(), "invoice/payed_date", &dmy)?,
+        State::WagesPaid => util::yaml::set_value(project.file_content(), "hours/wages_date", &dmy)?,
+        State::Archived => {
+            if let Err(errors) = project.is_ready_for_archive() {
+                bail!(format_err!("{} is not ready for the archive: {}", project.short_desc(), errors));
+            }
+            let year = project.year().ok_or_else(|| format_err!("{} has no date", project.short_desc()))?;
+            storage::setup_with_git::<Project>()?.archive_project(project, year)?;
+            return Ok(State::Archived);
+        }
+        State::Canceled => {
+            cancel(project, date, None, false)?;
+            return Ok(State::Canceled);
+        }
+        State::Stornoed => {
+            storno(project, date, None)?;
+            return Ok(State::Stornoed);
+        }
+        State::Held => bail!(format_err!("{} is held by itself once the event on {} is over",
+                                         project.short_desc(),
+                                         project.event_date().map(|d| d.format("%d.%m.%Y").to_string()).unwrap_or_default())),
+        State::Draft => bail!(format_err!("{} cannot become a draft again", project.short_desc())),
+    };
+    project.write_content(&content)?;
+    Ok(Project::from_file_content(&content)?.state())
+}
+
+/// Reads `extras/exchange_rates.yml`, the table is empty if there is no such file.
+fn rate_table() -> Result<RateTable, Error> {
+    let storage = storage::setup::<Project>()?;
+    let path = storage.get_extra_file(exchange::RATES_FILE)?;
+    if storage.backend().exists(&path) {
+        RateTable::from_yaml_str(&storage.backend().read(&path)?)
+    } else {
+        Ok(RateTable::default())
+    }
+}
+
+/// Command RATE
+///
+/// Stores a snapshot of the exchange rate in the project file, after switching it to `currency` if given.
+/// Without `rate` the latest rate up to `date` is taken from `extras/exchange_rates.yml`.
+/// Returns the currency and its rate, `None` if the project is billed in the home currency.
+pub fn exchange_rate(project: &Project, currency: Option<&str>, rate: Option<Decimal>, date: Date<Utc>) -> Result<Option<(String, ExchangeRate)>, Error> {
+    let mut content = project.file_content().to_owned();
+    if let Some(currency) = currency {
+        content = util::yaml::set_value(&content, "currency", currency)?;
+    }
+    let code = match Project::from_file_content(&content)?.currency() {
+        Some(code) => code.to_owned(),
+        None => {
+            project.write_content(&content)?;
+            return Ok(None);
+        }
+    };
+
+    let rate = match rate {
+        Some(rate) => ExchangeRate { rate, date: Some(date) },
+        None => rate_table()?.rate_at(&code, date)
+                             .ok_or_else(|| format_err!("{} has no rate for {} on {}", exchange::RATES_FILE, code, date.format("%d.%m.%Y")))?
+    };
+    let snapshot = match rate.date {
+        Some(date) => format!("{{ rate: {}, date: {} }}", rate.rate, date.format("%d.%m.%Y")),
+        None => format!("{{ rate: {} }}", rate.rate)
+    };
+    content = util::yaml::set_value(&content, "exchange_rate", &snapshot)?;
+    project.write_content(&content)?;
+    Ok(Some((code, rate)))
+}
+
+/// Writes `deposits` back into the project file, as the `deposits` list.
+fn write_deposits(project: &Project, deposits: &[Deposit<'_>]) -> Result<(), Error> {
+    let mut list = String::new();
+    for deposit in deposits {
+        list += &format!("\n- name: {}", util::yaml::quote(deposit.name));
+        if let Some(number) = deposit.number {
+            list += &format!("\n  number: {}", number);
+        }
+        if let Some(date) = deposit.date {
+            list += &format!("\n  date: {}", date.format("%d.%m.%Y"));
+        }
+        match deposit.value {
+            AdjustmentValue::Percent(percent) => list += &format!("\n  percent: {}", percent),
+            AdjustmentValue::Amount(amount) => list += &format!("\n  amount: {:.2}", Decimal::from(amount)),
+        }
+        if let Some(payed_date) = deposit.payed_date {
+            list += &format!("\n  payed_date: {}", payed_date.format("%d.%m.%Y"));
+        }
+    }
+    let content = util::yaml::set_value(project.file_content(), "deposits", &list)?;
+    project.write_content(&content)
+}
+
+/// Command DEPOSIT
+///
+/// Adds a deposit invoice to `project`, numbered from the invoice sequence of the year of `date`.
+/// Returns the number of the deposit.
+pub fn deposit(project: &Project, value: AdjustmentValue, date: Date<Utc>, name: Option<&str>) -> Result<i64, Error> {
+    if let Some(number) = project.invoice().number_str() {
+        bail!(format_err!("{} was already invoiced with {}", project.short_desc(), number));
+    }
+
+    let storage = storage::setup::<Project>()?;
+    let projects = storage.open_projects(StorageDir::Year(date.year()))?;
+    let number = next_invoice_number(&projects);
+
+    let mut deposits = project.deposits()?;
+    deposits.push(Deposit {
+        name: name.unwrap_or("Deposit"),
+        number: Some(number),
+        date: Some(date),
+        value,
+        payed_date: None,
+    });
+    write_deposits(project, &deposits)?;
+    Ok(number)
+}
+
+/// Command DEPOSIT --payed
+///
+/// Marks the deposit at `index` as payed on `date`.
+pub fn deposit_payed(project: &Project, index: usize, date: Date<Utc>) -> Result<(), Error> {
+    let mut deposits = project.deposits()?;
+    match deposits.get_mut(index) {
+        Some(deposit) => deposit.payed_date = Some(date),
+        None => bail!(format_err!("{} has no deposit {}", project.short_desc(), index + 1))
+    }
+    write_deposits(project, &deposits)
+}
+
+/// Command ATTACH
+///
+/// Copies `file` into the attachments folder of the one project matching `search_terms`
+/// and lists it in the project file, `date` defaults to today.
+pub fn attach(dir: StorageDir, search_terms: &[&str], file: &Path, kind: Option<&str>, date: Option<Date<Utc>>, note: Option<&str>) -> Result<PathBuf, Error> {
+    let storage = storage::setup_with_git::<Project>()?;
+    let mut projects = storage.search_projects_any(dir, search_terms)?;
+    let search_terms = || search_terms.iter().map(ToString::to_string).collect();
+    let project = match projects.len() {
+        0 => bail!(ActionError::NothingFound(search_terms())),
+        1 => projects.remove(0),
+        _ => bail!(ActionError::Ambiguous(search_terms())),
+    };
+
+    let file_name = file.file_name()
+                        .and_then(OsStr::to_str)
+                        .ok_or_else(|| format_err!("{:?} is not a file", file))?;
+    let attachments_dir = project.attachments_dir()
+                                 .ok_or_else(|| format_err!("{} has no folder to attach to", project.short_desc()))?;
+    let target = attachments_dir.join(file_name);
+    let backend = storage.backend();
+    if backend.exists(&target) {
+        bail!(format_err!("{} already exists", target.display()));
+    }
+    if !backend.is_dir(&attachments_dir) {
+        backend.create_dir(&attachments_dir)?;
+    }
+    backend.import(file, &target)?;
+
+    let date = date.unwrap_or_else(Utc::today);
+    let mut entry = format!("  - file: {}\n    date: {}\n", util::yaml::quote(file_name), date.format("%d.%m.%Y"));
+    if let Some(kind) = kind {
+        entry += &format!("    kind: {}\n", util::yaml::quote(kind));
+    }
+    if let Some(note) = note {
+        entry += &format!("    note: {}\n", util::yaml::quote(note));
+    }
+
+    if let Err(e) = project.append_to_list("attachments", &entry) {
+        backend.remove_file(&target)?;
+        return Err(e);
+    }
+
+    if let Some(repo) = storage.repository() {
+        repo.add(&[project.file(), target.clone()]);
+    }
+
+    Ok(target)
 }
 
 /// Clone the repo
@@ -275,3 +967,30 @@ pub fn store_meta() -> Result<(), Error> {
         Err(ActionError::AddingFailed.into())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(name: &str, currency: &str) -> Project {
+        let doc = format!(r#"
+        event:
+          name: {}
+          dates:
+            - begin: 01.06.2019
+        currency: {}
+        hours:
+          salary: 10.0
+          caterers: {{ Hendrik: 2 }}
+        "#, name, currency);
+        Project::from_file_content(&doc).unwrap()
+    }
+
+    #[test]
+    fn lists_projects_without_exchange_rate() {
+        let projects = vec![project("Summer Party", "EUR"), project("Zurich Party", "CHF")];
+        let mut without_rate = Vec::new();
+        assert_eq!(open_wages(&projects, &mut without_rate).value(), 20_00);
+        assert_eq!(without_rate, vec![projects[1].short_desc()]);
+    }
+}
@@ -0,0 +1,241 @@
+//! Settling wages across projects.
+//!
+//! A payroll run collects every project that has been payed by the customer
+//! but whose employees have not been payed yet (`hours.wages_date` is empty).
+//! Hours and wages are totaled per employee, which yields one statement per person.
+//! Settling the payroll sets `hours.wages_date` on all included projects.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use bill::Currency;
+use chrono::prelude::*;
+use failure::Error;
+
+use crate::project::Project;
+use crate::project::spec::*;
+use crate::storage::Storable;
+use crate::util::currency_to_string;
+
+use super::employees::{home_wage, EmployeeRegistry};
+
+/// Work of one employee on one project.
+#[derive(Debug, Clone)]
+pub struct PayrollItem {
+    pub project: String,
+    pub date: Option<Date<Utc>>,
+    pub role: Option<String>,
+    pub time: f64,
+    pub salary: Currency,
+    pub wage: Currency,
+}
+
+/// Everything one employee is owed.
+#[derive(Debug, Clone)]
+pub struct Statement {
+    pub name: String,
+    pub time: f64,
+    pub wage: Currency,
+    pub items: Vec<PayrollItem>,
+}
+
+/// Projects whose wages are due, together with the resulting statements.
+#[derive(Debug)]
+pub struct Payroll {
+    pub projects: Vec<Project>,
+    pub statements: Vec<Statement>,
+}
+
+/// Whether the customer payed, but the employees didn't get their wages yet.
+pub fn wages_due(project: &Project) -> bool {
+    !project.canceled()
+        && project.is_payed()
+        && project.hours().wages_date().is_none()
+        && project.hours().employees().map_or(false, |e| !e.is_empty())
+}
+
+impl Payroll {
+    /// Collects all projects with due wages and totals them per employee.
+    ///
+    /// Caterer names are resolved against `registry`.
+    pub fn from_projects<I: IntoIterator<Item = Project>>(projects: I, registry: &EmployeeRegistry) -> Self {
+        let projects = projects.into_iter()
+                               .filter(wages_due)
+                               .collect::<Vec<Project>>();
+
+        let mut statements: BTreeMap<String, Statement> = BTreeMap::new();
+        for project in &projects {
+            for employee in project.hours().employees().unwrap_or_else(Vec::new) {
+                let name = registry.canonical_name(&employee.name);
+                let wage = home_wage(project, &employee);
+                let salary = project.to_home_currency(employee.salary).unwrap_or(employee.salary);
+                let statement = statements.entry(name.clone())
+                    .or_insert_with(|| Statement {
+                        name,
+                        time: 0f64,
+                        wage: Currency { symbol: wage.symbol, value: 0 },
+                        items: Vec::new(),
+                    });
+                statement.time += employee.time;
+                statement.wage = statement.wage + wage;
+                statement.items.push(PayrollItem {
+                    project: project.short_desc(),
+                    date: project.event_date(),
+                    role: employee.role,
+                    time: employee.time,
+                    salary,
+                    wage,
+                });
+            }
+        }
+
+        Payroll {
+            projects,
+            statements: statements.into_iter().map(|(_, s)| s).collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.projects.is_empty()
+    }
+
+    /// Sum of all wages in this payroll
+    pub fn total(&self) -> Currency {
+        self.statements.iter()
+            .map(|s| s.wage)
+            .fold(Currency::default(), |acc, x| acc + x)
+    }
+
+    /// One line per employee and project, followed by the employee's total.
+    pub fn to_csv(&self) -> Result<String, Error> {
+        let mut string = String::new();
+        let splitter = ";";
+
+        writeln!(&mut string, "{}",
+                 [
+                 lformat!("Name"),
+                 lformat!("Project"),
+                 lformat!("Date"),
+                 lformat!("Role"),
+                 lformat!("Hours"),
+                 lformat!("Salary"),
+                 lformat!("Wage"),
+                 ]
+                 .join(splitter))?;
+
+        for statement in &self.statements {
+            for item in &statement.items {
+                writeln!(&mut string, "{}", [
+                         statement.name.clone(),
+                         item.project.clone(),
+                         item.date.map(|d| d.format("%d.%m.%Y").to_string()).unwrap_or_default(),
+                         item.role.clone().unwrap_or_default(),
+                         item.time.to_string(),
+                         currency_to_string(&item.salary),
+                         currency_to_string(&item.wage),
+                ].join(splitter))?;
+            }
+            writeln!(&mut string, "{}", [
+                     statement.name.clone(),
+                     lformat!("Total"),
+                     String::new(),
+                     String::new(),
+                     statement.time.to_string(),
+                     String::new(),
+                     currency_to_string(&statement.wage),
+            ].join(splitter))?;
+        }
+        Ok(string)
+    }
+
+    /// Statements as they are handed to the payroll template.
+    #[cfg(feature = "serialization")]
+    pub fn export(&self, date: Date<Utc>) -> Vec<ExportStatement> {
+        self.statements.iter()
+            .map(|statement| ExportStatement {
+                name: statement.name.clone(),
+                date: date.format("%d.%m.%Y").to_string(),
+                time: statement.time,
+                wage: currency_to_string(&statement.wage),
+                items: statement.items.iter()
+                    .map(|item| ExportPayrollItem {
+                        project: item.project.clone(),
+                        date: item.date.map(|d| d.format("%d.%m.%Y").to_string()),
+                        role: item.role.clone(),
+                        time: item.time,
+                        salary: currency_to_string(&item.salary),
+                        wage: currency_to_string(&item.wage),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(feature = "serialization")]
+#[derive(Debug, Serialize)]
+pub struct ExportPayrollItem {
+    project: String,
+    date: Option<String>,
+    role: Option<String>,
+    time: f64,
+    salary: String,
+    wage: String,
+}
+
+#[cfg(feature = "serialization")]
+#[derive(Debug, Serialize)]
+pub struct ExportStatement {
+    pub name: String,
+    date: String,
+    time: f64,
+    wage: String,
+    items: Vec<ExportPayrollItem>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(name: &str, payed: bool, wages_date: Option<&str>) -> Project {
+        let doc = format!(r#"
+        event:
+          name: {}
+        invoice:
+          payed_date: {}
+        hours:
+          salary: 8.0
+          wages_date: {}
+          caterers:
+            Alice: 3
+            Bob: {{ hours: 2, rate: 10.0 }}
+        "#, name, if payed { "01.02.2019" } else { "" }, wages_date.unwrap_or(""));
+        Project::from_file_content(&doc).unwrap()
+    }
+
+    #[test]
+    fn totals_due_wages_per_employee() {
+        let payroll = Payroll::from_projects(vec![
+            project("first", true, None),
+            project("second", true, None),
+            project("unpayed", false, None),
+            project("settled", true, Some("03.02.2019")),
+        ], &EmployeeRegistry::default());
+
+        assert_eq!(payroll.projects.len(), 2);
+        assert_eq!(payroll.statements.len(), 2);
+
+        let alice = &payroll.statements[0];
+        assert_eq!(alice.name, "Alice");
+        assert_eq!(alice.time, 6.0);
+        assert_eq!(alice.wage.value(), 48_00);
+        assert_eq!(alice.items.len(), 2);
+
+        let bob = &payroll.statements[1];
+        assert_eq!(bob.wage.value(), 40_00);
+        assert_eq!(payroll.total().value(), 88_00);
+
+        let csv = payroll.to_csv().unwrap();
+        assert_eq!(csv.lines().count(), 1 + 3 + 3);
+    }
+}
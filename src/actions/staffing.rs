@@ -0,0 +1,189 @@
+//! Planning who works which event.
+//!
+//! Planned shifts are listed per project under `staffing` (see [`Assignment`](../../project/spec/struct.Assignment.html)).
+//! Across projects they are checked for people who are booked twice at the same time,
+//! shown as a weekly schedule and exported as one calendar per employee.
+//! Once the event is over the planned hours can pre-fill `hours.caterers`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use icalendar::{Calendar, Component, Event as CalEvent};
+
+use crate::project::Project;
+use crate::project::spec::*;
+use crate::storage::Storable;
+
+use super::employees::EmployeeRegistry;
+
+/// A planned shift together with the project it belongs to.
+#[derive(Debug, Clone)]
+pub struct Booking {
+    pub project: String,
+    /// [`calendar_uid()`](../../project/struct.Project.html#method.calendar_uid) of the project
+    pub project_uid: String,
+    /// Position in the project's `staffing` list
+    pub index: usize,
+    pub assignment: Assignment,
+}
+
+/// Somebody is booked for two shifts at the same time.
+#[derive(Debug, Clone)]
+pub struct Conflict {
+    pub name: String,
+    pub first: Booking,
+    pub second: Booking,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let describe = |booking: &Booking| format!("{} ({} {}-{})",
+                                                   booking.project,
+                                                   booking.assignment.date.format("%d.%m.%Y"),
+                                                   booking.assignment.start.format("%H:%M"),
+                                                   booking.assignment.end.format("%H:%M"));
+        write!(f, "{}", lformat!("{} is booked for {} and {} at the same time",
+                                 self.name, describe(&self.first), describe(&self.second)))
+    }
+}
+
+/// All planned shifts of `projects` in chronological order, names are resolved against `registry`.
+///
+/// Canceled projects are left out.
+pub fn bookings(projects: &[Project], registry: &EmployeeRegistry) -> Vec<Booking> {
+    let mut bookings = projects.iter()
+        .filter(|project| !project.canceled())
+        .flat_map(|project| {
+            let project_name = project.short_desc();
+            let project_uid = project.calendar_uid();
+            project.staffing()
+                   .into_iter()
+                   .enumerate()
+                   .map(move |(index, mut assignment)| {
+                       assignment.name = registry.canonical_name(&assignment.name);
+                       Booking { project: project_name.clone(), project_uid: project_uid.clone(), index, assignment }
+                   })
+        })
+        .collect::<Vec<Booking>>();
+    bookings.sort_by_key(|b| b.assignment.interval().0);
+    bookings
+}
+
+/// Every pair of overlapping shifts of the same person.
+pub fn conflicts(bookings: &[Booking]) -> Vec<Conflict> {
+    let mut conflicts = Vec::new();
+    for (i, first) in bookings.iter().enumerate() {
+        for second in &bookings[i + 1..] {
+            if first.assignment.name == second.assignment.name && first.assignment.overlaps(&second.assignment) {
+                conflicts.push(Conflict {
+                    name: first.assignment.name.clone(),
+                    first: first.clone(),
+                    second: second.clone(),
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+/// Monday of the week that `date` is in.
+pub fn start_of_week(date: Date<Utc>) -> Date<Utc> {
+    date - Duration::days(i64::from(date.weekday().num_days_from_monday()))
+}
+
+/// Shifts of the week starting at `monday`, by day.
+pub fn week<'a>(bookings: &'a [Booking], monday: Date<Utc>) -> BTreeMap<Date<Utc>, Vec<&'a Booking>> {
+    let sunday = monday + Duration::days(6);
+    let mut days: BTreeMap<Date<Utc>, Vec<&Booking>> = BTreeMap::new();
+    for booking in bookings.iter().filter(|b| monday <= b.assignment.date && b.assignment.date <= sunday) {
+        days.entry(booking.assignment.date).or_insert_with(Vec::new).push(booking);
+    }
+    days
+}
+
+/// Calendar of all shifts of one employee.
+pub fn to_ical(bookings: &[Booking], name: &str) -> Calendar {
+    let mut calendar = Calendar::new();
+    for booking in bookings.iter().filter(|b| b.assignment.name == name) {
+        let (start, end) = booking.assignment.interval();
+        let mut event = CalEvent::new();
+        event.add_property("UID", &format!("{}-staffing-{}@asciii", booking.project_uid, booking.index));
+        match booking.assignment.role {
+            Some(ref role) => event.summary(&format!("{} ({})", booking.project, role)),
+            None => event.summary(&booking.project),
+        };
+        event.starts(Utc.from_utc_datetime(&start));
+        event.ends(Utc.from_utc_datetime(&end));
+        calendar.push(event);
+    }
+    calendar
+}
+
+/// Planned hours of `project` per canonical name.
+pub fn planned_hours(project: &Project, registry: &EmployeeRegistry) -> BTreeMap<String, f64> {
+    let mut hours = BTreeMap::new();
+    for (name, planned) in project.planned_hours() {
+        *hours.entry(registry.canonical_name(&name)).or_insert(0f64) += planned;
+    }
+    hours
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(name: &str, date: &str, staffing: &str) -> Project {
+        let doc = format!(r#"
+        event:
+          name: {}
+          dates:
+            - begin: {}
+              times:
+                - {{ begin: "18:00", end: "23:00" }}
+        staffing: {}
+        "#, name, date, staffing);
+        Project::from_file_content(&doc).unwrap()
+    }
+
+    #[test]
+    fn finds_conflicts() {
+        let registry = EmployeeRegistry::from_yaml_str("employees:\n  Alice Smith:\n    aliases: [Alice]\n").unwrap();
+        let projects = vec![
+            project("Party", "03.05.2019", "[{ name: Alice }, { name: Bob, begin: '17:00', end: '01:00' }]"),
+            project("Wedding", "03.05.2019", "[{ name: Alice Smith, begin: '22:00', end: '02:00' }]"),
+            project("Brunch", "04.05.2019", "[{ name: Bob, begin: '00:30', end: '04:00' }, { name: Alice, begin: '09:00', end: '12:00' }]"),
+        ];
+
+        let bookings = bookings(&projects, &registry);
+        assert_eq!(bookings.len(), 5);
+        assert_eq!(bookings[0].assignment.name, "Bob");
+        assert_eq!(bookings[0].assignment.hours(), 8.0);
+
+        let conflicts = conflicts(&bookings);
+        assert_eq!(conflicts.len(), 2);
+        assert_eq!(conflicts[0].name, "Bob");
+        assert_eq!(conflicts[1].name, "Alice Smith");
+        assert_eq!(conflicts[1].second.project, projects[1].short_desc());
+    }
+
+    #[test]
+    fn plans_week_and_hours() {
+        let registry = EmployeeRegistry::default();
+        let projects = vec![
+            project("Party", "03.05.2019", "[{ name: Alice }, { name: Alice, begin: '23:00', end: '00:30' }]"),
+            project("Later", "13.05.2019", "[{ name: Alice }]"),
+        ];
+        let bookings = bookings(&projects, &registry);
+
+        let monday = start_of_week(Utc.ymd(2019, 5, 3));
+        assert_eq!(monday, Utc.ymd(2019, 4, 29));
+        let week = week(&bookings, monday);
+        assert_eq!(week.len(), 1);
+        assert_eq!(week[&Utc.ymd(2019, 5, 3)].len(), 2);
+
+        assert_eq!(planned_hours(&projects[0], &registry)["Alice"], 6.5);
+        assert!(to_ical(&bookings, "Alice").to_string().contains("staffing-1@asciii"));
+    }
+}
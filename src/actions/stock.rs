@@ -0,0 +1,350 @@
+//! Purchasing aid that learns from past events.
+//!
+//! Every product of a finished event tells how much was brought (`amount`)
+//! and how much was actually used (`sold` or `amount - returned`).
+//! From that history amounts for new offers are estimated, per event type if `event/type` is set
+//! and per head if `event/attendees` is known,
+//! and the products of upcoming events are summed up into a shopping list.
+
+use std::collections::BTreeMap;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use failure::Error;
+use log::warn;
+
+use crate::project::Project;
+use crate::project::spec::*;
+use crate::storage::Storable;
+
+/// Products are told apart by name and unit.
+pub type ProductKey = (String, Option<String>);
+
+/// One product at one past event.
+#[derive(Debug, Clone)]
+pub struct Usage {
+    pub project: String,
+    pub date: Date<Utc>,
+    pub event_type: Option<String>,
+    /// Head count that actually came
+    pub attendees: Option<u32>,
+    pub name: String,
+    pub unit: Option<String>,
+    pub offered: f64,
+    pub sold: f64,
+}
+
+impl Usage {
+    pub fn key(&self) -> ProductKey {
+        (self.name.clone(), self.unit.clone())
+    }
+
+    /// What was left over.
+    pub fn returned(&self) -> f64 {
+        (self.offered - self.sold).max(0.0)
+    }
+
+    /// Nothing was left, maybe more could have been sold.
+    pub fn sold_out(&self) -> bool {
+        self.sold >= self.offered
+    }
+
+    /// What one attendee used.
+    pub fn per_head(&self) -> Option<f64> {
+        self.attendees.filter(|&n| n > 0).map(|n| self.sold / f64::from(n))
+    }
+}
+
+/// Product usage of all events before `today`, canceled projects are left out.
+pub fn usages(projects: &[Project], today: Date<Utc>) -> Vec<Usage> {
+    let mut usages = Vec::new();
+    for project in projects.iter().filter(|p| !p.canceled()) {
+        let date = match project.event_date() {
+            Some(date) if date < today => date,
+            _ => continue,
+        };
+        let amounts = match project.product_amounts() {
+            Ok(amounts) => amounts,
+            Err(error) => { warn!("{}: {}", project.short_desc(), error); continue; }
+        };
+        for amounts in amounts.into_iter().filter(|a| a.offered > 0.0 || a.sold > 0.0) {
+            usages.push(Usage {
+                project: project.short_desc(),
+                date,
+                event_type: project.event_type().map(ToOwned::to_owned),
+                attendees: project.attendees().for_invoice(),
+                name: amounts.name,
+                unit: amounts.unit,
+                offered: amounts.offered,
+                sold: amounts.sold,
+            });
+        }
+    }
+    usages.sort_by_key(|u| u.date);
+    usages
+}
+
+/// How one product did across events.
+#[derive(Debug, Clone)]
+pub struct Stats {
+    pub name: String,
+    pub unit: Option<String>,
+    /// Number of events the product was offered at
+    pub events: usize,
+    /// Average amount brought to an event
+    pub offered: f64,
+    /// Average amount used at an event
+    pub sold: f64,
+    /// Everything that was left over
+    pub returned: f64,
+    /// Events at which everything was used up
+    pub sold_out: usize,
+}
+
+impl Stats {
+    /// Share of the brought amount that was left over.
+    pub fn waste(&self) -> f64 {
+        let offered = self.offered * self.events as f64;
+        if offered > 0.0 { self.returned / offered } else { 0.0 }
+    }
+}
+
+fn matches_type(usage: &Usage, event_type: Option<&str>) -> bool {
+    match event_type {
+        Some(event_type) => usage.event_type.as_ref().map(String::as_str) == Some(event_type),
+        None => true
+    }
+}
+
+/// Statistics per product, optionally only of one event type.
+pub fn statistics(usages: &[Usage], event_type: Option<&str>) -> Vec<Stats> {
+    let mut stats: BTreeMap<ProductKey, Stats> = BTreeMap::new();
+    for usage in usages.iter().filter(|u| matches_type(u, event_type)) {
+        let entry = stats.entry(usage.key()).or_insert_with(|| Stats {
+            name: usage.name.clone(),
+            unit: usage.unit.clone(),
+            events: 0,
+            offered: 0.0,
+            sold: 0.0,
+            returned: 0.0,
+            sold_out: 0,
+        });
+        entry.events += 1;
+        entry.offered += usage.offered;
+        entry.sold += usage.sold;
+        entry.returned += usage.returned();
+        if usage.sold_out() {
+            entry.sold_out += 1;
+        }
+    }
+
+    stats.into_iter()
+         .map(|(_, mut stats)| {
+             stats.offered /= stats.events as f64;
+             stats.sold /= stats.events as f64;
+             stats
+         })
+         .collect()
+}
+
+/// Expected use of a product and how many past events that is based on.
+///
+/// Only events of the same type count, unless there are none.
+/// If `attendees` is given and past events know their head count, the use per head is scaled up.
+pub fn estimate(usages: &[Usage], key: &ProductKey, event_type: Option<&str>, attendees: Option<u32>) -> Option<(f64, usize)> {
+    let same_product = usages.iter().filter(|u| u.key() == *key).collect::<Vec<&Usage>>();
+    let same_type = same_product.iter().cloned().filter(|u| matches_type(u, event_type)).collect::<Vec<&Usage>>();
+    let basis = if same_type.is_empty() { same_product } else { same_type };
+    if basis.is_empty() {
+        return None;
+    }
+
+    if let Some(attendees) = attendees {
+        let per_head = basis.iter().filter_map(|u| u.per_head()).collect::<Vec<f64>>();
+        if !per_head.is_empty() {
+            let mean = per_head.iter().sum::<f64>() / per_head.len() as f64;
+            return Some(((mean * f64::from(attendees)).ceil(), per_head.len()));
+        }
+    }
+
+    let mean = basis.iter().map(|u| u.sold).sum::<f64>() / basis.len() as f64;
+    Some((mean.ceil(), basis.len()))
+}
+
+/// Proposed amount of a product for an offer.
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub name: String,
+    pub unit: Option<String>,
+    /// Amount currently in the offer
+    pub planned: Option<f64>,
+    pub suggested: Option<f64>,
+    /// Number of past events the suggestion is based on
+    pub basis: usize,
+}
+
+/// Suggests amounts for the products of `project`.
+///
+/// If the event type is known, products that were used at least at every other event
+/// of this type are suggested as well.
+pub fn suggest(project: &Project, usages: &[Usage]) -> Result<Vec<Suggestion>, Error> {
+    let event_type = project.event_type();
+    let attendees = project.attendees().for_offer();
+    let mut suggestions = Vec::new();
+
+    for amounts in project.product_amounts()? {
+        let key = (amounts.name, amounts.unit);
+        let estimate = estimate(usages, &key, event_type, attendees);
+        suggestions.push(Suggestion {
+            name: key.0,
+            unit: key.1,
+            planned: Some(amounts.offered),
+            suggested: estimate.map(|(amount, _)| amount),
+            basis: estimate.map(|(_, basis)| basis).unwrap_or(0),
+        });
+    }
+
+    if let Some(event_type) = event_type {
+        let mut events = usages.iter()
+                               .filter(|u| matches_type(u, Some(event_type)))
+                               .map(|u| (&u.project, u.date))
+                               .collect::<Vec<_>>();
+        events.dedup();
+
+        for stats in statistics(usages, Some(event_type)) {
+            let known = suggestions.iter().any(|s| s.name == stats.name && s.unit == stats.unit);
+            if !known && stats.events * 2 >= events.len() {
+                let key = (stats.name, stats.unit);
+                let estimate = estimate(usages, &key, Some(event_type), attendees);
+                suggestions.push(Suggestion {
+                    suggested: estimate.map(|(amount, _)| amount),
+                    basis: estimate.map(|(_, basis)| basis).unwrap_or(0),
+                    name: key.0,
+                    unit: key.1,
+                    planned: None,
+                });
+            }
+        }
+    }
+    Ok(suggestions)
+}
+
+/// Everything that needs to be bought of one product.
+#[derive(Debug, Clone)]
+pub struct ShoppingItem {
+    pub name: String,
+    pub unit: Option<String>,
+    pub amount: f64,
+    /// Projects that need the product
+    pub projects: Vec<String>,
+}
+
+/// Offered amounts of all events from `today` until `days` later, grouped by product and unit.
+pub fn shopping_list(projects: &[Project], today: Date<Utc>, days: i64) -> Vec<ShoppingItem> {
+    let last_day = today + Duration::days(days);
+    let mut items: BTreeMap<ProductKey, ShoppingItem> = BTreeMap::new();
+
+    for project in projects.iter().filter(|p| !p.canceled()) {
+        match project.event_date() {
+            Some(date) if today <= date && date <= last_day => {},
+            _ => continue,
+        }
+        let amounts = match project.product_amounts() {
+            Ok(amounts) => amounts,
+            Err(error) => { warn!("{}: {}", project.short_desc(), error); continue; }
+        };
+        for amounts in amounts.into_iter().filter(|a| a.offered > 0.0) {
+            let item = items.entry((amounts.name.clone(), amounts.unit.clone()))
+                            .or_insert_with(|| ShoppingItem { name: amounts.name.clone(), unit: amounts.unit.clone(), amount: 0.0, projects: Vec::new() });
+            item.amount += amounts.offered;
+            item.projects.push(project.short_desc());
+        }
+    }
+    items.into_iter().map(|(_, item)| item).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn project(name: &str, date: &str, event_type: &str, products: &str) -> Project {
+        let doc = format!(r#"
+        event:
+          name: {}
+          type: {}
+          dates:
+            - begin: {}
+        products: {}
+        hours:
+          salary: 8.0
+        "#, name, event_type, date, products);
+        Project::from_file_content(&doc).unwrap()
+    }
+
+    fn projects() -> Vec<Project> {
+        vec![
+            project("First", "03.05.2019", "wedding", r#"{ "Kaffee": { amount: 10, sold: 6, price: 2.5, unit: 1l }, "Kuchen": { amount: 20, price: 0.94, unit: stk } }"#),
+            project("Second", "10.05.2019", "wedding", r#"{ "Kaffee": { amount: 10, returned: 1, price: 2.5, unit: 1l } }"#),
+            project("Third", "17.05.2019", "conference", r#"{ "Kaffee": { amount: 30, sold: 28, price: 2.5, unit: 1l } }"#),
+            project("Next", "25.05.2019", "wedding", r#"{ "Kaffee": { amount: 8, price: 2.5, unit: 1l }, "Wasser": { amount: 4, price: 0.6, unit: 1l } }"#),
+            project("Later", "01.07.2019", "wedding", r#"{ "Kaffee": { amount: 8, price: 2.5, unit: 1l } }"#),
+        ]
+    }
+
+    #[test]
+    fn learns_from_past_events() {
+        let projects = projects();
+        let usages = usages(&projects, Utc.ymd(2019, 5, 20));
+        assert_eq!(usages.len(), 4);
+
+        let stats = statistics(&usages, None);
+        assert_eq!(stats.len(), 2);
+        let coffee = &stats[0];
+        assert_eq!(coffee.name, "Kaffee");
+        assert_eq!(coffee.events, 3);
+        assert_eq!(coffee.returned, 7.0);
+        assert_eq!(coffee.sold_out, 0);
+        assert!((coffee.waste() - 0.14).abs() < 1e-9);
+        assert_eq!(stats[1].sold_out, 1);
+
+        let key = ("Kaffee".to_owned(), Some("1l".to_owned()));
+        assert_eq!(estimate(&usages, &key, Some("wedding"), None), Some((8.0, 2)));
+        assert_eq!(estimate(&usages, &key, Some("birthday"), None), Some((15.0, 3)));
+
+        let suggestions = suggest(&projects[3], &usages).unwrap();
+        assert_eq!(suggestions.len(), 3);
+        assert_eq!(suggestions[0].suggested, Some(8.0));
+        assert_eq!(suggestions[1].name, "Wasser");
+        assert_eq!(suggestions[1].suggested, None);
+        assert_eq!(suggestions[2].name, "Kuchen");
+        assert_eq!(suggestions[2].planned, None);
+        assert_eq!(suggestions[2].suggested, Some(20.0));
+    }
+
+    #[test]
+    fn estimates_per_head() {
+        let mut projects = projects();
+        projects.push(project("Fourth", "18.05.2019", "wedding", r#"{ "Kaffee": { amount: 10, sold: 9, price: 2.5, unit: 1l } }"#));
+        let doc = projects[0].file_content().replace("type: wedding", "type: wedding\n          attendees: 20");
+        projects[0] = Project::from_file_content(&doc).unwrap();
+        let usages = usages(&projects, Utc.ymd(2019, 5, 20));
+        assert_eq!(usages[0].per_head(), Some(0.3));
+
+        let key = ("Kaffee".to_owned(), Some("1l".to_owned()));
+        assert_eq!(estimate(&usages, &key, Some("wedding"), Some(50)), Some((15.0, 1)));
+        assert_eq!(estimate(&usages, &key, Some("wedding"), None), Some((8.0, 3)));
+    }
+
+    #[test]
+    fn sums_up_shopping_list() {
+        let projects = projects();
+        let list = shopping_list(&projects, Utc.ymd(2019, 5, 20), 14);
+        assert_eq!(list.len(), 2);
+        assert_eq!(list[0].name, "Kaffee");
+        assert_eq!(list[0].amount, 8.0);
+        assert_eq!(list[0].projects, vec![projects[3].short_desc()]);
+
+        let list = shopping_list(&projects, Utc.ymd(2019, 5, 20), 60);
+        assert_eq!(list[0].amount, 16.0);
+    }
+}
@@ -0,0 +1,386 @@
+//! Importing caterer hours from time sheets.
+//!
+//! Two formats are understood:
+//!
+//! * CSV with a header line, separated by `;` or `,`.
+//!   Columns are `date` and `name`, plus either `hours` or `start` and `end`,
+//!   an optional `project` column helps when several events happen on the same day.
+//! * iCalendar with one `VEVENT` per shift,
+//!   `SUMMARY` is the name of the employee, `DTSTART` and `DTEND` are the shift
+//!   and `DESCRIPTION` may name the project.
+//!
+//! Shifts are matched to projects by the date of the event, hours are summed up per employee.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use chrono::prelude::*;
+use failure::{bail, format_err, Error};
+use yaml_rust::Yaml;
+
+use crate::project::Project;
+use crate::project::spec::*;
+use crate::storage::Storable;
+use crate::util::{self, ical, yaml};
+
+use super::employees::EmployeeRegistry;
+
+/// Formats a time sheet can be in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Csv,
+    ICal,
+}
+
+impl Format {
+    /// Guesses the format from a file extension, `.ics` is iCalendar, everything else CSV.
+    pub fn from_extension(extension: Option<&str>) -> Self {
+        match extension.map(str::to_lowercase).as_ref().map(String::as_str) {
+            Some("ics") | Some("ical") => Format::ICal,
+            _ => Format::Csv,
+        }
+    }
+}
+
+impl std::str::FromStr for Format {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(Format::Csv),
+            "ics" | "ical" => Ok(Format::ICal),
+            _ => bail!("unknown time sheet format {:?}", s),
+        }
+    }
+}
+
+/// One shift of one employee.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shift {
+    /// Line (CSV) or event (iCalendar) the shift was read from, starting at `1`
+    pub line: usize,
+    pub employee: String,
+    pub date: Date<Utc>,
+    pub hours: f64,
+    /// Name or part of the name of the project, if given
+    pub project: Option<String>,
+}
+
+/// A row that could not be imported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub line: usize,
+    pub description: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", lformat!("line {}: {}", self.line, self.description))
+    }
+}
+
+fn problem(line: usize, description: String) -> Problem {
+    Problem { line, description }
+}
+
+/// Reads shifts, rows that can't be read are returned as problems.
+pub fn parse(content: &str, format: Format) -> Result<(Vec<Shift>, Vec<Problem>), Error> {
+    match format {
+        Format::Csv => parse_csv(content),
+        Format::ICal => Ok(parse_ical(content)),
+    }
+}
+
+fn hours_between(start: NaiveTime, end: NaiveTime) -> f64 {
+    let mut minutes = end.signed_duration_since(start).num_minutes();
+    if minutes <= 0 {
+        // shift ends after midnight
+        minutes += 24 * 60;
+    }
+    minutes as f64 / 60f64
+}
+
+fn parse_csv(content: &str) -> Result<(Vec<Shift>, Vec<Problem>), Error> {
+    let mut lines = content.lines().enumerate().filter(|(_, l)| !l.trim().is_empty());
+    let (_, header) = lines.next().ok_or_else(|| format_err!("empty time sheet"))?;
+    let separator = if header.contains(';') { ';' } else { ',' };
+
+    let columns = header.split(separator)
+                        .map(|c| c.trim().trim_matches('"').to_lowercase())
+                        .collect::<Vec<String>>();
+    let column = |names: &[&str]| columns.iter().position(|c| names.contains(&c.as_str()));
+
+    let date_column = column(&["date", "datum"]).ok_or_else(|| format_err!("time sheet has no date column"))?;
+    let name_column = column(&["name", "employee", "caterer"]).ok_or_else(|| format_err!("time sheet has no name column"))?;
+    let hours_column = column(&["hours", "stunden"]);
+    let start_column = column(&["start", "begin"]);
+    let end_column = column(&["end", "ende"]);
+    let project_column = column(&["project", "event"]);
+
+    let mut shifts = Vec::new();
+    let mut problems = Vec::new();
+
+    for (index, line) in lines {
+        let line_number = index + 1;
+        let fields = line.split(separator)
+                         .map(|f| f.trim().trim_matches('"'))
+                         .collect::<Vec<&str>>();
+        let field = |column: Option<usize>| column.and_then(|c| fields.get(c)).cloned().filter(|f| !f.is_empty());
+
+        let date = match field(Some(date_column)).and_then(yaml::parse_dmy_date) {
+            Some(date) => date,
+            None => { problems.push(problem(line_number, lformat!("invalid date"))); continue; }
+        };
+        let employee = match field(Some(name_column)) {
+            Some(name) => name.to_owned(),
+            None => { problems.push(problem(line_number, lformat!("no name"))); continue; }
+        };
+
+        let hours = field(hours_column)
+            .and_then(|h| h.replace(',', ".").parse::<f64>().ok())
+            .or_else(|| {
+                let start = field(start_column).and_then(util::naive_time_from_str)?;
+                let end = field(end_column).and_then(util::naive_time_from_str)?;
+                Some(hours_between(start, end))
+            });
+        let hours = match hours {
+            Some(hours) if hours > 0f64 => hours,
+            _ => { problems.push(problem(line_number, lformat!("no hours for {}", employee))); continue; }
+        };
+
+        shifts.push(Shift {
+            line: line_number,
+            employee,
+            date,
+            hours,
+            project: field(project_column).map(ToOwned::to_owned),
+        });
+    }
+
+    Ok((shifts, problems))
+}
+
+fn parse_ical(content: &str) -> (Vec<Shift>, Vec<Problem>) {
+    let mut shifts = Vec::new();
+    let mut problems = Vec::new();
+
+    for (index, properties) in ical::parse_components(content, "VEVENT").iter().enumerate() {
+        let event_number = index + 1;
+        if properties.get("STATUS").map(String::as_str) == Some("CANCELLED") {
+            continue;
+        }
+        let start = properties.get("DTSTART").and_then(|v| ical::parse_date_time(v));
+        let end = properties.get("DTEND").and_then(|v| ical::parse_date_time(v));
+        let employee = properties.get("SUMMARY").map(|s| ical::unescape(s));
+        match (employee, start, end) {
+            (Some(employee), Some(start), Some(end)) if end > start => shifts.push(Shift {
+                line: event_number,
+                employee,
+                date: Utc.from_utc_date(&start.date()),
+                hours: end.signed_duration_since(start).num_minutes() as f64 / 60f64,
+                project: properties.get("DESCRIPTION")
+                                   .map(|d| ical::unescape(d).replace('\n', " "))
+                                   .filter(|d| !d.is_empty()),
+            }),
+            (None, _, _) => problems.push(problem(event_number, lformat!("event without summary"))),
+            _ => problems.push(problem(event_number, lformat!("event without start and end time"))),
+        }
+    }
+
+    (shifts, problems)
+}
+
+/// New hours for one project.
+#[derive(Debug, Clone)]
+pub struct Update {
+    pub project: Project,
+    /// Hours per caterer as they will be written
+    pub hours: BTreeMap<String, f64>,
+    /// New content of the project file
+    pub content: String,
+}
+
+impl Update {
+    /// Changed lines of the project file.
+    pub fn diff(&self) -> Vec<String> {
+        util::diff_lines(self.project.file_content(), &self.content)
+    }
+
+    /// Writes the new content into the project file.
+    pub fn apply(&self) -> Result<(), Error> {
+        self.project.write_content(&self.content)
+    }
+}
+
+/// Result of matching shifts against projects.
+#[derive(Debug, Clone, Default)]
+pub struct Import {
+    pub updates: Vec<Update>,
+    pub problems: Vec<Problem>,
+}
+
+fn takes_place_on(project: &Project, date: Date<Utc>) -> bool {
+    project.events()
+           .unwrap_or_else(Vec::new)
+           .iter()
+           .any(|event| event.begin <= date && date <= event.end.unwrap_or(event.begin))
+}
+
+fn is_named(project: &Project, hint: &str) -> bool {
+    let hint = hint.to_lowercase();
+    project.name()
+           .map(str::to_lowercase)
+           .map_or(false, |name| name.contains(&hint) || hint.contains(&name))
+}
+
+fn format_hours(hours: f64) -> String {
+    format!("{}", (hours * 100f64).round() / 100f64)
+}
+
+/// Computes the new content of a project file with `hours` per caterer.
+///
+/// Caterers that are listed under an alias keep that name.
+/// Caterers that already have different hours are left alone and reported, unless `overwrite` is set.
+/// Returns `None` if nothing changes.
+pub fn update_hours(project: &Project, registry: &EmployeeRegistry, hours: BTreeMap<String, f64>, overwrite: bool) -> (Option<Update>, Vec<String>) {
+    let caterers = yaml::get_hash(project.yaml(), "hours/caterers");
+    let mut content = project.file_content().to_owned();
+    let mut written = BTreeMap::new();
+    let mut problems = Vec::new();
+
+    for (name, new_hours) in hours {
+        // caterers may be listed under an alias
+        let existing = caterers.and_then(|c| c.iter().find(|(key, _)| {
+            key.as_str().map_or(false, |key| registry.canonical_name(key) == name)
+        }));
+        let key = existing.and_then(|(key, _)| key.as_str()).unwrap_or(&name).to_owned();
+        let value = format_hours(new_hours);
+
+        let path = match existing.map(|(_, entry)| entry) {
+            Some(Yaml::Hash(entry)) => {
+                let old = entry.get(&Yaml::String("hours".into())).and_then(|h| h.as_f64().or_else(|| h.as_i64().map(|i| i as f64)));
+                if let Some(old) = old.filter(|&old| old > 0f64 && format_hours(old) != value) {
+                    if !overwrite {
+                        problems.push(lformat!("{} already has {}h in {}, not {}h", key, old, project.short_desc(), value));
+                        continue;
+                    }
+                }
+                vec!["hours", "caterers", key.as_str(), "hours"]
+            },
+            Some(entry) => {
+                let old = entry.as_f64().or_else(|| entry.as_i64().map(|i| i as f64)).unwrap_or(0f64);
+                if old > 0f64 && format_hours(old) != value && !overwrite {
+                    problems.push(lformat!("{} already has {}h in {}, not {}h", key, old, project.short_desc(), value));
+                    continue;
+                }
+                vec!["hours", "caterers", key.as_str()]
+            },
+            None => vec!["hours", "caterers", key.as_str()],
+        };
+
+        match yaml::set_value_at(&content, &path, &value) {
+            Ok(changed) => {
+                content = changed;
+                written.insert(key, new_hours);
+            },
+            Err(e) => problems.push(e.to_string()),
+        }
+    }
+
+    let update = if content != project.file_content() {
+        Some(Update { project: project.clone(), hours: written, content })
+    } else {
+        None
+    };
+    (update, problems)
+}
+
+/// Matches `shifts` to `projects` and computes the new content of every affected project file.
+///
+/// Shifts that match no project or more than one are reported as problems.
+/// So are caterers that already have different hours in the project file, unless `overwrite` is set.
+pub fn import(projects: &[Project], registry: &EmployeeRegistry, shifts: &[Shift], overwrite: bool) -> Import {
+    let mut import = Import::default();
+    let mut hours_by_project: BTreeMap<usize, (usize, BTreeMap<String, f64>)> = BTreeMap::new();
+
+    for shift in shifts {
+        let candidates = projects.iter()
+            .enumerate()
+            .filter(|(_, p)| !p.canceled() && takes_place_on(p, shift.date))
+            .filter(|(_, p)| shift.project.as_ref().map_or(true, |hint| is_named(p, hint)))
+            .map(|(index, _)| index)
+            .collect::<Vec<usize>>();
+
+        match candidates.as_slice() {
+            [index] => {
+                let (_, hours) = hours_by_project.entry(*index).or_insert((shift.line, BTreeMap::new()));
+                *hours.entry(registry.canonical_name(&shift.employee)).or_insert(0f64) += shift.hours;
+            },
+            [] => import.problems.push(problem(shift.line, lformat!("no project on {} for {}", shift.date.format("%d.%m.%Y"), shift.employee))),
+            _ => import.problems.push(problem(shift.line, lformat!("{} projects on {}, please add a project column", candidates.len(), shift.date.format("%d.%m.%Y")))),
+        }
+    }
+
+    for (index, (line, hours)) in hours_by_project {
+        let (update, problems) = update_hours(&projects[index], registry, hours, overwrite);
+        import.updates.extend(update);
+        import.problems.extend(problems.into_iter().map(|description| problem(line, description)));
+    }
+
+    import
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static PROJECT: &str = r#"event:
+  name: Summer Party
+  dates:
+    - begin: 01.05.2019
+hours:
+  salary: 8.0
+  caterers:
+    "unknown" : 0
+    Alice: 2
+"#;
+
+    #[test]
+    fn reads_csv() {
+        let csv = "Date;Name;Start;End\n01.05.2019;Alice;18:00;23:30\n01.05.2019;Bob;22:00;01:00\n32.05.2019;Carl;1;2\n";
+        let (shifts, problems) = parse(csv, Format::Csv).unwrap();
+        assert_eq!(shifts.len(), 2);
+        assert_eq!(shifts[0].hours, 5.5);
+        assert_eq!(shifts[1].hours, 3.0);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].line, 4);
+    }
+
+    #[test]
+    fn reads_ical() {
+        let ics = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Alice\r\nDTSTART:20190501T180000\r\nDTEND:20190501T220000\r\nEND:VEVENT\r\nBEGIN:VEVENT\r\nSUMMARY:Bob\r\nDTSTART;TZID=Europe/Berlin:20190501T200000\r\nDTEND;TZID=Europe/Berlin:20190502T010000\r\nDESCRIPTION:Summer\r\n  Party\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+        let (shifts, problems) = parse(ics, Format::ICal).unwrap();
+        assert!(problems.is_empty());
+        assert_eq!(shifts.len(), 2);
+        assert_eq!(shifts[0].hours, 4.0);
+        assert_eq!(shifts[1].hours, 5.0);
+        assert_eq!(shifts[1].project.as_ref().map(String::as_str), Some("Summer Party"));
+    }
+
+    #[test]
+    fn matches_shifts_to_projects() {
+        let projects = vec![Project::from_file_content(PROJECT).unwrap()];
+        let csv = "date,name,hours,project\n01.05.2019,Alice,4,summer\n01.05.2019,Bob,3,\n01.05.2019,Bob,1.5,\n02.05.2019,Carl,2,\n";
+        let (shifts, _) = parse(csv, Format::Csv).unwrap();
+
+        let import = import(&projects, &EmployeeRegistry::default(), &shifts, false);
+        assert_eq!(import.problems.len(), 2); // Alice has 2h already, nothing on the 2nd
+        assert_eq!(import.updates.len(), 1);
+        let update = &import.updates[0];
+        assert_eq!(update.hours.get("Bob"), Some(&4.5));
+        assert_eq!(update.diff(), vec!["  10 +     Bob: 4.5"]);
+
+        let overwritten = super::import(&projects, &EmployeeRegistry::default(), &shifts, true);
+        let content = yaml::parse(&overwritten.updates[0].content).unwrap();
+        assert_eq!(yaml::get_f64(&content, "hours/caterers/Alice"), Some(4.0));
+    }
+}
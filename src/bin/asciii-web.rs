@@ -87,6 +87,32 @@ pub mod api {
         name: String
     }
 
+    #[derive(Deserialize, Debug)]
+    pub struct AttachmentRequest {
+        name: String,
+        file: String
+    }
+
+    #[derive(Deserialize, Debug)]
+    pub struct TokenRequest {
+        token: String
+    }
+
+    /// Subscribable calendar, see `ProjectLoader::calendar()`
+    #[get("/calendar.ics")]
+    pub fn calendar(query: web::Query<TokenRequest>) -> HttpResponse {
+        info!("calendar.ics");
+        self::CHANNEL.send(()).unwrap();
+        let loader = self::PROJECTS.lock().unwrap();
+        match loader.calendar(&query.token) {
+            Some(Ok(calendar)) => HttpResponse::Ok()
+                .content_type("text/calendar; charset=utf-8")
+                .body(calendar),
+            Some(Err(e)) => HttpResponse::InternalServerError().body(e.to_string()),
+            None => HttpResponse::Forbidden().finish(),
+        }
+    }
+
     #[get("/version")]
     pub fn version() -> HttpResponse {
         let version: &str = asciii::VERSION_JSON.as_ref();
@@ -120,6 +146,27 @@ pub mod api {
             HttpResponse::Ok().json(exported)
         }
 
+        #[get("/{name}/history")]
+        pub fn history(param: web::Path<NameRequest>) -> HttpResponse {
+            info!("history({:?})", param.name);
+            let loader = self::PROJECTS.lock().unwrap();
+            match loader.history(&param.name) {
+                Some(Ok(history)) => HttpResponse::Ok().json(history),
+                Some(Err(e)) => HttpResponse::InternalServerError().body(e.to_string()),
+                None => HttpResponse::NotFound().finish(),
+            }
+        }
+
+        #[get("/{name}/attachments/{file}")]
+        pub fn attachment(param: web::Path<AttachmentRequest>) -> Result<fs::NamedFile, Error> {
+            info!("attachment({:?}, {:?})", param.name, param.file);
+            let loader = self::PROJECTS.lock().unwrap();
+            match loader.attachment(&param.name, &param.file) {
+                Some(path) => Ok(fs::NamedFile::open(path)?),
+                None => Err(actix_web::error::ErrorNotFound("no such attachment")),
+            }
+        }
+
          #[get("/{name}")]
         pub fn by_name(param: web::Path<NameRequest>) -> HttpResponse {
             info!("by_name({:?})", param.name);
@@ -228,6 +275,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .service(api::projects::years)
                     .service(api::projects::by_year)
                     .service(api::projects::working_dir)
+                    .service(api::projects::history)
+                    .service(api::projects::attachment)
                     .service(api::projects::by_name)
                 )
                 .service(web::scope("full_projects")
@@ -236,6 +285,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                     .service(api::projects::years)
                 )
             )
+            .service(api::calendar)
             // .service(fs::Files::new("/", "webapp/public").index_file("index.html"))
             .service(
                 web::resource("/").route(
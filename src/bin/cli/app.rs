@@ -1,4 +1,6 @@
 use asciii;
+use asciii::util::decimal::Decimal;
+use asciii::project::state::State;
 use clap::{App, AppSettings, Arg, ArgGroup, ArgMatches, SubCommand, Shell};
 use failure::{Error, format_err};
 use log::{info, error};
@@ -46,7 +48,14 @@ pub fn with_cli<F> (app_handler:F) where F: Fn(App<'_, '_>) {
 
                         .arg(Arg::with_name("name")
                              .help(lformat!("Project name").as_ref())
-                             .required(true))
+                             .required_unless("from-ics"))
+
+                        .arg(Arg::with_name("from-ics")
+                             .help(lformat!("Create projects from the events of an iCalendar file, e.g. an invite").as_ref())
+                             .long("from-ics")
+                             .value_name("file")
+                             .conflicts_with("name")
+                             .takes_value(true))
 
                         .arg(Arg::with_name("date")
                              .help(lformat!("Manually set the date of the project").as_ref())
@@ -352,6 +361,48 @@ pub fn with_cli<F> (app_handler:F) where F: Fn(App<'_, '_>) {
                             )
                        )
 
+            .subcommand(SubCommand::with_name("attach")
+                        .about(lformat!("Attach a document to a project").as_ref())
+                        .arg(Arg::with_name("search_term")
+                             .help(lformat!("Search term, possibly event name").as_ref())
+                             .required(true)
+                            )
+                        .arg(Arg::with_name("file")
+                             .help(lformat!("The document to attach").as_ref())
+                             .required(true)
+                            )
+                        .arg(Arg::with_name("kind")
+                             .help(lformat!("What kind of document this is, e.g. receipt or contract").as_ref())
+                             .long("kind")
+                             .short("k")
+                             .takes_value(true)
+                            )
+                        .arg(Arg::with_name("date")
+                             .help(lformat!("Date of the document, defaults to today").as_ref())
+                             .long("date")
+                             .short("d")
+                             .validator(validators::is_dmy)
+                             .takes_value(true)
+                            )
+                        .arg(Arg::with_name("note")
+                             .help(lformat!("A note about the document").as_ref())
+                             .long("note")
+                             .short("n")
+                             .takes_value(true)
+                            )
+                        .arg(Arg::with_name("archive")
+                             .help(lformat!("attach to an archived project").as_ref())
+                             .short("a")
+                             .long("archive")
+                             .min_values(0)
+                             .takes_value(true)
+                            )
+                       )
+
+            .subcommand(SubCommand::with_name("undo")
+                        .about(lformat!("Revert the last archive, unarchive or delete").as_ref())
+                       )
+
             .subcommand(SubCommand::with_name("show")
                         .aliases(&["display"])
                         .about(lformat!("Display a specific project").as_ref())
@@ -469,6 +520,252 @@ pub fn with_cli<F> (app_handler:F) where F: Fn(App<'_, '_>) {
                             )
                         )
 
+            .subcommand(SubCommand::with_name("storno")
+                        .aliases(&["credit-note", "cancel-invoice"])
+                        .about(lformat!("Cancel the invoice of a project by a credit note").as_ref())
+                        .arg(Arg::with_name("search_term")
+                             .help(lformat!("Search term, possibly event name").as_ref())
+                             .required(true)
+                            )
+
+                        .arg(Arg::with_name("reason")
+                             .help(lformat!("Why the invoice is canceled, printed on the credit note").as_ref())
+                             .long("reason")
+                             .short("r")
+                             .takes_value(true)
+                            )
+
+                        .arg(Arg::with_name("date")
+                             .help(lformat!("Date of the credit note, defaults to today").as_ref())
+                             .long("date")
+                             .short("d")
+                             .takes_value(true)
+                             .validator(validators::is_dmy)
+                            )
+
+                        .arg(Arg::with_name("archive")
+                             .help(lformat!("Pick an archived project").as_ref())
+                             .short("a")
+                             .long("archive")
+                             .min_values(0)
+                             .takes_value(true)
+                            )
+                        )
+
+            .subcommand(SubCommand::with_name("cancel")
+                        .about(lformat!("Mark a project as canceled by the client, a fee may be due").as_ref())
+                        .arg(Arg::with_name("search_term")
+                             .help(lformat!("Search term, possibly event name").as_ref())
+                             .required(true)
+                            )
+
+                        .arg(Arg::with_name("date")
+                             .help(lformat!("When the client canceled, defaults to today").as_ref())
+                             .long("date")
+                             .short("d")
+                             .takes_value(true)
+                             .validator(validators::is_dmy)
+                            )
+
+                        .arg(Arg::with_name("percent")
+                             .help(lformat!("Fee as a percentage of the offer, instead of the configured schedule").as_ref())
+                             .long("percent")
+                             .short("p")
+                             .takes_value(true)
+                             .validator(|p| p.parse::<f64>().map(|_ok|()).map_err(|e|e.to_string()))
+                            )
+
+                        .arg(Arg::with_name("free")
+                             .help(lformat!("Waive the cancellation fee").as_ref())
+                             .long("free")
+                             .conflicts_with("percent")
+                            )
+                        )
+
+            .subcommand(SubCommand::with_name("transition")
+                        .aliases(&["state"])
+                        .about(lformat!("Move a project on to its next state, e.g. accepted or paid").as_ref())
+                        .arg(Arg::with_name("search_term")
+                             .help(lformat!("Search term, possibly event name").as_ref())
+                             .required(true)
+                            )
+
+                        .arg(Arg::with_name("state")
+                             .help(lformat!("The new state, leave out to see the current one: draft, offered, accepted, held, invoiced, partially_paid, paid, wages_paid, archived, canceled, stornoed").as_ref())
+                             .validator(|s| s.parse::<State>().map(|_ok|()).map_err(|e|e.to_string()))
+                            )
+
+                        .arg(Arg::with_name("date")
+                             .help(lformat!("Date of the transition, defaults to today").as_ref())
+                             .long("date")
+                             .short("d")
+                             .takes_value(true)
+                             .validator(validators::is_dmy)
+                            )
+                        )
+
+            .subcommand(SubCommand::with_name("offer")
+                        .aliases(&["angebot"])
+                        .about(lformat!("Note the answer of the client and compare the versions of an offer").as_ref())
+                        .subcommand(SubCommand::with_name("accept")
+                                    .about(lformat!("The client accepted the current version of the offer").as_ref())
+                                    .arg(Arg::with_name("search_term")
+                                         .help(lformat!("Search term, possibly event name").as_ref())
+                                         .required(true)
+                                        )
+                                    .arg(Arg::with_name("date")
+                                         .help(lformat!("When the client accepted, defaults to today").as_ref())
+                                         .long("date")
+                                         .short("d")
+                                         .takes_value(true)
+                                         .validator(validators::is_dmy)
+                                        )
+                                    .arg(Arg::with_name("note")
+                                         .help(lformat!("What was agreed on").as_ref())
+                                         .long("note")
+                                         .short("n")
+                                         .takes_value(true)
+                                        )
+                                   )
+                        .subcommand(SubCommand::with_name("reject")
+                                    .about(lformat!("The client turned the current version of the offer down").as_ref())
+                                    .arg(Arg::with_name("search_term")
+                                         .help(lformat!("Search term, possibly event name").as_ref())
+                                         .required(true)
+                                        )
+                                    .arg(Arg::with_name("date")
+                                         .help(lformat!("When the client rejected, defaults to today").as_ref())
+                                         .long("date")
+                                         .short("d")
+                                         .takes_value(true)
+                                         .validator(validators::is_dmy)
+                                        )
+                                    .arg(Arg::with_name("note")
+                                         .help(lformat!("Why the offer was rejected").as_ref())
+                                         .long("note")
+                                         .short("n")
+                                         .takes_value(true)
+                                        )
+                                   )
+                        .subcommand(SubCommand::with_name("versions")
+                                    .aliases(&["list"])
+                                    .about(lformat!("List every exported version of the offer").as_ref())
+                                    .arg(Arg::with_name("search_term")
+                                         .help(lformat!("Search term, possibly event name").as_ref())
+                                         .required(true)
+                                        )
+                                   )
+                        .subcommand(SubCommand::with_name("diff")
+                                    .about(lformat!("Show what changed between two versions of the offer, the latest two by default").as_ref())
+                                    .arg(Arg::with_name("search_term")
+                                         .help(lformat!("Search term, possibly event name").as_ref())
+                                         .required(true)
+                                        )
+                                    .arg(Arg::with_name("from")
+                                         .help(lformat!("Appendix of the older version").as_ref())
+                                         .validator(|a| a.parse::<i64>().map(|_ok|()).map_err(|e|e.to_string()))
+                                        )
+                                    .arg(Arg::with_name("to")
+                                         .help(lformat!("Appendix of the newer version, the latest by default").as_ref())
+                                         .validator(|a| a.parse::<i64>().map(|_ok|()).map_err(|e|e.to_string()))
+                                        )
+                                    .arg(Arg::with_name("invoice")
+                                         .help(lformat!("Compare the accepted offer with the invoice").as_ref())
+                                         .long("invoice")
+                                         .short("i")
+                                         .conflicts_with_all(&["from", "to"])
+                                        )
+                                   )
+                       )
+
+            .subcommand(SubCommand::with_name("rate")
+                        .aliases(&["exchange-rate", "kurs"])
+                        .about(lformat!("Store the exchange rate of a project billed in a foreign currency").as_ref())
+                        .arg(Arg::with_name("search_term")
+                             .help(lformat!("Search term, possibly event name").as_ref())
+                             .required(true)
+                            )
+
+                        .arg(Arg::with_name("currency")
+                             .help(lformat!("Bill the project in this currency, e.g. CHF").as_ref())
+                             .long("currency")
+                             .short("c")
+                             .takes_value(true)
+                            )
+
+                        .arg(Arg::with_name("rate")
+                             .help(lformat!("What one unit of the currency is worth in the home currency, instead of looking it up in extras/exchange_rates.yml").as_ref())
+                             .long("rate")
+                             .short("r")
+                             .takes_value(true)
+                             .validator(|r| r.parse::<Decimal>().map(|_ok|()).map_err(|e|e.to_string()))
+                            )
+
+                        .arg(Arg::with_name("date")
+                             .help(lformat!("Date of the rate, defaults to today").as_ref())
+                             .long("date")
+                             .short("d")
+                             .takes_value(true)
+                             .validator(validators::is_dmy)
+                            )
+                        )
+
+            .subcommand(SubCommand::with_name("deposit")
+                        .aliases(&["abschlag", "advance"])
+                        .about(lformat!("Add a deposit invoice to a project, or mark one as payed").as_ref())
+                        .arg(Arg::with_name("search_term")
+                             .help(lformat!("Search term, possibly event name").as_ref())
+                             .required(true)
+                            )
+
+                        .arg(Arg::with_name("percent")
+                             .help(lformat!("Deposit as a percentage of the offer").as_ref())
+                             .long("percent")
+                             .short("p")
+                             .takes_value(true)
+                             .validator(|p| p.parse::<f64>().map(|_ok|()).map_err(|e|e.to_string()))
+                            )
+
+                        .arg(Arg::with_name("amount")
+                             .help(lformat!("Deposit as a fixed amount before tax").as_ref())
+                             .long("amount")
+                             .takes_value(true)
+                             .conflicts_with("percent")
+                             .validator(|a| a.parse::<f64>().map(|_ok|()).map_err(|e|e.to_string()))
+                            )
+
+                        .arg(Arg::with_name("name")
+                             .help(lformat!("How the deposit is called on the invoices").as_ref())
+                             .long("name")
+                             .short("n")
+                             .takes_value(true)
+                            )
+
+                        .arg(Arg::with_name("payed")
+                             .help(lformat!("Mark the deposit with this number (1, 2, ...) as payed").as_ref())
+                             .long("payed")
+                             .takes_value(true)
+                             .conflicts_with_all(&["percent", "amount", "name"])
+                             .validator(|n| n.parse::<usize>().map(|_ok|()).map_err(|e|e.to_string()))
+                            )
+
+                        .arg(Arg::with_name("date")
+                             .help(lformat!("Date of the deposit invoice or payment, defaults to today").as_ref())
+                             .long("date")
+                             .short("d")
+                             .takes_value(true)
+                             .validator(validators::is_dmy)
+                            )
+
+                        .arg(Arg::with_name("archive")
+                             .help(lformat!("Pick an archived project").as_ref())
+                             .short("a")
+                             .long("archive")
+                             .min_values(0)
+                             .takes_value(true)
+                            )
+                        )
+
             .subcommand(SubCommand::with_name("invoice")
                         .about(lformat!("Assign invoice id to project").as_ref())
                         .arg(Arg::with_name("search_term")
@@ -556,6 +853,16 @@ pub fn with_cli<F> (app_handler:F) where F: Fn(App<'_, '_>) {
                             )
                         )
 
+            .subcommand(SubCommand::with_name("remind")
+                        .aliases(&["reminders", "todo"])
+                        .about(lformat!("Shows what needs to be done, according to the reminder rules in the config").as_ref())
+                        .arg(Arg::with_name("digest")
+                             .help(lformat!("Print a plain text digest of all reminders, e.g. for an email").as_ref())
+                             .short("d")
+                             .long("digest")
+                            )
+                       )
+
             .subcommand(SubCommand::with_name("calendar")
                         .aliases(&["cal","ical","ics","kalender"])
                         .arg(Arg::with_name("archive")
@@ -604,6 +911,150 @@ pub fn with_cli<F> (app_handler:F) where F: Fn(App<'_, '_>) {
                             )
                        )
 
+            .subcommand(SubCommand::with_name("hours")
+                        .about(lformat!("Manage caterer hours").as_ref())
+                        .setting(AppSettings::SubcommandRequiredElseHelp)
+                        .subcommand(SubCommand::with_name("import")
+                                    .about(lformat!("Import hours from a time sheet (CSV or iCalendar)").as_ref())
+                                    .arg(Arg::with_name("file")
+                                         .help(lformat!("The time sheet").as_ref())
+                                         .required(true)
+                                        )
+                                    .arg(Arg::with_name("format")
+                                         .help(lformat!("Format of the time sheet, guessed from the extension otherwise").as_ref())
+                                         .long("format")
+                                         .short("f")
+                                         .possible_values(&["csv", "ics"])
+                                         .takes_value(true)
+                                        )
+                                    .arg(Arg::with_name("overwrite")
+                                         .help(lformat!("Replace hours that are already in the project").as_ref())
+                                         .long("overwrite")
+                                        )
+                                    .arg(Arg::with_name("dry-run")
+                                         .help(lformat!("Only show the changes").as_ref())
+                                         .long("dry-run")
+                                         .short("n")
+                                        )
+                                    .arg(Arg::with_name("yes")
+                                         .help(lformat!("Don't ask for confirmation").as_ref())
+                                         .long("yes")
+                                         .short("y")
+                                        )
+                                   )
+                       )
+
+            .subcommand(SubCommand::with_name("staff")
+                        .aliases(&["staffing", "schedule"])
+                        .about(lformat!("Plan who works which event").as_ref())
+                        .subcommand(SubCommand::with_name("week")
+                                    .about(lformat!("Show the planned shifts of one week, this week by default").as_ref())
+                                    .arg(Arg::with_name("date")
+                                         .help(lformat!("Any day of the week").as_ref())
+                                         .validator(validators::is_dmy)
+                                        )
+                                   )
+                        .subcommand(SubCommand::with_name("ical")
+                                    .about(lformat!("Calendar with the planned shifts of one employee").as_ref())
+                                    .arg(Arg::with_name("name")
+                                         .help(lformat!("Name of the employee").as_ref())
+                                         .required(true)
+                                        )
+                                   )
+                        .subcommand(SubCommand::with_name("prefill")
+                                    .about(lformat!("Fill in the hours of caterers from the planned shifts").as_ref())
+                                    .arg(Arg::with_name("search_term")
+                                         .help(lformat!("Search terms to match the project").as_ref())
+                                         .multiple(true)
+                                        )
+                                    .arg(Arg::with_name("overwrite")
+                                         .help(lformat!("Replace hours that are already in the project").as_ref())
+                                         .long("overwrite")
+                                        )
+                                    .arg(Arg::with_name("dry-run")
+                                         .help(lformat!("Only show the changes").as_ref())
+                                         .long("dry-run")
+                                         .short("n")
+                                        )
+                                    .arg(Arg::with_name("yes")
+                                         .help(lformat!("Don't ask for confirmation").as_ref())
+                                         .long("yes")
+                                         .short("y")
+                                        )
+                                   )
+                       )
+
+            .subcommand(SubCommand::with_name("stock")
+                        .about(lformat!("Learn from past events how much of which product is needed").as_ref())
+                        .subcommand(SubCommand::with_name("stats")
+                                    .about(lformat!("Show consumption and leftovers per product, this is the default").as_ref())
+                                    .arg(Arg::with_name("type")
+                                         .help(lformat!("Only events of this type").as_ref())
+                                         .long("type")
+                                         .short("t")
+                                         .takes_value(true)
+                                        )
+                                    .arg(Arg::with_name("events")
+                                         .help(lformat!("List every event").as_ref())
+                                         .long("events")
+                                         .short("e")
+                                        )
+                                   )
+                        .subcommand(SubCommand::with_name("suggest")
+                                    .about(lformat!("Suggest amounts for the products of an offer").as_ref())
+                                    .arg(Arg::with_name("search_term")
+                                         .help(lformat!("Search terms to match the project").as_ref())
+                                         .required(true)
+                                         .multiple(true)
+                                        )
+                                   )
+                        .subcommand(SubCommand::with_name("shopping")
+                                    .aliases(&["shopping-list", "buy"])
+                                    .about(lformat!("Everything that is needed for the upcoming events").as_ref())
+                                    .arg(Arg::with_name("days")
+                                         .help(lformat!("How many days ahead").as_ref())
+                                         .long("days")
+                                         .short("d")
+                                         .default_value("14")
+                                         .validator(|d| d.parse::<i64>().map(|_ok|()).map_err(|e|e.to_string()))
+                                        )
+                                   )
+                       )
+
+            .subcommand(SubCommand::with_name("earnings")
+                        .aliases(&["limits"])
+                        .about(lformat!("Show what every employee earned in a year and warn about earning limits").as_ref())
+                        .arg(Arg::with_name("year")
+                             .help(lformat!("Year, defaults to the current one").as_ref())
+                             .validator(|y| y.parse::<i32>().map(|_ok|()).map_err(|e|e.to_string()))
+                            )
+                       )
+
+            .subcommand(SubCommand::with_name("payroll")
+                        .about(lformat!("Settle wages of all projects that are payed but whose employees aren't").as_ref())
+                        .arg(Arg::with_name("date")
+                             .help(lformat!("Date the wages are payed on, defaults to today").as_ref())
+                             .long("date")
+                             .short("d")
+                             .validator(validators::is_dmy)
+                             .takes_value(true)
+                            )
+                        .arg(Arg::with_name("dry-run")
+                             .help(lformat!("Only show the statements, don't change anything").as_ref())
+                             .long("dry-run")
+                             .short("n")
+                            )
+                        .arg(Arg::with_name("no-documents")
+                             .help(lformat!("Don't create statement documents").as_ref())
+                             .long("no-documents")
+                            )
+                        .arg(Arg::with_name("yes")
+                             .help(lformat!("Don't ask for confirmation").as_ref())
+                             .long("yes")
+                             .short("y")
+                            )
+                       )
+
             .subcommand(SubCommand::with_name("make")
                         .about(lformat!("Creates documents from projects").as_ref())
                         .aliases(&["mk"])
@@ -662,6 +1113,20 @@ pub fn with_cli<F> (app_handler:F) where F: Fn(App<'_, '_>) {
                              .long("invoice")
                              )
 
+                        .arg(Arg::with_name("storno")
+                             .help(lformat!("Produce the credit note that cancels the invoice").as_ref())
+                             .long("storno")
+                             .conflicts_with_all(&["offer", "invoice"])
+                             )
+
+                        .arg(Arg::with_name("deposit")
+                             .help(lformat!("Produce the deposit invoice with this number (1, 2, ...)").as_ref())
+                             .long("deposit")
+                             .takes_value(true)
+                             .conflicts_with_all(&["offer", "invoice", "storno"])
+                             .validator(|n| n.parse::<usize>().map(|_ok|()).map_err(|e|e.to_string()))
+                             )
+
                         .arg(Arg::with_name("archive")
                              .help(lformat!("Pick an archived project").as_ref())
                              .short("a")
@@ -780,6 +1245,19 @@ pub fn with_cli<F> (app_handler:F) where F: Fn(App<'_, '_>) {
                             )
                        )
 
+            .subcommand(SubCommand::with_name("sync")
+                        .about(lformat!("Pull and merge conflicting project files field by field").as_ref())
+                        .arg(Arg::with_name("ours")
+                             .help(lformat!("Keep local values on conflicts").as_ref())
+                             .long("ours")
+                             .conflicts_with("theirs")
+                            )
+                        .arg(Arg::with_name("theirs")
+                             .help(lformat!("Keep remote values on conflicts").as_ref())
+                             .long("theirs")
+                            )
+                       )
+
             .subcommand(SubCommand::with_name("diff")
                         .about(lformat!("git diff").as_ref())
                         .arg(Arg::with_name("search_term")
@@ -864,7 +1342,7 @@ pub fn with_cli<F> (app_handler:F) where F: Fn(App<'_, '_>) {
             .subcommand(SubCommand::with_name("pop").about(lformat!("equals git pop").as_ref()))
 
             .subcommand(SubCommand::with_name("log")
-                        .aliases(&["lg", "hist", "history"])
+                        .aliases(&["lg"])
                         .about(lformat!("Show commit logs").as_ref())
                         .arg(Arg::with_name("search_term")
                              .help(lformat!("Search term, possibly event name").as_ref())
@@ -884,6 +1362,23 @@ pub fn with_cli<F> (app_handler:F) where F: Fn(App<'_, '_>) {
                             )
                        )
 
+            .subcommand(SubCommand::with_name("history")
+                        .aliases(&["hist"])
+                        .about(lformat!("Show what changed in a project and who changed it").as_ref())
+                        .arg(Arg::with_name("search_term")
+                             .help(lformat!("Search term, possibly event name").as_ref())
+                             .required(true)
+                             .multiple(true)
+                            )
+                        .arg(Arg::with_name("archive")
+                             .help(lformat!("list archived projects").as_ref())
+                             .short("a")
+                             .long("archive")
+                             .min_values(0)
+                             .takes_value(true)
+                            )
+                       )
+
             .subcommand(SubCommand::with_name("remote")
                         .about(lformat!("Show information about the remote").as_ref())
                        )
@@ -957,10 +1452,19 @@ pub fn match_matches(matches: &ArgMatches<'_>) {
      ("workspace", Some(sub_m)) => subcommands::workspace(sub_m),
      ("set",       Some(sub_m)) => subcommands::set(sub_m),
      ("invoice",   Some(sub_m)) => subcommands::invoice(sub_m),
+     ("storno",    Some(sub_m)) => subcommands::storno(sub_m),
+     ("deposit",   Some(sub_m)) => subcommands::deposit(sub_m),
+     ("cancel",    Some(sub_m)) => subcommands::cancel(sub_m),
+     ("rate",      Some(sub_m)) => subcommands::rate(sub_m),
+     ("transition", Some(sub_m)) => subcommands::transition(sub_m),
+     ("offer",     Some(sub_m)) => subcommands::offer(sub_m),
      ("show",      Some(sub_m)) => subcommands::show(sub_m),
      ("calendar",  Some(sub_m)) => subcommands::calendar(sub_m),
+     ("remind",    Some(sub_m)) => subcommands::remind(sub_m),
      ("archive",   Some(sub_m)) => subcommands::archive(sub_m),
      ("unarchive", Some(sub_m)) => subcommands::unarchive(sub_m),
+     ("undo",      _          ) => subcommands::undo(),
+     ("attach",    Some(sub_m)) => subcommands::attach(sub_m),
      ("config",    Some(sub_m)) => subcommands::config(sub_m),
      ("whoami",    _          ) => subcommands::config_show("user/name"),
      ("nocommand", Some(sub_m)) => subcommands::no_command(sub_m),
@@ -979,10 +1483,16 @@ pub fn match_matches(matches: &ArgMatches<'_>) {
      ("version",   Some(sub_m)) => subcommands::version(sub_m),
 
      ("dues",      Some(sub_m)) => subcommands::dues(sub_m),
+     ("payroll",   Some(sub_m)) => subcommands::payroll(sub_m),
+     ("earnings",  Some(sub_m)) => subcommands::earnings(sub_m),
+     ("hours",     Some(sub_m)) => subcommands::hours(sub_m),
+     ("staff",     Some(sub_m)) => subcommands::staff(sub_m),
+     ("stock",     Some(sub_m)) => subcommands::stock(sub_m),
      ("shell",     Some(sub_m)) => subcommands::shell(sub_m),
 
      ("remote",    _          ) => subcommands::git_remote(),
      ("pull",      Some(sub_m)) => subcommands::git_pull(sub_m),
+     ("sync",      Some(sub_m)) => subcommands::git_sync(sub_m),
      ("diff",      Some(sub_m)) => subcommands::git_diff(sub_m),
      ("cleanup",   Some(sub_m)) => subcommands::git_cleanup(sub_m),
      ("status",    _          ) => subcommands::git_status(),
@@ -992,6 +1502,7 @@ pub fn match_matches(matches: &ArgMatches<'_>) {
      ("stash",     _          ) => subcommands::git_stash(),
      ("pop",       _          ) => subcommands::git_stash_pop(),
      ("log",       Some(sub_m)) => subcommands::git_log(sub_m),
+     ("history",   Some(sub_m)) => subcommands::history(sub_m),
      ("complete",  Some(sub_m)) => generate_completions(sub_m),
      _                          => Err(format_err!("unhandled command"))
     };
@@ -1,11 +1,14 @@
+use std::path::Path;
+
+use chrono::DateTime;
 use clap::ArgMatches;
 use failure::{bail, format_err, Error};
 use log::{trace, error};
 
-use asciii::{storage, util};
+use asciii::{actions, storage, util};
 use asciii::project::Project;
 
-use super::matches_to_paths;
+use super::{matches_to_paths, matches_to_search};
 
 /// Command LOG
 pub fn git_log(matches: &ArgMatches<'_>) -> Result<(), Error> {
@@ -19,6 +22,29 @@ pub fn git_log(matches: &ArgMatches<'_>) -> Result<(), Error> {
     }
 }
 
+/// Command HISTORY
+pub fn history(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    use asciii::project::spec::IsProject;
+
+    let (search_terms, dir) = matches_to_search(matches);
+    for (project, entries) in actions::history(dir, &search_terms)? {
+        println!("{}", project.name().unwrap_or(""));
+        for entry in entries {
+            let date = DateTime::parse_from_rfc3339(&entry.date)
+                .map(|d| d.format("%d.%m.%Y %H:%M").to_string())
+                .unwrap_or(entry.date);
+            println!("  {} {} ({})", date, entry.author, &entry.commit[..7.min(entry.commit.len())]);
+            for change in &entry.changes {
+                println!("    {}", change);
+            }
+            if let (Some(sum), Some(delta)) = (entry.sum_sold, entry.sum_sold_delta) {
+                println!("    {}", lformat!("sum sold: {} ({})", sum, delta));
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Command STATUS
 pub fn git_status() -> Result<(), Error> {
     let storage = storage::setup_with_git::<Project>()?;
@@ -147,6 +173,27 @@ pub fn git_pull(matches: &ArgMatches<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Command SYNC
+pub fn git_sync(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    use asciii::storage::merge::{Conflict, Side};
+
+    let merged = actions::sync(|path: &Path, conflict: &Conflict| {
+        if matches.is_present("ours") {
+            Side::Ours
+        } else if matches.is_present("theirs") {
+            Side::Theirs
+        } else {
+            println!("{}\n{}", path.display(), conflict);
+            if util::really(&lformat!("keep theirs?")) { Side::Theirs } else { Side::Ours }
+        }
+    })?;
+
+    for path in merged {
+        println!("{}", lformat!("merged {}", path.display()));
+    }
+    Ok(())
+}
+
 /// Command PUSH
 pub fn git_push() -> Result<(), Error> {
     let storage = storage::setup_with_git::<Project>()?;
@@ -14,8 +14,11 @@ use yaml_rust::Yaml;
 
 use asciii::{self, CONFIG, config, util, actions};
 use asciii::project::Exportable;
+use asciii::project::spec::Invoicable;
 
 use asciii::project::Project;
+use asciii::util::decimal::Decimal;
+use asciii::project::state::State;
 use asciii::storage::*;
 use asciii::actions::error::ActionError;
 use asciii::templater::Templater;
@@ -61,7 +64,6 @@ pub fn no_shell(_matches: &ArgMatches<'_>) -> Result<(), Error> {
 /// Create NEW Project
 // #[deprecated(note="move to asciii::actions")]
 pub fn new(matches: &ArgMatches<'_>) -> Result<(), Error> {
-    let project_name = matches.value_of("name").expect("You did not pass a \"Name\"!");
     let editor = CONFIG.get("user/editor").and_then(Yaml::as_str);
 
     let template_name = matches.value_of("template")
@@ -98,6 +100,21 @@ pub fn new(matches: &ArgMatches<'_>) -> Result<(), Error> {
         fill_data.insert("MANAGER", manager.to_owned());
     }
 
+    if let Some(ics_file) = matches.value_of("from-ics") {
+        let project_files = actions::new_from_ics(Path::new(ics_file), template_name, &fill_data)?
+            .iter()
+            .map(Storable::file)
+            .collect::<Vec<PathBuf>>();
+        for file in &project_files {
+            println!("{}", file.display());
+        }
+        if edit {
+            util::pass_to_command(editor, &project_files)?;
+        }
+        return Ok(());
+    }
+
+    let project_name = matches.value_of("name").expect("You did not pass a \"Name\"!");
     let project_file = storage.create_project(project_name, template_name, &fill_data)?.file();
     if edit {
         util::pass_to_command(editor, &[project_file])?;
@@ -349,12 +366,7 @@ pub fn invoice(m: &ArgMatches<'_>) -> Result<(), Error> {
     let storage = setup::<Project>()?;
     let dir = StorageDir::Year(Utc::today().year());
     let projects = storage.open_projects(dir)?;
-    let invoice_number = 1 + projects.iter()
-                             .filter_map(|p| p.field("invoice/number"))
-                             .map(|s| s.parse::<u32>())
-                             .filter_map(Result::ok)
-                             .max()
-                             .unwrap_or(0);
+    let invoice_number = actions::next_invoice_number(&projects);
     let value = invoice_number.to_string();
 
     let (search_terms, dir) = matches_to_search(m);
@@ -376,6 +388,275 @@ pub fn invoice(m: &ArgMatches<'_>) -> Result<(), Error> {
 }
 
 
+/// Command STORNO
+pub fn storno(m: &ArgMatches<'_>) -> Result<(), Error> {
+    let (search_terms, dir) = matches_to_search(m);
+    let date = m.value_of("date")
+                .and_then(util::yaml::parse_dmy_date)
+                .unwrap_or_else(Utc::today);
+    let reason = m.value_of("reason");
+
+    actions::with_projects(dir, &search_terms, |project| {
+        let invoice = project.invoice().number_long_str().unwrap_or_default();
+        if util::really(&lformat!("Do you want to cancel the invoice {} of {:?} by a credit note?",
+                                  invoice,
+                                  project.short_desc())) {
+            let number = actions::storno(project, date, reason)?;
+            println!("{}", lformat!("credit note R{:03} cancels {}, export it with `make --storno`", number, invoice));
+            Ok(())
+        } else {
+            Err(format_err!("Don't want to"))
+        }
+    })?;
+    Ok(())
+}
+
+/// Command CANCEL
+pub fn cancel(m: &ArgMatches<'_>) -> Result<(), Error> {
+    let (search_terms, dir) = matches_to_search(m);
+    let date = m.value_of("date")
+                .and_then(util::yaml::parse_dmy_date)
+                .unwrap_or_else(Utc::today);
+    let percent = match m.value_of("percent") {
+        Some(percent) => Some(percent.parse::<Decimal>()?),
+        None => None
+    };
+    let waived = m.is_present("free");
+
+    actions::with_projects(dir, &search_terms, |project| {
+        if util::really(&lformat!("Do you want to cancel {:?}?", project.short_desc())) {
+            match actions::cancel(project, date, percent, waived)? {
+                Some(percent) => println!("{}", lformat!("a cancellation fee of {}% of the offer is due, assign an invoice number with `invoice`", percent)),
+                None => println!("{}", lformat!("canceled free of charge")),
+            }
+            Ok(())
+        } else {
+            Err(format_err!("Don't want to"))
+        }
+    })?;
+    Ok(())
+}
+
+/// Command TRANSITION
+pub fn transition(m: &ArgMatches<'_>) -> Result<(), Error> {
+    let (search_terms, dir) = matches_to_search(m);
+    let date = m.value_of("date")
+                .and_then(util::yaml::parse_dmy_date)
+                .unwrap_or_else(Utc::today);
+    let to = match m.value_of("state") {
+        Some(state) => Some(state.parse::<State>()?),
+        None => None
+    };
+
+    actions::with_projects(dir, &search_terms, |project| {
+        let from = project.state();
+        match to {
+            Some(to) => {
+                let state = actions::transition(project, to, date)?;
+                println!("{}", lformat!("{}: {} → {}", project.short_desc(), from, state));
+            }
+            None => {
+                let next = from.next().iter().map(|state| state.name()).collect::<Vec<_>>().join(", ");
+                println!("{}", lformat!("{} is {}, it can become: {}", project.short_desc(), from, next));
+            }
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Command OFFER
+pub fn offer(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    match matches.subcommand() {
+        (answer @ "accept", Some(m)) |
+        (answer @ "reject", Some(m)) => {
+            let (search_terms, dir) = matches_to_search(m);
+            let date = m.value_of("date")
+                        .and_then(util::yaml::parse_dmy_date)
+                        .unwrap_or_else(Utc::today);
+            let accepted = answer == "accept";
+            actions::with_projects(dir, &search_terms, |project| {
+                let appendix = actions::answer_offer(project, accepted, date, m.value_of("note"))?;
+                if accepted {
+                    println!("{}", lformat!("{}: appendix {} of the offer was accepted", project.short_desc(), appendix));
+                } else {
+                    println!("{}", lformat!("{}: appendix {} of the offer was rejected", project.short_desc(), appendix));
+                }
+                Ok(())
+            })?;
+            Ok(())
+        }
+        ("versions", Some(m)) => offer_versions(m),
+        ("diff", Some(m)) => offer_diff(m),
+        _ => bail!(format_err!("{}", lformat!("use `offer accept`, `offer reject`, `offer versions` or `offer diff`")))
+    }
+}
+
+#[cfg(feature="serialization")]
+fn offer_versions(m: &ArgMatches<'_>) -> Result<(), Error> {
+    use asciii::project::offer_versions;
+    use asciii::project::spec::Offerable;
+
+    let (search_terms, dir) = matches_to_search(m);
+    actions::with_projects(dir, &search_terms, |project| {
+        println!("{}", project.short_desc());
+        let accepted = project.offer().accepted_appendix();
+        for version in offer_versions::versions(project)? {
+            println!("  {:>2} {:<14} {:>12} {:<10} {}{}",
+                     version.appendix,
+                     version.number,
+                     version.total,
+                     version.exported.as_ref().map(String::as_str).unwrap_or(""),
+                     version.pdf.as_ref().map(String::as_str).unwrap_or(""),
+                     if Some(version.appendix) == accepted { lformat!(" (accepted)") } else { String::new() });
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[cfg(feature="serialization")]
+fn offer_diff(m: &ArgMatches<'_>) -> Result<(), Error> {
+    use asciii::project::offer_versions::{self, Version};
+
+    let (search_terms, dir) = matches_to_search(m);
+    let appendix = |name: &str| m.value_of(name).and_then(|a| a.parse::<i64>().ok());
+    actions::with_projects(dir, &search_terms, |project| {
+        let versions = offer_versions::versions(project)?;
+        let find = |appendix: i64| versions.iter()
+                                           .find(|version| version.appendix == appendix)
+                                           .cloned()
+                                           .ok_or_else(|| format_err!("{}", lformat!("{} has no snapshot of appendix {}", project.short_desc(), appendix)));
+
+        let (before, after) = if m.is_present("invoice") {
+            let accepted = offer_versions::accepted(project)?
+                .ok_or_else(|| format_err!("{}", lformat!("{} has no snapshot of an accepted offer", project.short_desc())))?;
+            (accepted, Version::of_invoice(project)?)
+        } else {
+            let latest = versions.last().map(|version| version.appendix).unwrap_or(1);
+            match (appendix("from"), appendix("to")) {
+                (Some(from), to) => (find(from)?, find(to.unwrap_or(latest))?),
+                (None, _) if versions.len() >= 2 => (versions[versions.len() - 2].clone(), versions[versions.len() - 1].clone()),
+                (None, _) => bail!(format_err!("{}", lformat!("{} has less than two versions of its offer", project.short_desc())))
+            }
+        };
+
+        println!("{}: {} → {}", project.short_desc(), before.number, after.number);
+        let changes = offer_versions::diff(&before, &after);
+        if changes.is_empty() {
+            println!("{}", lformat!("  no changes"));
+        }
+        for change in changes {
+            println!("  {}", change);
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+#[cfg(not(feature="serialization"))]
+fn offer_versions(_: &ArgMatches<'_>) -> Result<(), Error> {
+    error!("Offer versions are not built-in with this release!");
+    Ok(())
+}
+
+#[cfg(not(feature="serialization"))]
+fn offer_diff(_: &ArgMatches<'_>) -> Result<(), Error> {
+    error!("Offer versions are not built-in with this release!");
+    Ok(())
+}
+
+/// Command RATE
+pub fn rate(m: &ArgMatches<'_>) -> Result<(), Error> {
+    let (search_terms, dir) = matches_to_search(m);
+    let date = m.value_of("date")
+                .and_then(util::yaml::parse_dmy_date)
+                .unwrap_or_else(Utc::today);
+    let rate = match m.value_of("rate") {
+        Some(rate) => Some(rate.parse::<Decimal>()?),
+        None => None
+    };
+    let currency = m.value_of("currency");
+
+    actions::with_projects(dir, &search_terms, |project| {
+        match actions::exchange_rate(project, currency, rate, date)? {
+            Some((code, rate)) => println!("{}", lformat!("{}: 1 {} = {} {}", project.short_desc(), code, rate.rate, CONFIG.get_str("currency"))),
+            None => println!("{}", lformat!("{} is billed in the home currency", project.short_desc())),
+        }
+        Ok(())
+    })?;
+    Ok(())
+}
+
+/// Command DEPOSIT
+pub fn deposit(m: &ArgMatches<'_>) -> Result<(), Error> {
+    use asciii::project::spec::{AdjustmentValue, Redeemable};
+
+    let (search_terms, dir) = matches_to_search(m);
+    let date = m.value_of("date")
+                .and_then(util::yaml::parse_dmy_date)
+                .unwrap_or_else(Utc::today);
+
+    if let Some(payed) = m.value_of("payed").and_then(|n| n.parse::<usize>().ok()) {
+        if payed == 0 {
+            bail!(format_err!("deposits are counted from 1"));
+        }
+        return actions::with_projects(dir, &search_terms, |project| actions::deposit_payed(project, payed - 1, date));
+    }
+
+    let value = match (m.value_of("percent"), m.value_of("amount")) {
+        (Some(percent), _) => AdjustmentValue::Percent(percent.parse()?),
+        (_, Some(amount)) => AdjustmentValue::Amount(amount.parse::<Decimal>()?.to_currency()),
+        (None, None) => bail!(format_err!("{}", lformat!("Please give either --percent or --amount")))
+    };
+    let name = m.value_of("name");
+
+    actions::with_projects(dir, &search_terms, |project| {
+        if util::really(&lformat!("Do you want to add a deposit invoice to {:?}?", project.short_desc())) {
+            let position = project.deposits()?.len() + 1;
+            let number = actions::deposit(project, value, date, name)?;
+            println!("{}", lformat!("deposit R{:03} was added, export it with `make --deposit {}`", number, position));
+            Ok(())
+        } else {
+            Err(format_err!("Don't want to"))
+        }
+    })?;
+    Ok(())
+}
+
+/// Command REMIND
+pub fn remind(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    use asciii::project::reminders::Kind;
+    let reminders = actions::reminders()?;
+
+    if matches.is_present("digest") {
+        for (project, reminders) in &reminders {
+            println!("{}", project.short_desc());
+            for reminder in reminders {
+                println!("  * {}", reminder);
+                if let Some(ref description) = reminder.description {
+                    for line in description.lines() {
+                        println!("    {}", line);
+                    }
+                }
+            }
+            println!();
+        }
+        return Ok(());
+    }
+
+    for (project, reminders) in &reminders {
+        for reminder in reminders {
+            match reminder.kind {
+                Kind::Warning => warn!("{}: {}", project.short_desc(), reminder),
+                Kind::Task => println!("{}: {}", project.short_desc(), reminder),
+                Kind::Digest => {}
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Command CALENDAR
 pub fn calendar(matches: &ArgMatches<'_>) -> Result<(), Error> {
     let calendar = actions::calendar_with_tasks(matches_to_dir(matches), matches.is_present("tasks"))?;
@@ -399,6 +680,12 @@ use self::document_export::ExportConfig;
 
 #[cfg(feature="document_export")]
 fn infer_bill_type(m: &ArgMatches<'_>) -> Option<BillType> {
+    if m.is_present("storno") {
+        return Some(BillType::Storno);
+    }
+    if let Some(number) = m.value_of("deposit").and_then(|n| n.parse::<usize>().ok()) {
+        return Some(BillType::Deposit(number.saturating_sub(1)));
+    }
     match (m.is_present("offer"), m.is_present("invoice")) {
         (true, true)   => unreachable!("this should have been prevented by clap-rs"),
         (true, false)  => Some(BillType::Offer),
@@ -511,6 +798,27 @@ pub fn unarchive(matches: &ArgMatches<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Command UNDO
+pub fn undo() -> Result<(), Error> {
+    let moved_files = actions::undo()?;
+    if moved_files.is_empty() {
+        println!("{}", lformat!("nothing to undo"));
+    }
+    debug!("undo() :\n{:?}", moved_files);
+    Ok(())
+}
+
+/// Command ATTACH
+pub fn attach(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let (search_terms, dir) = matches_to_search(matches);
+    let file = Path::new(matches.value_of("file").unwrap());
+    let date = matches.value_of("date").and_then(util::yaml::parse_dmy_date);
+    let attached = actions::attach(dir, &search_terms, file,
+                                   matches.value_of("kind"), date, matches.value_of("note"))?;
+    println!("{}", lformat!("attached {}", attached.display()));
+    Ok(())
+}
+
 pub fn config(matches: &ArgMatches<'_>) -> Result<(), Error> {
     let editor = matches.value_of("editor")
                         .or_else(|| CONFIG.get("user.editor")
@@ -646,15 +954,294 @@ pub fn version(matches: &ArgMatches<'_>) -> Result<(), Error> {
 
 /// Command DUES
 pub fn dues(matches: &ArgMatches<'_>) -> Result<(), Error> {
-    let dues = actions::dues();
+    let dues = actions::dues(matches.is_present("wages"));
     if let Ok(dues) = dues {
         println!("Open Payments: {}", dues.acc_sum_sold.postfix());
         println!("Open Wages:    {}", dues.acc_wages.postfix());
+        for project in &dues.without_rate {
+            warn!("{}", lformat!("{} is left out, it has no exchange rate", project));
+        }
         if matches.is_present("wages") {
             for (employee, open_wages) in &dues.unpayed_employees {
                 println!("{}:    {}", employee, open_wages.postfix());
             }
+            for warning in &dues.limit_warnings {
+                warn!("{}", warning);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Command EARNINGS
+pub fn earnings(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let year = matches.value_of("year")
+                      .and_then(|y| y.parse::<i32>().ok())
+                      .unwrap_or_else(|| Utc::today().year());
+    let (earnings, warnings) = actions::earnings(year)?;
+
+    for e in &earnings {
+        let limit = |limit: Option<bill::Currency>| limit.map(|l| format!(" / {}", l.postfix())).unwrap_or_default();
+        let peak = e.peak_month()
+                    .map(|(month, wage)| format!("{:02}: {}{}", month, wage.postfix(), limit(e.limits.monthly)))
+                    .unwrap_or_default();
+        println!("{:<20} {:<16} {:>12}{:<14} {}",
+                 e.name,
+                 e.contract.as_ref().map(String::as_str).unwrap_or(""),
+                 e.yearly.postfix().to_string(),
+                 limit(e.limits.yearly),
+                 peak);
+    }
+
+    for warning in &warnings {
+        warn!("{}", warning);
+    }
+    Ok(())
+}
+
+/// Command PAYROLL
+pub fn payroll(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let date = matches.value_of("date")
+                      .and_then(util::yaml::parse_dmy_date)
+                      .unwrap_or_else(Utc::today);
+    let payroll = actions::payroll()?;
+    if payroll.is_empty() {
+        println!("{}", lformat!("no wages due"));
+        return Ok(());
+    }
+
+    for project in &payroll.projects {
+        println!("{}", project.short_desc());
+    }
+    println!();
+    for statement in &payroll.statements {
+        println!("{:<20} {:>6}h {:>12}", statement.name, statement.time, statement.wage.postfix());
+    }
+    println!("{:<20} {:>7} {:>12}", lformat!("Total"), "", payroll.total().postfix());
+
+    if matches.is_present("dry-run") {
+        println!("\n{}", payroll.to_csv()?);
+        return Ok(());
+    }
+
+    if !matches.is_present("yes")
+        && !util::really(&lformat!("Set the wages date of these {} projects to {}?", payroll.projects.len(), date.format("%d.%m.%Y"))) {
+        return Ok(());
+    }
+
+    let output_folder = util::get_valid_path(CONFIG.get_str("output_path"))
+        .ok_or_else(|| format_err!("{}", lformat!("output path does not exist")))?;
+    let csv_file = output_folder.join(format!("{} {}.csv", lformat!("Payroll"), date.format("%Y-%m-%d")));
+    fs::write(&csv_file, payroll.to_csv()?)?;
+    println!("{}", csv_file.display());
+
+    #[cfg(feature="document_export")]
+    {
+        if !matches.is_present("no-documents") {
+            for document in document_export::payroll_to_doc(&payroll, date, false)? {
+                println!("{}", document.display());
+            }
+        }
+    }
+
+    let settled = actions::settle_payroll(&payroll, date)?;
+    debug!("payroll() :\n{:?}", settled);
+    Ok(())
+}
+
+/// Command HOURS
+pub fn hours(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    match matches.subcommand() {
+        ("import", Some(sub_m)) => import_hours(sub_m),
+        _ => Ok(())
+    }
+}
+
+/// Command HOURS IMPORT
+fn import_hours(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let file = Path::new(matches.value_of("file").unwrap());
+    let format = matches.value_of("format").map(str::parse::<actions::timesheet::Format>).transpose()?;
+    let import = actions::import_hours(file, format, matches.is_present("overwrite"))?;
+
+    for update in &import.updates {
+        println!("{}", update.project.short_desc());
+        for line in update.diff() {
+            println!("{}", line);
+        }
+        println!();
+    }
+
+    for problem in &import.problems {
+        warn!("{}", problem);
+    }
+
+    if import.updates.is_empty() {
+        println!("{}", lformat!("nothing to import"));
+        return Ok(());
+    }
+
+    if matches.is_present("dry-run") {
+        return Ok(());
+    }
+
+    if !matches.is_present("yes")
+        && !util::really(&lformat!("Write hours into these {} projects?", import.updates.len())) {
+        return Ok(());
+    }
+
+    let written = actions::apply_hours(&import.updates)?;
+    debug!("import_hours() :\n{:?}", written);
+    Ok(())
+}
+
+/// Command STAFF
+pub fn staff(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    match matches.subcommand() {
+        ("prefill", Some(sub_m)) => return staff_prefill(sub_m),
+        ("ical", Some(sub_m)) => {
+            let (bookings, _) = actions::staffing()?;
+            let name = sub_m.value_of("name").unwrap();
+            print!("{}", util::ical::to_string(&actions::staffing::to_ical(&bookings, name)));
+            return Ok(());
+        },
+        _ => {}
+    }
+
+    let (bookings, conflicts) = actions::staffing()?;
+    let date = matches.subcommand_matches("week")
+                      .and_then(|m| m.value_of("date"))
+                      .and_then(util::yaml::parse_dmy_date)
+                      .unwrap_or_else(Utc::today);
+    let monday = actions::staffing::start_of_week(date);
+
+    println!("{}", lformat!("week of {}", monday.format("%d.%m.%Y")));
+    for (day, bookings) in actions::staffing::week(&bookings, monday) {
+        println!("\n{}", day.format("%a %d.%m.%Y"));
+        for booking in bookings {
+            let assignment = &booking.assignment;
+            println!("  {}-{} {:<20} {:<12} {}",
+                     assignment.start.format("%H:%M"),
+                     assignment.end.format("%H:%M"),
+                     assignment.name,
+                     assignment.role.as_ref().map(String::as_str).unwrap_or(""),
+                     booking.project);
+        }
+    }
+
+    for conflict in &conflicts {
+        warn!("{}", conflict);
+    }
+    Ok(())
+}
+
+/// Command STAFF PREFILL
+fn staff_prefill(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let search_terms = matches.values_of("search_term")
+                              .map(|terms| terms.collect::<Vec<&str>>())
+                              .unwrap_or_else(Vec::new);
+    let (updates, problems) = actions::prefill_hours(&search_terms, matches.is_present("overwrite"))?;
+
+    for update in &updates {
+        println!("{}", update.project.short_desc());
+        for line in update.diff() {
+            println!("{}", line);
+        }
+        println!();
+    }
+    for problem in &problems {
+        warn!("{}", problem);
+    }
+
+    if updates.is_empty() {
+        println!("{}", lformat!("nothing to fill in"));
+        return Ok(());
+    }
+    if matches.is_present("dry-run") {
+        return Ok(());
+    }
+    if !matches.is_present("yes")
+        && !util::really(&lformat!("Write hours into these {} projects?", updates.len())) {
+        return Ok(());
+    }
+
+    let written = actions::apply_hours(&updates)?;
+    debug!("staff_prefill() :\n{:?}", written);
+    Ok(())
+}
+
+fn amount_with_unit(amount: f64, unit: &Option<String>) -> String {
+    match *unit {
+        Some(ref unit) => format!("{} x {}", amount, unit),
+        None => amount.to_string()
+    }
+}
+
+/// Command STOCK
+pub fn stock(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    match matches.subcommand() {
+        ("suggest", Some(sub_m)) => return stock_suggest(sub_m),
+        ("shopping", Some(sub_m)) => return stock_shopping(sub_m),
+        _ => {}
+    }
+
+    let sub_m = matches.subcommand_matches("stats");
+    let event_type = sub_m.and_then(|m| m.value_of("type"));
+    let usages = actions::stock_history()?;
+
+    if sub_m.map(|m| m.is_present("events")).unwrap_or(false) {
+        for usage in usages.iter().filter(|u| event_type.is_none() || u.event_type.as_ref().map(String::as_str) == event_type) {
+            println!("{} {:<30} {:<20} {:>6} {:>6} {:>6}",
+                     usage.date.format("%d.%m.%Y"), usage.project, usage.name,
+                     usage.offered, usage.sold, usage.returned());
+        }
+        println!();
+    }
+
+    println!("{:<20} {:<8} {:>6} {:>8} {:>8} {:>8} {:>8}",
+             lformat!("product"), lformat!("unit"), lformat!("events"),
+             lformat!("offered"), lformat!("used"), lformat!("waste"), lformat!("sold out"));
+    for stats in actions::stock::statistics(&usages, event_type) {
+        println!("{:<20} {:<8} {:>6} {:>8.1} {:>8.1} {:>7.0}% {:>8}",
+                 stats.name,
+                 stats.unit.as_ref().map(String::as_str).unwrap_or(""),
+                 stats.events,
+                 stats.offered,
+                 stats.sold,
+                 stats.waste() * 100.0,
+                 stats.sold_out);
+    }
+    Ok(())
+}
+
+/// Command STOCK SUGGEST
+fn stock_suggest(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let search_terms = matches.values_of("search_term").unwrap().collect::<Vec<&str>>();
+    for (project, suggestions) in actions::stock_suggestions(&search_terms)? {
+        println!("{}", project.short_desc());
+        for suggestion in suggestions {
+            let planned = suggestion.planned.map(|p| p.to_string()).unwrap_or_else(|| "-".into());
+            match suggestion.suggested {
+                Some(suggested) => println!("  {:<20} {:>6} -> {:<12} {}",
+                                            suggestion.name, planned,
+                                            amount_with_unit(suggested, &suggestion.unit),
+                                            lformat!("(from {} events)", suggestion.basis)),
+                None => println!("  {:<20} {:>6}    {}", suggestion.name, planned, lformat!("no history")),
+            }
         }
+        println!();
+    }
+    Ok(())
+}
+
+/// Command STOCK SHOPPING
+fn stock_shopping(matches: &ArgMatches<'_>) -> Result<(), Error> {
+    let days = matches.value_of("days").unwrap().parse::<i64>()?;
+    let list = actions::shopping_list(days)?;
+    if list.is_empty() {
+        println!("{}", lformat!("nothing needed in the next {} days", days));
+    }
+    for item in list {
+        println!("{:<20} {:<12} {}", item.name, amount_with_unit(item.amount, &item.unit), item.projects.join(", "));
     }
     Ok(())
 }
@@ -6,7 +6,7 @@ use asciii::storage::*;
 //use asciii::storage::error::*;
 
 use asciii::project::{spec, BillType, Project};
-use asciii::project::spec::HasEvents;
+use asciii::project::spec::{HasAttachments, HasEvents};
 use asciii::templater::Templater;
 
 use super::{matches_to_search, matches_to_selection};
@@ -69,6 +69,12 @@ fn show_errors(selection: StorageSelection) -> Result<(), Error> {
         spec::print_spec_result("offer", &p.is_ready_for_offer());
         spec::print_spec_result("invoice", &p.is_ready_for_invoice());
         spec::print_spec_result("archive", &p.is_ready_for_archive());
+        for attachment in p.missing_attachments() {
+            println!("{}", lformat!("attachment missing: {}", attachment.file));
+        }
+        for name in p.hours().employees_off_shift() {
+            println!("{}", lformat!("warning: the shift of {} is outside the event times", name));
+        }
     }
     Ok(())
 }
@@ -97,7 +103,7 @@ fn show_yaml(selection: StorageSelection) -> Result<(), Error> {
 
 fn show_ical(selection: StorageSelection) -> Result<(), Error> {
     for p in setup::<Project>()?.open_projects(selection)? {
-        p.to_ical().print()?
+        print!("{}", asciii::util::ical::to_string(&p.to_ical()));
     }
     Ok(())
 }
@@ -5,6 +5,8 @@
 use std::{time,fs};
 use std::path::{Path, PathBuf};
 
+use chrono::prelude::*;
+
 use serde::ser::Serialize;
 use failure::{bail, Error};
 use yaml_rust::Yaml;
@@ -14,8 +16,9 @@ use handlebars::{Handlebars, no_escape, Helper, RenderContext, HelperDef, Contex
 use log::{info, debug, trace, error, warn};
 
 use crate::util;
+use crate::actions::payroll::Payroll;
 use crate::project::{self, Project, Exportable};
-use crate::project::BillType::{self, Invoice, Offer};
+use crate::project::BillType::{self, Invoice, Offer, Storno, Deposit};
 use crate::project::export::ExportTarget;
 use crate::storage::{self, Storable, StorageSelection};
 
@@ -27,7 +30,9 @@ use self::error::*;
 struct DocAndStorage<'a, T: Serialize> {
     document: &'a T,
     storage: Option<storage::Paths>,
-    is_invoice: bool
+    is_invoice: bool,
+    is_storno: bool,
+    is_deposit: bool
 }
 
 impl<'a, T: 'a + Serialize> DocAndStorage<'a, T> {
@@ -35,7 +40,9 @@ impl<'a, T: 'a + Serialize> DocAndStorage<'a, T> {
         DocAndStorage {
             document,
             storage: storage::setup::<Project>().ok().map(|s| s.paths()),
-            is_invoice: bill_type == Invoice
+            is_invoice: bill_type == Invoice,
+            is_storno: bill_type == Storno,
+            is_deposit: if let Deposit(_) = bill_type { true } else { false }
         }
     }
 }
@@ -71,6 +78,12 @@ impl HelperDef for CountHelper {
 // pub fn fill_template<E:Serialize>(document:E, template_file:&Path) -> PathBuf{
 pub fn fill_template<E, P>(document: &E, bill_type: BillType, template_path: P) -> Result<String, Error>
     where E: Serialize, P:AsRef<Path>
+{
+    render(&DocAndStorage::from(document, bill_type), template_path)
+}
+
+fn render<T, P>(data: &T, template_path: P) -> Result<String, Error>
+    where T: Serialize, P:AsRef<Path>
 {
     let mut handlebars = Handlebars::new();
 
@@ -82,7 +95,7 @@ pub fn fill_template<E, P>(document: &E, bill_type: BillType, template_path: P)
 
     handlebars.register_template_file("document", template_path).unwrap();
 
-    Ok(handlebars.render("document", &DocAndStorage::from(document, bill_type))
+    Ok(handlebars.render("document", data)
                  .map(|r| r.replace("<", "{")
                            .replace(">", "}"))?)
 }
@@ -153,6 +166,18 @@ fn project_to_doc(project: &Project, config: &ExportConfig<'_>) -> Result<Option
         (Option<BillType>, Option<PathBuf>) =
          match (bill_type, ready_for_offer, ready_for_invoice)
     {
+        (Some(Storno),  _,      _     ) => match project.is_ready_for_storno() {
+            Ok(_) => (Some(Storno), Some(project.dir()
+                                                .join(project.storno_file_name(output_ext)
+                                                             .expect("this should have been caught by ready_for_storno()")))),
+            Err(e) => {error!("cannot create a credit note, check out:{}",e);(None,None)}
+        },
+        (Some(Deposit(index)), _, _   ) => match project.is_ready_for_deposit(index) {
+            Ok(_) => (Some(Deposit(index)), Some(project.dir()
+                                                        .join(project.deposit_file_name(index, output_ext)
+                                                                     .expect("this should have been caught by ready_for_deposit()")))),
+            Err(e) => {error!("cannot create deposit {}, check out:{}",index + 1,e);(None,None)}
+        },
         (Some(Offer),   Ok(_),  _     ) |
         (None,          Ok(_),  Err(_)) => (Some(Offer), Some(project.dir()
                                                                     .join(project.offer_file_name(output_ext)
@@ -169,7 +194,10 @@ fn project_to_doc(project: &Project, config: &ExportConfig<'_>) -> Result<Option
     // }
 
     if let (Some(tex_file), Some(dyn_bill)) = (outfile_tex, dyn_bill_type) {
-        let exported_project: project::export::Complete = project.export();
+        let mut exported_project: project::export::Complete = project.export();
+        if let Deposit(index) = dyn_bill {
+            exported_project = exported_project.for_deposit(index);
+        }
         let filled = fill_template(&exported_project, dyn_bill, &template_path)?;
 
         let pdffile = util::to_local_file(&tex_file, convert_ext);
@@ -255,6 +283,10 @@ fn project_to_doc(project: &Project, config: &ExportConfig<'_>) -> Result<Option
             } else {
                 bail!(ExportError::NoPdfCreated);
             }
+            if dyn_bill == Offer {
+                let snapshot = project::offer_versions::store(project, &exported_project, &document_file)?;
+                debug!("offer snapshot stored in {:?}", snapshot);
+            }
             Ok(Some(document_file))
         }
 
@@ -306,3 +338,44 @@ pub fn projects_to_doc(config: &ExportConfig<'_>) -> Result<(), Error> {
     Ok(())
 }
 
+/// Renders one payout statement per employee with the `document_export/payroll_template`.
+///
+/// Returns the paths of the created documents.
+#[cfg(feature="document_export")]
+pub fn payroll_to_doc(payroll: &Payroll, date: Date<Utc>, dry_run: bool) -> Result<Vec<PathBuf>, Error> {
+    let output_ext    = crate::CONFIG.get_str("extensions/output_file");
+    let convert_ext   = crate::CONFIG.get_str("document_export/output_extension");
+    let convert_tool  = crate::CONFIG.get_str("document_export/convert_tool");
+    let output_folder = util::get_valid_path(crate::CONFIG.get_str("output_path")).unwrap();
+    let template_path = output_template_path(crate::CONFIG.get_str("document_export/payroll_template"))?;
+
+    let mut documents = Vec::new();
+    for statement in payroll.export(date) {
+        let filled = render(&statement, &template_path)?;
+        let tex_file = output_folder.join(format!("{} {} {}.{}",
+                                                  lformat!("Payroll"),
+                                                  slug::slugify(&statement.name),
+                                                  date.format("%Y-%m-%d"),
+                                                  output_ext));
+        let document_file = tex_file.with_extension(convert_ext);
+
+        if dry_run {
+            warn!("Dry run! This does not produce any output:\n * {}", document_file.display());
+            continue;
+        }
+
+        fs::write(&tex_file, filled)?;
+        util::pass_to_command(Some(convert_tool), &[&tex_file])?;
+
+        // the converter puts its output into the current directory
+        let local_file = util::to_local_file(&tex_file, convert_ext);
+        if local_file.exists() && local_file != document_file {
+            fs::rename(&local_file, &document_file)?;
+        }
+        if !document_file.exists() {
+            bail!(ExportError::NoPdfCreated);
+        }
+        documents.push(document_file);
+    }
+    Ok(documents)
+}
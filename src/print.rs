@@ -12,8 +12,10 @@ use prettytable::{cell, row};
 
 
 use crate::project::{BillType, Project, Exportable};
-use crate::project::spec::{IsProject, Redeemable, Invoicable, HasEmployees, HasEvents};
+use crate::project::spec::{IsProject, Redeemable, Invoicable, HasAttachments, HasCurrency, HasEmployees, HasEvents, HasExpenses};
 use crate::project::error::SpecResult;
+use crate::project::totals::{line_cost, Totals};
+use crate::project::state::State;
 use crate::storage::Storable;
 use crate::util::currency_to_string;
 
@@ -70,14 +72,15 @@ fn result_to_cell(res: &SpecResult, bold:bool) -> Cell{
 
 /// create a Style string from the properties of a project
 fn project_to_style(project:&Project) -> &str{
-    // can be send as invoice
-    if project.is_ready_for_invoice().is_ok(){
+    let state = project.state();
+    // was send as invoice
+    if state.is_invoiced() {
         return "d"
     }
 
     if let Some(date) = project.modified_date(){
         let age = (Local::today().signed_duration_since(date)).num_days();
-        if project.canceled(){
+        if state == State::Canceled {
             return ""
         }
         return match age{
@@ -198,7 +201,7 @@ pub fn verbose_rows(projects:&[Project], list_config:&ListConfig<'_>) -> Vec<Row
                 //cell!(output_file_exists(project, Project::offer_file_name)),
                 //cell!(output_file_exists(project, Project::invoice_file_name)),
 
-                cell!(r->project.sum_sold().map(|i|currency_to_string(&i)).unwrap_or_else(|e| format!("{}", e))),
+                cell!(r->project.sum_sold().map(|i|project.format_amount(&i)).unwrap_or_else(|e| format!("{}", e))),
                 //cell!(project.wages().map(|i|i.to_string()).unwrap_or(String::from("none"))),
                 //cell!(project.sum_sold_and_wages().map(|i|i.to_string()).unwrap_or(String::from("none"))),
             ]);
@@ -315,7 +318,7 @@ pub fn show_details(project:&Project, bill_type: BillType) {
     trace!("print::show_details()");
     println!("{}: {}", bill_type.to_string(), project.short_desc());
 
-    let (offer, invoice) = match project.bills() {
+    let (offer, _) = match project.bills() {
         Ok(tuple) => tuple,
         Err(e) => {
             error!("{}, sorry", e);
@@ -325,7 +328,27 @@ pub fn show_details(project:&Project, bill_type: BillType) {
 
     let bill = match bill_type {
         BillType::Offer => offer,
-        BillType::Invoice => invoice
+        BillType::Invoice => match project.final_invoice() {
+            Ok(final_invoice) => final_invoice,
+            Err(e) => {
+                error!("{}, sorry", e);
+                return
+            }
+        },
+        BillType::Storno => match project.credit_note() {
+            Ok(credit_note) => credit_note,
+            Err(e) => {
+                error!("{}, sorry", e);
+                return
+            }
+        },
+        BillType::Deposit(index) => match project.deposit_bill(index) {
+            Ok(deposit) => deposit,
+            Err(e) => {
+                error!("{}, sorry", e);
+                return
+            }
+        }
     };
 
     // TODO: move to Project::product_table(&self) {
@@ -341,30 +364,31 @@ pub fn show_details(project:&Project, bill_type: BillType) {
             row![ cell!((index+1).to_string()),
                   item.product.name,
                   r->item.amount.to_string(),
-                  r->currency_to_string(&item.product.price),
-                  r->currency_to_string(&(item.gross()))
+                  r->project.format_amount(&item.product.price),
+                  r->project.format_amount(&line_cost(item))
             ]);
     }
 
     table.add_row( row![cell!(""), cell!("======="), cell!(r->"======"), cell!(r->"======"), cell!(r->"======")]);
-    for (&tax, itemlist) in bill.iter() {
+    let totals = Totals::of(&bill);
+    for group in &totals.groups {
         table.add_row( row!["",
                             "",
                             "",
                             "",
-                            cell!(r->itemlist.gross_sum().postfix())
+                            cell!(r->project.format_amount(&group.before_tax))
         ]);
-        if itemlist.tax_sum().value() > 0 {
+        if group.tax_amount.value() > 0 {
             table.add_row( row!["",
                                 "",
                                 "",
-                                cell!(r->format!("+{}%",**tax*100f64)),
-                                cell!(r->format!("{}", itemlist.tax_sum().postfix()))
-                                //cell!(r->itemlist.net_sum().postfix())
+                                cell!(r->format!("+{}%", group.percent())),
+                                cell!(r->project.format_amount(&group.tax_amount))
+                                //cell!(r->group.after_tax().postfix())
             ]);
         }
     }
-    table.add_row( row!["", "Total", "", "", bill.net_total().postfix()]);
+    table.add_row( row!["", "Total", "", "", project.format_amount(&totals.after_tax())]);
     // }
 
     table.printstd();
@@ -378,4 +402,43 @@ pub fn show_details(project:&Project, bill_type: BillType) {
 
     println!("{}", project.hours().employees_string().unwrap_or_else(String::new));
 
+    let attachments = project.attachments();
+    if !attachments.is_empty() {
+        println!("{}", lformat!("Attachments:"));
+        let missing = project.missing_attachments();
+        for attachment in attachments {
+            let is_missing = missing.iter().any(|m| m.file == attachment.file);
+            println!(" * {} {}{}{}{}",
+                     attachment.file,
+                     attachment.kind.map(|k| format!("[{}] ", k)).unwrap_or_default(),
+                     attachment.date.map(|d| format!("{} ", d.format("%d.%m.%Y"))).unwrap_or_default(),
+                     attachment.note.unwrap_or_default(),
+                     if is_missing { lformat!(" (missing)") } else { String::new() });
+        }
+    }
+
+    match project.expenses() {
+        Ok(ref expenses) if !expenses.is_empty() => {
+            println!("{}", lformat!("Expenses:"));
+            let missing = project.missing_receipts().unwrap_or_default();
+            for expense in expenses {
+                let is_missing = missing.iter().any(|m| m.receipt == expense.receipt);
+                println!(" * {} {}{}{}{}",
+                         expense.name,
+                         expense.kind.as_ref().map(|k| format!("[{}] ", k)).unwrap_or_default(),
+                         expense.date.map(|d| format!("{} ", d.format("%d.%m.%Y"))).unwrap_or_default(),
+                         project.format_amount(&expense.amount),
+                         if is_missing { lformat!(" (receipt missing)") } else { String::new() });
+            }
+        }
+        Ok(_) => {},
+        Err(e) => error!("{}", e)
+    }
+
+    if let (Ok(cost), Ok(margin)) = (project.cost(), project.margin()) {
+        if cost.value != 0 {
+            println!("{}", lformat!("Cost: {}, Margin: {}", project.format_amount(&cost), project.format_amount(&margin)));
+        }
+    }
+
 }
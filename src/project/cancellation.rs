@@ -0,0 +1,117 @@
+//! Fees for events that the client canceled.
+//!
+//! A canceled project (`canceled: yes`) is free unless the client canceled late.
+//! When they canceled is noted in the project file:
+//!
+//! ```yaml
+//! canceled: yes
+//! cancellation:
+//!   date: 01.05.2019  # when the client canceled
+//!   percent: 40       # optional, overrides the schedule below
+//!   waived: no        # optional, `yes` makes the cancellation free after all
+//! ```
+//!
+//! How much of the offer is due depends on the days between `cancellation/date` and the event,
+//! as configured under `cancellation_fees`:
+//!
+//! ```yaml
+//! cancellation_fees:
+//!   - { days: 30, percent: 25 } # canceled 30 days or less before the event
+//!   - { days: 7, percent: 50 }
+//!   - { days: 1, percent: 80 }
+//! ```
+//!
+//! The step with the fewest `days` that were not yet passed applies,
+//! canceling earlier than every step is free.
+//! The fee is invoiced like any other project, see [`Redeemable::cancellation_fee()`](../spec/trait.Redeemable.html#tymethod.cancellation_fee).
+
+use failure::{bail, format_err, Error};
+use yaml_rust::Yaml;
+
+use crate::util::yaml;
+use crate::util::decimal::Decimal;
+
+/// Canceling `days` or less before the event costs `percent` of the offer.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FeeStep {
+    pub days: i64,
+    pub percent: Decimal,
+}
+
+/// The `cancellation_fees` of the config.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FeeSchedule {
+    /// Sorted by `days`, fewest first
+    steps: Vec<FeeStep>,
+}
+
+impl FeeSchedule {
+    pub fn from_yaml(yaml: &Yaml) -> Result<Self, Error> {
+        let entries = match *yaml {
+            Yaml::Array(ref entries) => entries,
+            Yaml::Null | Yaml::BadValue => return Ok(Self::default()),
+            _ => bail!("cancellation_fees must be a list of steps"),
+        };
+
+        let mut steps = Vec::new();
+        for entry in entries {
+            let days = yaml::get_int(entry, "days")
+                            .ok_or_else(|| format_err!("every cancellation fee needs its `days`"))?;
+            let percent = yaml::get_decimal(entry, "percent")
+                               .filter(|percent| !percent.is_negative())
+                               .ok_or_else(|| format_err!("the cancellation fee for {} days needs a positive `percent`", days))?;
+            steps.push(FeeStep { days, percent });
+        }
+        steps.sort_by_key(|step| step.days);
+        Ok(FeeSchedule { steps })
+    }
+
+    pub fn from_yaml_str(content: &str) -> Result<Self, Error> {
+        Self::from_yaml(&yaml::parse(content)?)
+    }
+
+    /// Schedule from the `cancellation_fees` section of the config.
+    pub fn from_config() -> Result<Self, Error> {
+        crate::CONFIG.get("cancellation_fees")
+            .map(Self::from_yaml)
+            .unwrap_or_else(|| Ok(Self::default()))
+    }
+
+    pub fn steps(&self) -> &[FeeStep] {
+        &self.steps
+    }
+
+    /// Percentage of the offer that is due when canceling `days` before the event,
+    /// `None` if canceling is free.
+    pub fn percent(&self, days: i64) -> Option<Decimal> {
+        self.steps.iter()
+            .find(|step| days <= step.days)
+            .map(|step| step.percent)
+            .filter(|percent| !percent.is_zero())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_closest_step() {
+        let schedule = FeeSchedule::from_yaml_str("- { days: 7, percent: 50 }\n- { days: 30, percent: 25 }\n- { days: 1, percent: 80 }\n").unwrap();
+        assert_eq!(schedule.steps()[0].days, 1);
+        assert_eq!(schedule.percent(45), None);
+        assert_eq!(schedule.percent(30), Some(Decimal::from(25)));
+        assert_eq!(schedule.percent(8), Some(Decimal::from(25)));
+        assert_eq!(schedule.percent(7), Some(Decimal::from(50)));
+        assert_eq!(schedule.percent(0), Some(Decimal::from(80)));
+        assert_eq!(schedule.percent(-2), Some(Decimal::from(80)));
+    }
+
+    #[test]
+    fn rejects_broken_steps() {
+        assert!(FeeSchedule::from_yaml_str("- { percent: 50 }\n").is_err());
+        assert!(FeeSchedule::from_yaml_str("- { days: 3 }\n").is_err());
+        assert!(FeeSchedule::from_yaml_str("days: 3\n").is_err());
+        assert_eq!(FeeSchedule::from_yaml_str("~").unwrap().percent(0), None);
+    }
+}
@@ -55,6 +55,18 @@ custom_derive! {
         Format,
         /// Directory the project is currently stored in
         Dir,
+        /// Number of attendees, the actual one if known
+        Attendees,
+        /// Amount of money owed by the customer per attendee
+        RevenuePerHead,
+        /// Expenses and purchase prices of the sold products, before tax
+        Cost,
+        /// Revenue before tax minus `Cost` and `Wages`
+        Margin,
+        /// `tax_category` of the project, `standard` if it has none
+        TaxCategory,
+        /// Where the project stands in its lifecycle, e.g. `invoiced`
+        State,
         /// Invalid Option
         Invalid
     }
@@ -118,6 +130,29 @@ impl ComputedField {
                        .and_then(|d| d.strip_prefix(&storage).ok())
                        .map(|d| d.display().to_string())
             }
+            ComputedField::Attendees => project.attendees().for_invoice().map(|n| n.to_string()),
+            ComputedField::RevenuePerHead => {
+                project.sum_sold()
+                       .ok()
+                       .and_then(|sum| Attendees::per_head(sum, project.attendees().for_invoice()))
+                       .map(|c| util::currency_to_string(&c))
+            }
+            ComputedField::Cost => {
+                project.cost()
+                       .map(|c| util::currency_to_string(&c))
+                       .ok()
+            }
+            ComputedField::Margin => {
+                project.margin()
+                       .map(|c| util::currency_to_string(&c))
+                       .ok()
+            }
+            ComputedField::TaxCategory => {
+                Some(project.tax_category()
+                            .map(|category| category.to_string())
+                            .unwrap_or_else(|| String::from("standard")))
+            }
+            ComputedField::State => Some(project.state().to_string()),
             ComputedField::Invalid => None,
 
             // _ => None
@@ -0,0 +1,154 @@
+//! Projects billed in a foreign currency.
+//!
+//! Every amount in a project file is in the currency of the project,
+//! which is the configured `currency` unless the project says otherwise:
+//!
+//! ```yaml
+//! currency: CHF
+//! exchange_rate:     # snapshot, written by `asciii rate`
+//!   rate: 0.89       # what one CHF is worth in the home currency
+//!   date: 03.06.2019
+//! ```
+//!
+//! Documents show the currency of the project,
+//! reports, dues and wages are converted with the snapshot of the exchange rate.
+//! The snapshot is either entered manually or looked up in `extras/exchange_rates.yml`:
+//!
+//! ```yaml
+//! CHF:
+//!   - { date: 01.05.2019, rate: 0.88 }
+//!   - { date: 03.06.2019, rate: 0.89 }
+//! ```
+
+use bill::Currency;
+use chrono::prelude::*;
+use failure::{bail, format_err, Error};
+use yaml_rust::Yaml;
+
+use crate::util::decimal::Decimal;
+use crate::util::yaml;
+
+/// Name of the rates file inside the extras folder.
+pub const RATES_FILE: &str = "exchange_rates.yml";
+
+/// Currencies that have a symbol of their own
+const SYMBOLS: &[(&str, char)] = &[("EUR", '€'), ("USD", '$'), ("GBP", '£'), ("JPY", '¥')];
+
+/// Symbol of a currency given by its code, e.g. `'$'` for `"USD"`.
+pub fn symbol(code: &str) -> Option<char> {
+    let code = code.trim();
+    SYMBOLS.iter()
+           .find(|&&(known, symbol)| known.eq_ignore_ascii_case(code) || code.chars().eq(Some(symbol)))
+           .map(|&(_, symbol)| symbol)
+}
+
+/// Whether `a` and `b` name the same currency, either by code or by symbol.
+pub fn same_currency(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+        || symbol(a).map(|symbol_a| Some(symbol_a) == symbol(b)).unwrap_or(false)
+}
+
+/// Whether `code` is the configured `currency`.
+pub fn is_home(code: &str) -> bool {
+    same_currency(code, crate::CONFIG.get_str("currency"))
+}
+
+/// Formats `amount` like `currency_to_string()` does, but in the given currency:
+/// `12,50$` or `12,50 CHF`.
+pub fn format(amount: &Currency, code: &str) -> String {
+    let number = format!("{:.2}", Decimal::from(*amount)).replace('.', ",");
+    match symbol(code) {
+        Some(symbol) => format!("{}{}", number, symbol),
+        None => format!("{} {}", number, code.trim())
+    }
+}
+
+/// What one unit of a foreign currency was worth in the home currency, at some date.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExchangeRate {
+    pub rate: Decimal,
+    pub date: Option<Date<Utc>>,
+}
+
+impl ExchangeRate {
+    /// Reads `{ rate, date }`.
+    pub fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        let rate = yaml::get_decimal(yaml, "rate").filter(|rate| *rate > Decimal::default())?;
+        let date = yaml::get_str(yaml, "date").and_then(yaml::parse_dmy_date);
+        Some(ExchangeRate { rate, date })
+    }
+
+    /// Converts `amount` into the home currency.
+    pub fn convert(&self, amount: Currency) -> Currency {
+        (Decimal::from(amount) * self.rate).to_currency()
+    }
+}
+
+/// The contents of the rates file: rates per currency code, ordered by date.
+#[derive(Debug, Clone, Default)]
+pub struct RateTable {
+    rates: Vec<(String, ExchangeRate)>,
+}
+
+impl RateTable {
+    pub fn from_yaml_str(content: &str) -> Result<Self, Error> {
+        let doc = yaml::parse(content)?;
+        let currencies = match doc {
+            Yaml::Hash(ref currencies) => currencies,
+            Yaml::Null | Yaml::BadValue => return Ok(Self::default()),
+            // empty file
+            Yaml::String(ref s) if s == "[]" => return Ok(Self::default()),
+            _ => bail!("{} must map currency codes to lists of rates", RATES_FILE)
+        };
+
+        let mut rates = Vec::new();
+        for (code, entries) in currencies {
+            let code = code.as_str().ok_or_else(|| format_err!("{} contains a currency without code", RATES_FILE))?;
+            for entry in entries.as_vec().map(Vec::as_slice).unwrap_or(&[]) {
+                let rate = ExchangeRate::from_yaml(entry)
+                    .filter(|rate| rate.date.is_some())
+                    .ok_or_else(|| format_err!("every rate of {} in {} needs a positive `rate` and a `date`", code, RATES_FILE))?;
+                rates.push((code.to_owned(), rate));
+            }
+        }
+        rates.sort_by_key(|&(_, rate)| rate.date);
+        Ok(RateTable { rates })
+    }
+
+    /// The latest rate of `code` from `date` or before.
+    pub fn rate_at(&self, code: &str, date: Date<Utc>) -> Option<ExchangeRate> {
+        self.rates.iter()
+            .filter(|(known, rate)| same_currency(known, code) && rate.date.map(|d| d <= date).unwrap_or(false))
+            .map(|&(_, rate)| rate)
+            .last()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RATES: &str = "CHF:\n  - { date: 03.06.2019, rate: 0.89 }\n  - { date: 01.05.2019, rate: 0.88 }\nUSD:\n  - { date: 01.05.2019, rate: 0.9 }\n";
+
+    #[test]
+    fn looks_up_latest_rate() {
+        let table = RateTable::from_yaml_str(RATES).unwrap();
+        assert_eq!(table.rate_at("CHF", Utc.ymd(2019, 4, 30)), None);
+        assert_eq!(table.rate_at("CHF", Utc.ymd(2019, 5, 20)).unwrap().rate, "0.88".parse().unwrap());
+        assert_eq!(table.rate_at("chf", Utc.ymd(2019, 6, 3)).unwrap().rate, "0.89".parse().unwrap());
+        assert_eq!(table.rate_at("$", Utc.ymd(2019, 6, 3)).unwrap().rate, "0.9".parse().unwrap());
+        assert!(RateTable::from_yaml_str("CHF:\n  - { rate: 0.9 }\n").is_err());
+        assert!(RateTable::from_yaml_str("- 0.9\n").is_err());
+    }
+
+    #[test]
+    fn converts_and_formats() {
+        let rate = ExchangeRate { rate: "0.89".parse().unwrap(), date: None };
+        let amount = Currency { symbol: None, value: 100_05 };
+        assert_eq!(rate.convert(amount).value, 89_04);
+        assert_eq!(format(&amount, "CHF"), "100,05 CHF");
+        assert_eq!(format(&amount, "usd"), "100,05$");
+        assert!(same_currency("EUR", "€"));
+        assert!(!same_currency("CHF", "€"));
+    }
+}
@@ -1,10 +1,12 @@
-use bill::{Bill, ItemList, Tax};
+use bill::{Bill, Currency};
 use crate::util::currency_to_string;
+use crate::util::decimal::Decimal;
 
 use crate::storage::storable::Storable;
 use crate::project::Project;
 use super::spec::*;
 use super::computed_field::ComputedField;
+use super::totals::{line_cost, TaxGroup, Totals};
 
 pub trait ExportTarget<T> {
     fn export(&self) -> T;
@@ -14,6 +16,23 @@ fn opt_str(opt: Option<&str>) -> Option<String> {
     opt.map(ToOwned::to_owned)
 }
 
+/// Bills that deposits are taken off can fail even if the products are fine,
+/// they are exported empty then, `validate()` reports why.
+fn or_empty<'a>(project: &Project, bill: Result<Bill<Product<'a>>, failure::Error>) -> Bill<Product<'a>> {
+    bill.unwrap_or_else(|error| {
+        log::error!("{}: {}", project.short_desc(), error);
+        Bill::new()
+    })
+}
+
+/// Offer and invoice, both empty if a product or an adjustment is broken, `validate()` reports that.
+fn bills_or_empty(project: &Project) -> (Bill<Product<'_>>, Bill<Product<'_>>) {
+    project.bills().unwrap_or_else(|error| {
+        log::error!("{}: {}", project.short_desc(), error);
+        (Bill::new(), Bill::new())
+    })
+}
+
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "serialization", derive(Serialize))]
 pub struct Client {
@@ -24,6 +43,7 @@ pub struct Client {
     address: Option<String>,
     email: Option<String>,
     addressing: Option<String>,
+    vat_id: Option<String>,
 }
 
 impl ExportTarget<Client> for Project {
@@ -36,6 +56,7 @@ impl ExportTarget<Client> for Project {
             first_name: opt_str(self.client().first_name()),
             title: opt_str(self.client().title()),
             address: opt_str(self.client().address()),
+            vat_id: opt_str(self.client().vat_id()),
         }
     }
 }
@@ -84,14 +105,20 @@ pub struct Employee {
     salary: String,
     time: f64,
     wage: String,
+    role: Option<String>,
+    start: Option<String>,
+    end: Option<String>,
 }
 
-fn export_employee(e: &crate::project::spec::Employee) -> Employee {
+fn export_employee(project: &Project, e: &crate::project::spec::Employee) -> Employee {
     Employee {
         name: e.name.clone(),
         time: e.time,
-        salary:  e.salary.postfix().to_string(),
-        wage:  e.wage.postfix().to_string(),
+        salary:  project.format_amount(&e.salary),
+        wage:  project.format_amount(&e.wage),
+        role: e.role.clone(),
+        start: e.start.map(|t| t.format("%H:%M").to_string()),
+        end: e.end.map(|t| t.format("%H:%M").to_string()),
     }
 }
 
@@ -99,17 +126,17 @@ impl ExportTarget<Service> for Project {
     fn export(&self) -> Service {
         Service {
             time:         self.hours().total_time(),
-            tax:          self.hours().tax().map(|t|t.value()),
+            tax:          self.hours().tax().map(Decimal::as_f64),
             salary:       self.hours().salary()
-                                      .map(|s| s.postfix().to_string()),
+                                      .map(|s| self.format_amount(&s)),
             gross_total:  self.hours().gross_wages()
-                                      .map(|s| s.postfix().to_string()),
+                                      .map(|s| self.format_amount(&s)),
             net_total:    self.hours().net_wages()
-                                      .map(|s| s.postfix().to_string()),
+                                      .map(|s| self.format_amount(&s)),
             employees:    self.hours().employees()
                                       .map(|employees|
                                            employees.iter()
-                                                .map(export_employee)
+                                                .map(|e| export_employee(self, e))
                                                 .collect()
                                       )
         }
@@ -117,7 +144,7 @@ impl ExportTarget<Service> for Project {
 }
 
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serialization", derive(Serialize))]
 pub struct Sum {
     gross_sum: String,
@@ -127,26 +154,36 @@ pub struct Sum {
 }
 
 use super::product::Product;
-fn sums_from_bill(bill: &Bill<Product<'_>>) -> Vec<Sum> {
-    bill.iter()
-        .map(|(tax, list)| Sum::from_itemlist(*tax, list))
+fn sums_from_bill(project: &Project, bill: &Bill<Product<'_>>) -> Vec<Sum> {
+    Totals::of(bill).groups
+        .iter()
+        .map(|group| Sum::from_tax_group(project, group))
         .rev()
         .collect::<Vec<_>>()
 }
 
 impl Sum {
-    pub fn from_itemlist(tax: Tax, list: &ItemList<Product<'_>>) -> Sum {
-        let gross_sum = list.gross_sum();
-        let tax_sum = list.tax_sum();
+    pub fn from_tax_group(project: &Project, group: &TaxGroup) -> Sum {
         Sum {
-            tax_value: (tax.into_inner() * 100.0),
-            gross_sum: currency_to_string(&gross_sum),
-            tax_sum: currency_to_string(&tax_sum),
-            has_tax: (tax.into_inner() > 0f64),
+            tax_value: (group.tax.into_inner() * 100.0),
+            gross_sum: project.format_amount(&group.before_tax),
+            tax_sum: project.format_amount(&group.tax_amount),
+            has_tax: (group.tax.into_inner() > 0f64),
         }
     }
 }
 
+fn per_head(project: &Project, total: Currency, attendees: Option<u32>) -> Option<String> {
+    Attendees::per_head(total, attendees).map(|c| project.format_amount(&c))
+}
+
+/// The tax of a bill in the home currency, only for projects in a foreign currency
+fn home_tax_sum(project: &Project, totals: &Totals) -> Option<String> {
+    project.currency()?;
+    project.exchange_rate()
+           .map(|rate| currency_to_string(&rate.convert(totals.tax())))
+}
+
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "serialization", derive(Serialize))]
 pub struct Offer {
@@ -156,19 +193,27 @@ pub struct Offer {
     sums: Vec<Sum>,
     net_total: String,
     gross_total: String,
+    attendees: Option<u32>,
+    net_per_head: Option<String>,
+    gross_per_head: Option<String>,
 }
 
 
 impl ExportTarget<Offer> for Project {
     fn export(&self) -> Offer {
-        let (offer, _) = self.bills().unwrap();
+        let (offer, _) = bills_or_empty(self);
+        let totals = Totals::of(&offer);
+        let attendees = self.attendees().for_offer();
         Offer {
             // appendix: self.offer().appendix(),
             date: dmy(self.offer().date()),
             number: self.offer().number(),
-            sums: sums_from_bill(&offer),
-            net_total: currency_to_string(&offer.net_total()),
-            gross_total: currency_to_string(&offer.gross_total()),
+            sums: sums_from_bill(self, &offer),
+            net_total: self.format_amount(&totals.after_tax()),
+            gross_total: self.format_amount(&totals.before_tax()),
+            attendees,
+            net_per_head: per_head(self, totals.after_tax(), attendees),
+            gross_per_head: per_head(self, totals.before_tax(), attendees),
         }
     }
 }
@@ -183,27 +228,198 @@ pub struct Invoice {
     sums: Vec<Sum>,
     net_total: String,
     gross_total: String,
+    /// Only for projects in a foreign currency
+    home_tax_sum: Option<String>,
+    attendees: Option<u32>,
+    net_per_head: Option<String>,
+    gross_per_head: Option<String>,
 }
 
 
 impl ExportTarget<Invoice> for Project {
     fn export(&self) -> Invoice {
-        let (_, invoice) = self.bills().unwrap();
+        let (_, invoice) = bills_or_empty(self);
+        let final_invoice = or_empty(self, self.final_invoice());
+        let totals = Totals::of(&invoice);
+        let final_totals = Totals::of(&final_invoice);
+        let attendees = self.attendees().for_invoice();
 
         Invoice {
             date: dmy(self.invoice().date()),
             number: self.invoice().number_str(),
             number_long: self.invoice().number_long_str(),
             official: self.invoice().official(),
-            sums: sums_from_bill(&invoice),
-            net_total: currency_to_string(&invoice.net_total()),
-            gross_total: currency_to_string(&invoice.gross_total()),
+            sums: sums_from_bill(self, &final_invoice),
+            net_total: self.format_amount(&final_totals.after_tax()),
+            gross_total: self.format_amount(&final_totals.before_tax()),
+            home_tax_sum: home_tax_sum(self, &final_totals),
+            attendees,
+            net_per_head: per_head(self, totals.after_tax(), attendees),
+            gross_per_head: per_head(self, totals.before_tax(), attendees),
         }
     }
 }
 
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "serialization", derive(Serialize))]
+pub struct Storno {
+    date: Option<String>,
+    number: Option<String>,
+    number_long: Option<String>,
+    reason: Option<String>,
+    /// Invoice that is canceled
+    invoice_number_long: Option<String>,
+    invoice_date: Option<String>,
+    sums: Vec<Sum>,
+    net_total: String,
+    gross_total: String,
+    home_tax_sum: Option<String>,
+}
+
+
+impl ExportTarget<Option<Storno>> for Project {
+    fn export(&self) -> Option<Storno> {
+        if !self.storno().is_issued() {
+            return None;
+        }
+        let credit_note = or_empty(self, self.credit_note());
+        let totals = Totals::of(&credit_note);
+
+        Some(Storno {
+            date: dmy(self.storno().date()),
+            number: self.storno().number_str(),
+            number_long: self.storno().number_long_str(),
+            reason: self.storno().reason(),
+            invoice_number_long: self.invoice().number_long_str(),
+            invoice_date: dmy(self.invoice().date()),
+            sums: sums_from_bill(self, &credit_note),
+            net_total: self.format_amount(&totals.after_tax()),
+            gross_total: self.format_amount(&totals.before_tax()),
+            home_tax_sum: home_tax_sum(self, &totals),
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize))]
+pub struct Cancellation {
+    date: Option<String>,
+    days_before_event: Option<i64>,
+    percent: f64,
+}
+
+impl ExportTarget<Option<Cancellation>> for Project {
+    fn export(&self) -> Option<Cancellation> {
+        let cancellation = self.cancellation()?;
+        Some(Cancellation {
+            date: dmy(cancellation.date),
+            days_before_event: cancellation.date.and_then(|date| self.event_date().map(|event| (event - date).num_days())),
+            percent: self.cancellation_percent()?.as_f64(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize))]
+pub struct Deposit {
+    name: String,
+    date: Option<String>,
+    number: Option<String>,
+    number_long: Option<String>,
+    percent: Option<f64>,
+    issued: bool,
+    payed: bool,
+    payed_date: Option<String>,
+    sums: Vec<Sum>,
+    products: Vec<ExportProduct>,
+    net_total: String,
+    gross_total: String,
+    home_tax_sum: Option<String>,
+}
+
+impl ExportTarget<Vec<Deposit>> for Project {
+    fn export(&self) -> Vec<Deposit> {
+        let deposits = match self.deposits() {
+            Ok(deposits) => deposits,
+            Err(error) => {
+                log::error!("{}: {}", self.short_desc(), error);
+                return Vec::new();
+            }
+        };
+        deposits.iter()
+            .enumerate()
+            .map(|(index, deposit)| {
+                let bill = or_empty(self, self.deposit_bill(index));
+                let totals = Totals::of(&bill);
+                Deposit {
+                    name: deposit.name.to_owned(),
+                    date: dmy(deposit.date),
+                    number: deposit.number_str(),
+                    number_long: deposit.number_long_str(),
+                    percent: match deposit.value { AdjustmentValue::Percent(percent) => Some(percent.as_f64()), _ => None },
+                    issued: deposit.is_issued(),
+                    payed: deposit.is_payed(),
+                    payed_date: dmy(deposit.payed_date),
+                    sums: sums_from_bill(self, &bill),
+                    products: bill_products(self, &bill),
+                    net_total: self.format_amount(&totals.after_tax()),
+                    gross_total: self.format_amount(&totals.before_tax()),
+                    home_tax_sum: home_tax_sum(self, &totals),
+                }
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize))]
+pub struct ExportCurrency {
+    code: String,
+    /// The configured home currency
+    home: String,
+    rate: Option<String>,
+    date: Option<String>,
+}
+
+impl ExportTarget<Option<ExportCurrency>> for Project {
+    fn export(&self) -> Option<ExportCurrency> {
+        let code = self.currency()?;
+        let rate = self.exchange_rate();
+        Some(ExportCurrency {
+            code: code.to_owned(),
+            home: crate::CONFIG.get_str("currency").to_owned(),
+            rate: rate.map(|rate| rate.rate.to_string().replace('.', ",")),
+            date: dmy(rate.and_then(|rate| rate.date)),
+        })
+    }
+}
+
+/// What the documents have to say about the tax
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize))]
+pub struct TaxNotes {
+    category: String,
+    reverse_charge: bool,
+    /// Reasons for exemption
+    exemptions: Vec<String>,
+}
+
+impl ExportTarget<Option<TaxNotes>> for Project {
+    fn export(&self) -> Option<TaxNotes> {
+        let exemptions = self.tax_exemptions();
+        if !self.is_reverse_charge() && exemptions.is_empty() {
+            return None;
+        }
+        Some(TaxNotes {
+            category: self.tax_category().map(|category| category.to_string()).unwrap_or_else(|| String::from("standard")),
+            reverse_charge: self.is_reverse_charge(),
+            exemptions: exemptions.into_iter().map(ToOwned::to_owned).collect(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize))]
 pub struct ExportProduct {
     name: String,
     price: String,
@@ -213,16 +429,16 @@ pub struct ExportProduct {
     tax: f64,
 }
 
-fn bill_products(bill: &Bill<Product<'_>>) -> Vec<ExportProduct> {
+fn bill_products(project: &Project, bill: &Bill<Product<'_>>) -> Vec<ExportProduct> {
     bill.as_items_with_tax()
         .into_iter()
         .map(|(tax, item)| {
         ExportProduct {
             name: item.product.name.to_string(),
-            price: currency_to_string(&item.product.price),
+            price: project.format_amount(&item.product.price),
             unit: item.product.unit.unwrap_or_else(|| "").to_string(),
             amount: item.amount,
-            cost: currency_to_string(&item.gross()),
+            cost: project.format_amount(&line_cost(&item)),
             tax: tax.value(),
         }
     })
@@ -234,20 +450,50 @@ fn bill_products(bill: &Bill<Product<'_>>) -> Vec<ExportProduct> {
 pub struct Bills {
     pub offer: Vec<ExportProduct>,
     pub invoice: Vec<ExportProduct>,
+    pub storno: Vec<ExportProduct>,
 }
 
 
 impl ExportTarget<Bills> for Project {
     fn export(&self) -> Bills {
-        let (offer, invoice) = self.bills().unwrap();
+        let (offer, _) = bills_or_empty(self);
+        let invoice = or_empty(self, self.final_invoice());
+        let storno = if self.storno().is_issued() { or_empty(self, self.credit_note()) } else { Bill::new() };
 
         Bills {
-            offer: bill_products(&offer),
-            invoice: bill_products(&invoice),
+            offer: bill_products(self, &offer),
+            invoice: bill_products(self, &invoice),
+            storno: bill_products(self, &storno),
         }
     }
 }
 
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize))]
+pub struct ExportAttachment {
+    file: String,
+    kind: Option<String>,
+    date: Option<String>,
+    note: Option<String>,
+    missing: bool,
+}
+
+impl ExportTarget<Vec<ExportAttachment>> for Project {
+    fn export(&self) -> Vec<ExportAttachment> {
+        let missing = self.missing_attachments();
+        self.attachments()
+            .into_iter()
+            .map(|a| ExportAttachment {
+                missing: missing.iter().any(|m| m.file == a.file),
+                date: dmy(a.date),
+                file: a.file,
+                kind: a.kind,
+                note: a.note,
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "serialization", derive(Serialize))]
 pub struct Complete {
@@ -256,10 +502,21 @@ pub struct Complete {
     service: Service,
     offer: Offer,
     invoice: Invoice,
+    storno: Option<Storno>,
+    /// Only if a cancellation fee is due
+    cancellation: Option<Cancellation>,
+    deposits: Vec<Deposit>,
+    /// The deposit that is being exported, see `for_deposit()`
+    deposit: Option<Deposit>,
+    /// Only for projects in a foreign currency
+    currency: Option<ExportCurrency>,
+    /// Only for reverse charge and tax exempt products
+    tax_notes: Option<TaxNotes>,
     bills: Bills,
     checks: Checks,
     errors: Errors,
     extras: Extras,
+    attachments: Vec<ExportAttachment>,
 }
 
 
@@ -271,14 +528,29 @@ impl ExportTarget<Complete> for Project {
             service: self.export(),
             offer: self.export(),
             invoice: self.export(),
+            storno: self.export(),
+            cancellation: self.export(),
+            deposits: self.export(),
+            deposit: None,
+            currency: self.export(),
+            tax_notes: self.export(),
             bills: self.export(),
             checks: self.export(),
             errors: self.export(),
             extras: self.export(),
+            attachments: self.export(),
         }
     }
 }
 
+impl Complete {
+    /// Picks the deposit at `index` as the document to fill in.
+    pub fn for_deposit(mut self, index: usize) -> Complete {
+        self.deposit = self.deposits.get(index).cloned();
+        self
+    }
+}
+
 #[derive(Debug, PartialEq)]
 #[cfg_attr(feature = "serialization", derive(Serialize))]
 pub struct Checks {
@@ -288,6 +560,9 @@ pub struct Checks {
     payed_by_customer: bool,
     payed_employees: bool,
     canceled: bool,
+    storno: bool,
+    /// See `Project::state()`
+    state: String,
 }
 
 impl ExportTarget<Checks> for Project {
@@ -299,6 +574,8 @@ impl ExportTarget<Checks> for Project {
             payed_by_customer: self.is_payed(),
             payed_employees: self.hours().employees_payed(),
             canceled: self.canceled(),
+            storno: self.storno().is_issued(),
+            state: self.state().to_string(),
             // errors: self.is_ready_for_offer().err().map(|list| list.errors)
         }
     }
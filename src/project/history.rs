@@ -0,0 +1,195 @@
+//! Semantic changelog of a project file.
+//!
+//! Consecutive versions of a project file, as committed to the storage repository,
+//! are opened and compared field by field.
+//! Instead of textual diffs this yields entries like "invoice number was set to 42"
+//! or "amount of coffee changed from 20 to 25", together with the effect on `sum_sold`.
+
+use std::fmt;
+use std::path::PathBuf;
+
+use bill::Currency;
+use failure::Error;
+use linked_hash_map::LinkedHashMap;
+use log::warn;
+
+use crate::storage::Storable;
+use crate::storage::repo::{Repository, Revision};
+use crate::util::{self, yaml};
+
+use super::Project;
+use super::spec::*;
+use super::yaml_provider::*;
+
+/// Plain fields that are compared between versions.
+static TRACKED_FIELDS: &[&str] = &[
+    "offer/number",
+    "offer/date",
+    "invoice/number",
+    "invoice/date",
+    "invoice/payed_date",
+    "hours/salary",
+    "hours/wages_date",
+    "canceled",
+];
+
+/// A single field that differs between two versions.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serialization", derive(Serialize))]
+pub struct Change {
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (&self.before, &self.after) {
+            (None, Some(after)) => write!(f, "{}", lformat!("{} set to {}", self.field, after)),
+            (Some(before), None) => write!(f, "{}", lformat!("{} removed (was {})", self.field, before)),
+            (Some(before), Some(after)) => write!(f, "{}", lformat!("{} changed from {} to {}", self.field, before, after)),
+            (None, None) => write!(f, "{}", self.field),
+        }
+    }
+}
+
+/// One commit that changed the project in a meaningful way.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serialization", derive(Serialize))]
+pub struct HistoryEntry {
+    pub commit: String,
+    pub author: String,
+    pub date: String,
+    pub changes: Vec<Change>,
+    /// `sum_sold` after this commit
+    pub sum_sold: Option<String>,
+    /// Difference to `sum_sold` before this commit, if there was any
+    pub sum_sold_delta: Option<String>,
+}
+
+type Snapshot = LinkedHashMap<String, String>;
+
+fn snapshot(project: &Project) -> Snapshot {
+    let mut fields = Snapshot::new();
+    for field in TRACKED_FIELDS {
+        if let Some(value) = yaml::get_to_string(project.yaml(), field) {
+            fields.insert((*field).to_owned(), value);
+        }
+    }
+
+    if let Some(total) = project.hours().total_time() {
+        fields.insert(String::from("hours/total"), total.to_string());
+    }
+
+    if let Some(products) = project.get_hash("products") {
+        for (desc, values) in products {
+            match project.item_from_desc_and_value(desc, values) {
+                Ok((offered, sold)) => {
+                    let name = offered.product.name;
+                    fields.insert(format!("products/{}/price", name), util::currency_to_string(&offered.product.price));
+                    fields.insert(format!("products/{}/amount", name), offered.amount.to_string());
+                    fields.insert(format!("products/{}/sold", name), sold.amount.to_string());
+                },
+                Err(e) => warn!("{}", e)
+            }
+        }
+    }
+
+    fields
+}
+
+fn compare(before: &Snapshot, after: &Snapshot) -> Vec<Change> {
+    let changed = after.iter()
+        .filter(|&(field, value)| before.get(field) != Some(value))
+        .map(|(field, value)| Change {
+            field: field.to_owned(),
+            before: before.get(field).cloned(),
+            after: Some(value.to_owned()),
+        });
+
+    let removed = before.iter()
+        .filter(|&(field, _)| !after.contains_key(field))
+        .map(|(field, value)| Change {
+            field: field.to_owned(),
+            before: Some(value.to_owned()),
+            after: None,
+        });
+
+    changed.chain(removed).collect()
+}
+
+fn delta_to_string(before: &Currency, after: &Currency) -> String {
+    let delta = Currency { symbol: after.symbol, value: after.value - before.value };
+    if delta.value > 0 {
+        format!("+{}", util::currency_to_string(&delta))
+    } else {
+        util::currency_to_string(&delta)
+    }
+}
+
+/// Builds the changelog from revisions and the content of the project file at each of them.
+///
+/// Revisions that can't be parsed are skipped, as are those that don't change any tracked field.
+pub fn changelog(versions: &[(Revision, String)]) -> Vec<HistoryEntry> {
+    let mut entries = Vec::new();
+    let mut last_snapshot = Snapshot::new();
+    let mut last_sum: Option<Currency> = None;
+    let mut last_path: Option<PathBuf> = None;
+
+    for (revision, content) in versions {
+        let project = match Project::from_file_content(content) {
+            Ok(project) => project,
+            Err(e) => {
+                warn!("{}", lformat!("can't read project in {}: {}", revision.commit, e));
+                continue;
+            }
+        };
+
+        let current = snapshot(&project);
+        let sum = project.sum_sold().ok();
+
+        let mut changes = compare(&last_snapshot, &current);
+        if last_path.as_ref().map_or(false, |p| p != &revision.path) {
+            changes.push(Change {
+                field: String::from("file"),
+                before: last_path.as_ref().map(|p| p.display().to_string()),
+                after: Some(revision.path.display().to_string()),
+            });
+        }
+
+        let sum_sold_delta = match (&last_sum, &sum) {
+            (Some(before), Some(after)) if before != after => Some(delta_to_string(before, after)),
+            _ => None
+        };
+
+        if !changes.is_empty() || sum_sold_delta.is_some() {
+            entries.push(HistoryEntry {
+                commit: revision.commit.clone(),
+                author: revision.author.clone(),
+                date: revision.date.clone(),
+                changes,
+                sum_sold: sum.as_ref().map(util::currency_to_string),
+                sum_sold_delta,
+            });
+        }
+
+        last_snapshot = current;
+        last_sum = sum;
+        last_path = Some(revision.path.clone());
+    }
+
+    entries
+}
+
+/// Semantic changelog of `project`, taken from `repo`.
+pub fn of_project(project: &Project, repo: &Repository) -> Result<Vec<HistoryEntry>, Error> {
+    let versions = repo.file_history(&project.file())
+        .into_iter()
+        .filter_map(|revision| {
+            let content = repo.show_revision(&revision)?;
+            Some((revision, content))
+        })
+        .collect::<Vec<_>>();
+
+    Ok(changelog(&versions))
+}
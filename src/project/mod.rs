@@ -8,6 +8,7 @@ use std::ffi::OsStr;
 use std::fmt;
 use std::path::{Path, PathBuf};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use chrono::prelude::*;
 use chrono::Duration;
@@ -21,10 +22,11 @@ use failure::{bail, Error};
 use bill::BillItem;
 use icalendar::*;
 use semver::Version;
-use log::{debug, trace, error, warn};
+use log::{debug, trace, error};
 
 use crate::util::{yaml, get_valid_path};
-use crate::storage::{Storable, list_path_content};
+use crate::util::decimal::Decimal;
+use crate::storage::{Backend, FsBackend, Storable, list_path_content};
 use crate::storage::StorableAndTempDir;
 use crate::storage::StorageError;
 use crate::storage::repo::GitStatus;
@@ -36,6 +38,13 @@ mod spec_yaml;
 mod yaml_provider;
 
 pub mod error;
+pub mod history;
+pub mod reminders;
+pub mod cancellation;
+pub mod totals;
+pub mod exchange;
+pub mod tax_category;
+pub mod state;
 mod computed_field;
 
 #[cfg(test)]
@@ -43,10 +52,11 @@ mod tests;
 
 #[cfg(feature="deserialization")] pub mod import;
 #[cfg(feature="serialization")] pub mod export;
+#[cfg(feature="serialization")] pub mod offer_versions;
 #[cfg(feature="serialization")] use self::export::*;
 
 use self::spec::{IsProject, IsClient};
-use self::spec::{Offerable, Invoicable, Redeemable, Validatable, HasEmployees};
+use self::spec::{Offerable, Invoicable, Cancelable, Redeemable, Validatable, HasEmployees, HasEvents, HasTaxCategory};
 use self::yaml_provider::*;
 
 pub use self::error:: ErrorList;
@@ -55,6 +65,9 @@ use self::product::{Product, ProductError};
 
 pub use self::computed_field::ComputedField;
 
+/// Name of the folder inside a project folder that holds attached documents.
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
 /// Represents a Project.
 ///
 /// A project is storable, contains products, and you can create an offer or invoice from it.
@@ -63,8 +76,10 @@ pub use self::computed_field::ComputedField;
 pub struct Project {
     file_path: PathBuf,
     git_status: Option<GitStatus>,
+    archived: bool,
     file_content: String,
-    yaml: Yaml
+    yaml: Yaml,
+    backend: Arc<dyn Backend>
 }
 
 impl Project {
@@ -78,11 +93,13 @@ impl Project {
         Ok(Project {
             file_path: file_path.to_owned(),
             git_status: None,
+            archived: false,
             yaml: yaml::parse(&file_content).unwrap_or_else(|e|{
                 error!("syntax error in {}\n  {}", file_path.display(), e);
                 Yaml::Null
             }),
             file_content,
+            backend: Arc::new(FsBackend),
         })
     }
 
@@ -128,8 +145,10 @@ impl Project {
         Ok(Project{
             file_path: PathBuf::new(),
             git_status: None,
-            yaml: yaml::parse(&content).unwrap(),
+            archived: false,
+            yaml: yaml::parse(&content)?,
             file_content: String::from(content),
+            backend: Arc::new(FsBackend),
         })
     }
 
@@ -160,6 +179,21 @@ impl Project {
         Hours { inner: self }
     }
 
+    /// Returns the struct `Storno`, which abstracts away the credit note that cancels the invoice.
+    pub fn storno(&self) -> Storno<'_> {
+        Storno { inner: self }
+    }
+
+    /// Where the project stands in its lifecycle, see [`State`](state/enum.State.html).
+    pub fn state(&self) -> state::State {
+        state::State::of(self, Utc::today())
+    }
+
+    /// Whether the storage opened the project from the archive rather than the working directory.
+    pub fn is_archived(&self) -> bool {
+        self.archived
+    }
+
     /// Ready to produce offer.
     ///
     /// Ready to send an **offer** to the client.
@@ -181,15 +215,64 @@ impl Project {
             )
     }
 
+    /// Valid to produce a credit note
+    ///
+    /// The invoice is ready and `asciii storno` assigned a number.
+    pub fn is_ready_for_storno(&self) -> SpecResult {
+        self::error::combine_spec_results(
+            vec![ self.is_ready_for_invoice(),
+                  self.storno().validate()]
+            )
+    }
+
+    /// Valid to produce a deposit invoice
+    ///
+    /// The offer is ready and the deposit at `index` has a number and a date.
+    pub fn is_ready_for_deposit(&self, index: usize) -> SpecResult {
+        let mut errors = ErrorList::new();
+        match self.deposits().map(|deposits| deposits.get(index).cloned()) {
+            Ok(Some(deposit)) => {
+                if deposit.number.is_none() {
+                    errors.push("deposit number")
+                }
+                if deposit.date.is_none() {
+                    errors.push("deposit date")
+                }
+            }
+            _ => errors.push("deposits")
+        }
+        let deposit = if errors.is_empty() { Ok(()) } else { Err(errors) };
+
+        self::error::combine_spec_results(
+            vec![ self.is_ready_for_offer(),
+                  deposit ]
+            )
+    }
+
     /// Completely done and in the past.
     ///
     /// Ready to be **h:
     pub fn is_ready_for_archive(&self) -> SpecResult {
-        if self.canceled(){
+        if self.canceled() && !self.is_billable() {
             Ok(())
+        } else if self.canceled() {
+            // the cancellation fee has to be payed, nobody worked
+            Redeemable::validate(self)
+        } else if self.storno().is_issued() {
+            // nothing is payed for the invoice any more, but whoever worked still is
+            self::error::combine_spec_results(
+                vec![ self.storno().validate(),
+                      self.hours().validate() ]
+                )
         } else {
+            // only projects whose wages are payed may leave the working directory
+            let state = match self.state() {
+                state::State::WagesPaid | state::State::Archived => Ok(()),
+                _ => Err(ErrorList::from(&["state"][..]))
+            };
             self::error::combine_spec_results(
-                vec![ Redeemable::validate(self),
+                vec![ state,
+                      Redeemable::validate(self),
                       self.hours().validate() ]
                 )
         }
@@ -197,8 +280,13 @@ impl Project {
 
     pub fn to_csv(&self, bill_type: BillType) -> Result<String, Error>{
         use std::fmt::Write;
-        let (offer, invoice) = self.bills()?;
-        let bill = match bill_type{ BillType::Offer => offer, BillType::Invoice => invoice };
+        let (offer, _) = self.bills()?;
+        let bill = match bill_type{
+            BillType::Offer => offer,
+            BillType::Invoice => self.final_invoice()?,
+            BillType::Storno => self.credit_note()?,
+            BillType::Deposit(index) => self.deposit_bill(index)?
+        };
         let mut csv_string = String::new();
         let splitter = ";";
 
@@ -210,9 +298,9 @@ impl Project {
                                         write!(&mut csv_string, "{};",  &index.to_string())?;
                                         write!(&mut csv_string, "{};",  item.product.name)?;
                                         write!(&mut csv_string, "{};",  item.amount.to_string())?;
-                                        write!(&mut csv_string, "{:.2};",  item.product.price.as_float())?;
+                                        write!(&mut csv_string, "{:.2};",  Decimal::from(item.product.price))?;
                                         write!(&mut csv_string, "{:.2};",  item.product.tax)?;
-                                        writeln!(&mut csv_string, "{:.2}", (item.product.price * item.amount).as_float())?;
+                                        writeln!(&mut csv_string, "{:.2}", totals::line_total(item, totals::Rounding::Line))?;
             }
         }
         Ok(csv_string)
@@ -235,22 +323,50 @@ impl Project {
             .finalize()
             .filled;
 
-        match yaml::parse(&filled){
-            Ok(_) => {
-                let mut file = File::create(self.file())?;
-                file.write_all(filled.as_bytes())?;
-                file.sync_all()?;
-                Ok(())
-            },
+        self.write_content(&filled)
+    }
+
+    /// Append an entry to a top level list, comments and formatting of the file are kept
+    pub fn append_to_list(&self, key:&str, entry:&str) -> Result<(), Error> {
+        let appended = yaml::append_to_list(&self.file_content, key, entry)?;
+        self.write_content(&appended)
+    }
+
+    /// Set a value, e.g. `hours/wages_date`, comments and formatting of the file are kept
+    pub fn set_value(&self, path:&str, value:&str) -> Result<(), Error> {
+        let changed = yaml::set_value(&self.file_content, path, value)?;
+        self.write_content(&changed)
+    }
+
+    /// Text of the project file as it was opened
+    pub fn file_content(&self) -> &str {
+        &self.file_content
+    }
+
+    /// The `Backend` the project was opened from, the filesystem unless it came out of a `Storage`
+    pub fn backend(&self) -> &dyn Backend {
+        &*self.backend
+    }
+
+    /// Replaces the content of the project file, if `content` is valid yaml
+    pub fn write_content(&self, content:&str) -> Result<(), Error> {
+        match yaml::parse(content){
+            Ok(_) => self.backend.write(&self.file(), content),
             Err(e) => {
                 error!("The resulting document is no valid yaml. SORRY!\n{}\n\n{}",
-                       filled.lines().enumerate().map(|(n,l)| format!("{:>3}. {}\n",n,l)).collect::<String>(), //line numbers :D
+                       content.lines().enumerate().map(|(n,l)| format!("{:>3}. {}\n",n,l)).collect::<String>(), //line numbers :D
                        e);
                 bail!(e)
             }
         }
     }
 
+    /// Folder next to the project file that attachments are kept in,
+    /// `None` if the project was not read from a file
+    pub fn attachments_dir(&self) -> Option<PathBuf> {
+        self.file().parent().map(|dir| dir.join(ATTACHMENTS_DIR))
+    }
+
     /// Time between event and creation of invoice
     pub fn our_bad(&self) -> Option<Duration> {
         let event   = self.event_date()?;
@@ -270,113 +386,74 @@ impl Project {
         Some(invoice.signed_duration_since(payed))
     }
 
-     /// What I need to do
+    /// Identifies the project in calendars.
     ///
-    /// Produces an iCal calendar from this project.
-    pub fn to_tasks(&self) -> Calendar {
-        //return if self.canceled();
-        let mut cal = Calendar::new();
-
-        let event   = self.event_date();
-        let invoice = self.invoice().date();
-        let payed   = self.payed_date();
-        let wages   = self.hours().wages_date();
-        let today   = Utc::today();
-
-        let days_since = |date:Date<Utc>| (today.signed_duration_since(date)).num_days();
-
-        if let Some(event) = event {
-            match (invoice, payed, wages) {
-
-                // we need to issue an invoice invoice
-                (None,          None,          _) if today >= event => { cal.push(self.task_issue_invoice(event)); },
-                (None,          None,          _) if today < event => { /* no need to worry yet */ },
-
-                // they haven't payed us yet
-                (Some(invoice), None,          _) if days_since(invoice) >= 14 => { cal.push(self.task_follow_up(invoice)); },
-                (Some(invoice), None,          _) if days_since(invoice) < 14 => { /* they have 14 days before we complain */ },
-
-                // we need to pay the employees
-                (Some(_),       Some(payed),   None) => { cal.push(self.task_pay_employees(payed)); },
+    /// This is the name of the project folder without the prefix it gets when archived,
+    /// followed by the date the project was created to tell apart projects that share a name.
+    /// Older projects without `created` use the date of the event.
+    /// Nothing that is written later, like the offer number, may go in here,
+    /// otherwise calendars would see a new event.
+    pub fn calendar_uid(&self) -> String {
+        let ident = if self.file_path.as_os_str().is_empty() {
+            slug::slugify(IsProject::name(self).unwrap_or("unnamed"))
+        } else {
+            Storable::ident(self)
+        };
+        let ident = match self.prefix() {
+            Some(ref prefix) if ident.starts_with(&format!("{}_", prefix)) => ident[prefix.len() + 1..].to_owned(),
+            _ => ident
+        };
+        let distinction = self.get_dmy("created")
+            .or_else(|| self.event_date())
+            .map(|d| d.format("%Y%m%d").to_string())
+            .unwrap_or_default();
+        format!("{}-{}", ident, distinction)
+    }
 
-                // everything's all set to close this
-                (Some(_),       Some(_),       Some(wages)) if days_since(wages) > 7 => { cal.push(self.task_close_project(wages)); },
-                _ => warn!("{}", lformat!("weird task edgecase in {:?}:\n{:?}", self.file(), (event, invoice, payed, wages) ))
+    /// When the project file was last written to.
+    pub fn last_modified(&self) -> Option<DateTime<Utc>> {
+        self.backend.modified(&self.file_path)
+    }
 
+    /// What I need to do
+    ///
+    /// Produces an iCal calendar with a task for every reminder of kind `task` that `rules` give rise to.
+    pub fn to_tasks(&self, rules: &reminders::Rules) -> Calendar {
+        let mut cal = Calendar::new();
+        for reminder in rules.check(self, Utc::today()) {
+            if reminder.kind == reminders::Kind::Task {
+                cal.push(reminder.to_todo(&self.calendar_uid()));
             }
         }
         cal
     }
 
-    fn task_issue_invoice(&self, event_date: Date<Utc>) -> Todo {
-        Todo::new().summary(&lformat!("Create an Invoice"))
-                   .due(&(event_date + Duration::days(14)).and_hms(11, 10, 0))
-                   .priority(6)
-                   .done()
-    }
-
-    fn task_pay_employees(&self, payed_date: Date<Utc>) -> Todo {
-        let days_since_payed = (Utc::today().signed_duration_since(payed_date)).num_days();
-        Todo::new().summary(&lformat!("{}: Hungry employees!", self.invoice().number_str().unwrap_or_else(String::new)))
-            .description( &lformat!("Pay {}\nYou have had the money for {} days!",
-                                   self.hours().employees_string().unwrap_or_else(String::new),
-                                   days_since_payed))
-            .due(&(payed_date + Duration::days(14)).and_hms(11, 10, 0))
-            .done()
-    }
-
-    fn task_follow_up(&self, invoice_date: Date<Utc>) -> Todo {
-        let days_since_invoice = (Utc::today().signed_duration_since(invoice_date)).num_days();
-        let mut follow_up = Todo::new();
-        follow_up.summary( &lformat!("Inquire about: \"{event}\"!", event = self.name().unwrap()));
-        follow_up.description(&lformat!("{inum }{event:?} on {invoice_date} ({days} days ago) was already invoiced but is still not marked as payed.\nPlease check for incoming payments! You can ask {client} ({mail}).",
-                                       event = self.name().unwrap(),
-                                       days = days_since_invoice,
-                                       inum = self.invoice().number_str().unwrap_or_else(String::new),
-                                       invoice_date = invoice_date.format("%d.%m.%Y").to_string(),
-                                       client = self.client().full_name().unwrap_or_else(String::new),
-                                       mail = self.client().email().unwrap_or(""),
-                                       ));
-        follow_up.priority(3);
-        if days_since_invoice > 14 {
-            follow_up.summary( &lformat!("{rnum}: payment is {weeks} weeks late: \"{event}\"",
-                                        rnum = self.invoice().number_str().unwrap_or_else(String::new),
-                                        weeks = days_since_invoice / 7,
-                                        event = self.name().unwrap()),
-                             );
-            follow_up.priority(10);
-        }
-        follow_up
-    }
-
-    fn task_close_project(&self, wages_date: Date<Utc>) -> Todo {
-            let days_since_wages = (Utc::today().signed_duration_since(wages_date)).num_days();
-            Todo::new().summary( &lformat!("Archive {}", self.name().unwrap()))
-                       .description( &lformat!("{:?} has been finished for {} days, get rid of it!",
-                                              self.name().unwrap(),
-                                              days_since_wages))
-                       .done()
-    }
-
     fn item_from_desc_and_value<'y>(&self, desc: &'y Yaml, values: &'y Yaml) -> Result<(BillItem<Product<'y>>,BillItem<Product<'y>>), Error> {
-        let get_f64 = |yaml, path|
+        let get_decimal = |yaml, path|
             self.get_direct(yaml,path)
-                .and_then(|y| y.as_f64()
-                               .or_else(|| y.as_i64()
-                                     .map(|y|y as f64)
-                                  )
-                         );
+                .and_then(Decimal::from_yaml);
+
+        let mut product = Product::from_desc_and_value(desc, values, self.tax())?;
+        if self.is_zero_rated() {
+            product = product.with_rate(Decimal::default());
+        }
 
-        let product = Product::from_desc_and_value(desc, values, self.tax())?;
+        // products priced per head get their amount from the number of attendees
+        let per_head = get_decimal(values, "per_head").filter(|_| get_decimal(values, "amount").is_none());
+        let heads = |count: Option<u32>| count.map(|count| Decimal::from(i64::from(count)))
+                                              .ok_or_else(|| ProductError::MissingAttendees(product.name.to_owned()));
 
-        let offered = get_f64(values, "amount")
+        let offered = match per_head {
+            Some(per_head) => per_head * heads(self.attendees().for_offer())?,
+            None => get_decimal(values, "amount")
                            .ok_or_else(
                                || ProductError::MissingAmount(product.name.to_owned())
-                               )?;
+                               )?
+        };
 
-        let sold = get_f64(values, "sold");
+        let sold = get_decimal(values, "sold");
         // TODO: test this
-        let sold = if let Some(returned) = get_f64(values, "returned") {
+        let sold = if let Some(returned) = get_decimal(values, "returned") {
             // if "returned", there must be no "sold"
             if sold.is_some() {
                 bail!(ProductError::AmbiguousAmounts(product.name.to_owned()));
@@ -384,28 +461,43 @@ impl Project {
             if returned > offered {
                 bail!(ProductError::TooMuchReturned(product.name.to_owned()));
             }
-            offered - returned
+            offered + -returned
         } else if let Some(sold) = sold {
             sold
+        } else if let Some(per_head) = per_head {
+            per_head * heads(self.attendees().for_invoice())?
         } else {
             offered
         };
 
-        Ok(( BillItem{ amount: offered, product }, BillItem{ amount: sold, product }))
+        Ok(( product.item(offered), product.item(sold) ))
+    }
+
+    /// Whether any product is priced per head.
+    fn has_per_head_products(&self) -> bool {
+        self.get_hash("products")
+            .map(|products| products.values().any(|values| self.get_direct(values, "per_head").is_some()))
+            .unwrap_or(false)
     }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum BillType{
     Offer,
-    Invoice
+    Invoice,
+    /// Credit note canceling the invoice
+    Storno,
+    /// Deposit invoice, by its position under `deposits`
+    Deposit(usize)
 }
 
 impl ToString for BillType{
     fn to_string(&self) -> String {
         match *self{
             BillType::Offer => "Offer",
-            BillType::Invoice => "Invoice"
+            BillType::Invoice => "Invoice",
+            BillType::Storno => "Storno",
+            BillType::Deposit(_) => "Deposit"
         }.to_owned()
     }
 }
@@ -421,17 +513,27 @@ pub trait Exportable {
     /// Filename of the invoice output file. **Careful!** uses today's date.
     fn invoice_file_name(&self, extension: &str) -> Option<String>;
 
+    /// Filename of the credit note output file.
+    fn storno_file_name(&self, extension: &str) -> Option<String>;
+
+    /// Filename of the output file of the deposit at `index`.
+    fn deposit_file_name(&self, index: usize, extension: &str) -> Option<String>;
+
     fn output_file_exists(&self, bill_type: BillType) -> bool {
         match bill_type{
             BillType::Offer   => self.offer_file_exists(),
-            BillType::Invoice => self.invoice_file_exists()
+            BillType::Invoice => self.invoice_file_exists(),
+            BillType::Storno  => self.storno_file_exists(),
+            BillType::Deposit(index) => self.deposit_file_exists(index)
         }
     }
 
     fn output_file(&self, bill_type: BillType) -> Option<PathBuf> {
         match bill_type{
             BillType::Offer   => self.offer_file(),
-            BillType::Invoice => self.invoice_file()
+            BillType::Invoice => self.invoice_file(),
+            BillType::Storno  => self.storno_file(),
+            BillType::Deposit(index) => self.deposit_file(index)
         }
     }
 
@@ -453,6 +555,24 @@ pub trait Exportable {
         }
     }
 
+    fn storno_file(&self) -> Option<PathBuf>{
+        let output_folder = get_valid_path(crate::CONFIG.get_str("output_path"));
+        let convert_ext  = crate::CONFIG.get_str("document_export/output_extension");
+        match (output_folder, self.storno_file_name(convert_ext)) {
+            (Some(folder), Some(name)) => folder.join(&name).into(),
+            _ => None
+        }
+    }
+
+    fn deposit_file(&self, index: usize) -> Option<PathBuf>{
+        let output_folder = get_valid_path(crate::CONFIG.get_str("output_path"));
+        let convert_ext  = crate::CONFIG.get_str("document_export/output_extension");
+        match (output_folder, self.deposit_file_name(index, convert_ext)) {
+            (Some(folder), Some(name)) => folder.join(&name).into(),
+            _ => None
+        }
+    }
+
     fn offer_file_exists(&self) -> bool {
         self.offer_file().map(|f|f.exists()).unwrap_or(false)
     }
@@ -461,6 +581,14 @@ pub trait Exportable {
         self.invoice_file().map(|f|f.exists()).unwrap_or(false)
     }
 
+    fn storno_file_exists(&self) -> bool {
+        self.storno_file().map(|f|f.exists()).unwrap_or(false)
+    }
+
+    fn deposit_file_exists(&self, index: usize) -> bool {
+        self.deposit_file(index).map(|f|f.exists()).unwrap_or(false)
+    }
+
     fn write_to_path<P:AsRef<OsStr> + fmt::Debug>(content: &str, target: &P) -> Result<(), Error> {
         trace!("writing content ({}bytes) to {:?}", content.len(), target);
         let mut file = File::create(Path::new(target))?;
@@ -472,7 +600,9 @@ pub trait Exportable {
     fn full_file_path(&self, bill_type: BillType, ext: &str) -> Result<PathBuf, Error> {
         match bill_type {
             BillType::Offer   => self.full_offer_file_path(ext),
-            BillType::Invoice => self.full_invoice_file_path(ext)
+            BillType::Invoice => self.full_invoice_file_path(ext),
+            BillType::Storno  => self.full_storno_file_path(ext),
+            BillType::Deposit(index) => self.full_deposit_file_path(index, ext)
         }
     }
 
@@ -492,10 +622,28 @@ pub trait Exportable {
         }
     }
 
+    fn full_storno_file_path(&self, ext: &str) -> Result<PathBuf, Error> {
+        if let Some(target) = self.storno_file_name(ext) {
+            Ok(self.export_dir().join(&target))
+        } else {
+            bail!(ProjectError::CantDetermineTargetFile)
+        }
+    }
+
+    fn full_deposit_file_path(&self, index: usize, ext: &str) -> Result<PathBuf, Error> {
+        if let Some(target) = self.deposit_file_name(index, ext) {
+            Ok(self.export_dir().join(&target))
+        } else {
+            bail!(ProjectError::CantDetermineTargetFile)
+        }
+    }
+
     fn write_to_file(&self, content: &str, bill_type: BillType, ext: &str) -> Result<PathBuf, Error> {
         match bill_type{
             BillType::Offer   => self.write_to_offer_file(content, ext),
-            BillType::Invoice => self.write_to_invoice_file(content, ext)
+            BillType::Invoice => self.write_to_invoice_file(content, ext),
+            BillType::Storno  => self.write_to_storno_file(content, ext),
+            BillType::Deposit(index) => self.write_to_deposit_file(content, index, ext)
         }
     }
 
@@ -510,6 +658,18 @@ pub trait Exportable {
         Self::write_to_path(content, &full_path)?;
         Ok(full_path)
     }
+
+    fn write_to_storno_file(&self, content: &str, ext: &str) -> Result<PathBuf, Error> {
+        let full_path = self.full_storno_file_path(ext)?;
+        Self::write_to_path(content, &full_path)?;
+        Ok(full_path)
+    }
+
+    fn write_to_deposit_file(&self, content: &str, index: usize, ext: &str) -> Result<PathBuf, Error> {
+        let full_path = self.full_deposit_file_path(index, ext)?;
+        Self::write_to_path(content, &full_path)?;
+        Ok(full_path)
+    }
 }
 
 impl Exportable for Project {
@@ -529,6 +689,21 @@ impl Exportable for Project {
         Some(format!("{} {} {}.{}",num,name,date,extension))
     }
 
+    fn storno_file_name(&self, extension: &str) -> Option<String>{
+        let num = self.storno().number_str()?;
+        let name = slug::slugify(self.name()?);
+        let date = self.storno().date()?.format("%Y-%m-%d").to_string();
+        Some(format!("{} Storno {} {}.{}",num,name,date,extension))
+    }
+
+    fn deposit_file_name(&self, index: usize, extension: &str) -> Option<String>{
+        let deposit = *self.deposits().ok()?.get(index)?;
+        let num = deposit.number_str()?;
+        let name = slug::slugify(self.name()?);
+        let date = deposit.date?.format("%Y-%m-%d").to_string();
+        Some(format!("{} Abschlag {} {}.{}",num,name,date,extension))
+    }
+
 }
 
 impl Storable for Project {
@@ -536,7 +711,7 @@ impl Storable for Project {
         crate::CONFIG.get_to_string("extensions.project_file")
     }
 
-    fn from_template(project_name: &str, template:&Path, fill: &HashMap<&str, String>) -> Result<StorableAndTempDir<Self>, Error> {
+    fn from_template(project_name: &str, template:&Path, template_content: &str, fill: &HashMap<&str, String>) -> Result<StorableAndTempDir<Self>, Error> {
         let template_name = template.file_stem().unwrap().to_str().unwrap();
 
         let event_date = (Utc::today() + Duration::days(14)).format("%d.%m.%Y").to_string();
@@ -557,7 +732,7 @@ impl Storable for Project {
         };
 
         // fills the template
-        let file_content = Templater::from_file(template)?
+        let file_content = Templater::new(template_content)
             .fill_in_data(&fill).fix()
             .fill_in_data(&default_fill)
             .finalize()
@@ -588,8 +763,10 @@ impl Storable for Project {
         let project = Project {
             file_path: temp_file,
             git_status: None,
+            archived: false,
             file_content,
-            yaml
+            yaml,
+            backend: Arc::new(FsBackend),
         };
 
         Ok(StorableAndTempDir {
@@ -641,6 +818,14 @@ impl Storable for Project {
         self.git_status = Some(status);
     }
 
+    fn set_archived(&mut self, archived: bool){
+        self.archived = archived;
+    }
+
+    fn set_backend(&mut self, backend: Arc<dyn Backend>){
+        self.backend = backend;
+    }
+
     /// Ask a project for its gitstatus
     #[cfg(feature="git_statuses")]
     fn get_git_status(&self) -> GitStatus{
@@ -665,6 +850,12 @@ impl Storable for Project {
         Ok(Project::open(file_path)?)
     }
 
+    fn open_content(file_path:&Path, content: &str) -> Result<Project, Error> {
+        let mut project = Project::from_file_content(content)?;
+        project.set_file(file_path);
+        Ok(project)
+    }
+
     /// Checks against a certain key-val pair.
     fn matches_filter(&self, key: &str, val: &str) -> bool{
         self.field(key).map_or(false, |c| c.to_lowercase().contains(&val.to_lowercase()))
@@ -707,6 +898,11 @@ pub struct Hours<'a> {
     inner: &'a Project
 }
 
+/// This is returned by [`Product::storno()`](struct.Project.html#method.storno).
+pub struct Storno<'a> {
+    inner: &'a Project
+}
+
 /// Output of `Project::debug()`.
 ///
 /// A project is storable, contains products, and you can create an offer or invoice from it.
@@ -0,0 +1,254 @@
+//! Every offer that was sent, and what the client answered.
+//!
+//! Raising `offer/appendix` reissues the offer, but the project file only knows the current amounts.
+//! So whenever an offer is exported, its [`Complete`](../export/struct.Complete.html) export
+//! is kept in `offers/<number>.json` inside the project folder, together with the path of the pdf.
+//!
+//! `asciii offer accept` and `asciii offer reject` note the answer of the client,
+//! see [`actions::answer_offer()`](../../actions/fn.answer_offer.html):
+//!
+//! ```yaml
+//! offer:
+//!   date: 01.05.2019
+//!   appendix: 2
+//!   rejected: 05.05.2019
+//!   rejected_appendix: 1
+//!   accepted: 10.05.2019
+//!   accepted_appendix: 2
+//!   note: "fewer vegetarian dishes"
+//! ```
+//!
+//! Versions can be compared with each other, and the invoice with the accepted version.
+
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use chrono::prelude::*;
+use failure::{format_err, Error};
+use serde_json::Value;
+
+use crate::storage::Storable;
+use super::Project;
+use super::export::{Complete, ExportTarget};
+use super::spec::*;
+
+/// Folder inside the project folder that keeps the snapshots
+pub const OFFERS_DIR: &str = "offers";
+
+#[derive(Serialize)]
+struct Stored<'a> {
+    number: &'a str,
+    appendix: i64,
+    exported: String,
+    pdf: String,
+    document: &'a Complete,
+}
+
+/// One line of an offer or invoice
+#[derive(Debug, Clone, PartialEq)]
+pub struct Line {
+    pub name: String,
+    pub amount: f64,
+    pub price: String,
+    pub cost: String,
+}
+
+/// An offer as it was exported, or the current invoice
+#[derive(Debug, Clone, PartialEq)]
+pub struct Version {
+    pub number: String,
+    /// `0` for the invoice
+    pub appendix: i64,
+    pub exported: Option<String>,
+    pub pdf: Option<String>,
+    pub lines: Vec<Line>,
+    pub total: String,
+}
+
+/// How one version differs from another
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(Line),
+    Removed(Line),
+    Changed { before: Line, after: Line },
+    Total { before: String, after: String },
+}
+
+fn lines_of(products: &Value) -> Vec<Line> {
+    products.as_array()
+            .map(|products| products.iter()
+                 .map(|product| Line {
+                     name: product["name"].as_str().unwrap_or_default().to_owned(),
+                     amount: product["amount"].as_f64().unwrap_or_default(),
+                     price: product["price"].as_str().unwrap_or_default().to_owned(),
+                     cost: product["cost"].as_str().unwrap_or_default().to_owned(),
+                 })
+                 .collect())
+            .unwrap_or_else(Vec::new)
+}
+
+impl Version {
+    /// Reads a snapshot as written by `store()`.
+    pub fn from_json(json: &str) -> Result<Self, Error> {
+        let stored: Value = serde_json::from_str(json)?;
+        let document = &stored["document"];
+        Ok(Version {
+            number: stored["number"].as_str()
+                                    .ok_or_else(|| format_err!("offer snapshot without number"))?
+                                    .to_owned(),
+            appendix: stored["appendix"].as_i64().unwrap_or(1),
+            exported: stored["exported"].as_str().map(ToOwned::to_owned),
+            pdf: stored["pdf"].as_str().map(ToOwned::to_owned),
+            lines: lines_of(&document["bills"]["offer"]),
+            total: document["offer"]["net_total"].as_str().unwrap_or_default().to_owned(),
+        })
+    }
+
+    /// The final invoice as it would be exported now.
+    pub fn of_invoice(project: &Project) -> Result<Self, Error> {
+        project.final_invoice()?;
+        let complete: Complete = project.export();
+        let document = serde_json::to_value(&complete)?;
+        Ok(Version {
+            number: project.invoice().number_str().unwrap_or_else(|| String::from("invoice")),
+            appendix: 0,
+            exported: None,
+            pdf: None,
+            lines: lines_of(&document["bills"]["invoice"]),
+            total: document["invoice"]["net_total"].as_str().unwrap_or_default().to_owned(),
+        })
+    }
+}
+
+/// Lines are matched by name, the total is compared as printed.
+pub fn diff(before: &Version, after: &Version) -> Vec<Change> {
+    let mut changes = Vec::new();
+    for old in &before.lines {
+        match after.lines.iter().find(|new| new.name == old.name) {
+            None => changes.push(Change::Removed(old.clone())),
+            Some(new) if new != old => changes.push(Change::Changed { before: old.clone(), after: new.clone() }),
+            Some(_) => {}
+        }
+    }
+    for new in after.lines.iter().filter(|new| !before.lines.iter().any(|old| old.name == new.name)) {
+        changes.push(Change::Added(new.clone()));
+    }
+    if before.total != after.total {
+        changes.push(Change::Total { before: before.total.clone(), after: after.total.clone() });
+    }
+    changes
+}
+
+impl fmt::Display for Line {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} × {} = {}", self.amount, self.price, self.cost)
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Change::Added(ref line) => write!(f, "+ {}: {}", line.name, line),
+            Change::Removed(ref line) => write!(f, "- {}: {}", line.name, line),
+            Change::Changed { ref before, ref after } => write!(f, "~ {}: {} → {}", after.name, before, after),
+            Change::Total { ref before, ref after } => write!(f, "  total: {} → {}", before, after),
+        }
+    }
+}
+
+fn offers_dir(project: &Project) -> PathBuf {
+    project.dir().join(OFFERS_DIR)
+}
+
+/// Keeps `document`, the export of the current offer, and the path of its `pdf`.
+pub fn store(project: &Project, document: &Complete, pdf: &Path) -> Result<PathBuf, Error> {
+    let number = project.offer()
+                        .number()
+                        .ok_or_else(|| format_err!("{} has no offer number", project.short_desc()))?;
+    let stored = Stored {
+        number: &number,
+        appendix: project.offer().appendix().unwrap_or(1),
+        exported: Utc::today().format("%d.%m.%Y").to_string(),
+        pdf: pdf.display().to_string(),
+        document,
+    };
+
+    let backend = project.backend();
+    let dir = offers_dir(project);
+    if !backend.is_dir(&dir) {
+        backend.create_dir(&dir)?;
+    }
+    let path = dir.join(format!("{}.json", number));
+    backend.write(&path, &serde_json::to_string_pretty(&stored)?)?;
+    Ok(path)
+}
+
+/// Every stored version, by appendix.
+pub fn versions(project: &Project) -> Result<Vec<Version>, Error> {
+    let backend = project.backend();
+    let dir = offers_dir(project);
+    if !backend.is_dir(&dir) {
+        return Ok(Vec::new());
+    }
+    let mut versions = Vec::new();
+    for path in backend.list(&dir)? {
+        if path.extension().map_or(false, |ext| ext == "json") {
+            versions.push(Version::from_json(&backend.read(&path)?)?);
+        }
+    }
+    versions.sort_by_key(|version| version.appendix);
+    Ok(versions)
+}
+
+/// The version the client accepted, `None` if there is no answer or no snapshot of it.
+pub fn accepted(project: &Project) -> Result<Option<Version>, Error> {
+    let appendix = match project.offer().accepted_appendix() {
+        Some(appendix) => appendix,
+        None => return Ok(None)
+    };
+    Ok(versions(project)?.into_iter().find(|version| version.appendix == appendix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(lines: &[(&str, f64, &str)], total: &str) -> Version {
+        Version {
+            number: String::from("A20190501-1"),
+            appendix: 1,
+            exported: None,
+            pdf: None,
+            lines: lines.iter()
+                        .map(|&(name, amount, price)| Line { name: name.into(), amount, price: price.into(), cost: String::new() })
+                        .collect(),
+            total: total.into(),
+        }
+    }
+
+    #[test]
+    fn reads_snapshots() {
+        let json = r#"{ "number": "A20190501-2", "appendix": 2, "exported": "01.05.2019", "pdf": "/tmp/offer.pdf",
+                        "document": { "offer": { "net_total": "119,00€" },
+                                      "bills": { "offer": [ { "name": "Kaffee", "amount": 40.0, "price": "2,50€", "cost": "100,00€", "tax": 0.19 } ] } } }"#;
+        let version = Version::from_json(json).unwrap();
+        assert_eq!(version.appendix, 2);
+        assert_eq!(version.pdf.as_ref().map(String::as_str), Some("/tmp/offer.pdf"));
+        assert_eq!(version.lines[0].name, "Kaffee");
+        assert_eq!(version.lines[0].amount, 40.0);
+        assert_eq!(version.total, "119,00€");
+        assert!(Version::from_json("{}").is_err());
+    }
+
+    #[test]
+    fn diffs_versions() {
+        let first = version(&[("Kaffee", 40.0, "2,50€"), ("Tee", 10.0, "2,00€")], "142,80€");
+        let second = version(&[("Kaffee", 50.0, "2,50€"), ("Kuchen", 20.0, "3,00€")], "220,15€");
+        let changes = diff(&first, &second);
+        assert_eq!(changes.len(), 4);
+        assert!(changes.iter().any(|c| match c { Change::Changed { after, .. } => after.amount == 50.0, _ => false }));
+        assert!(changes.contains(&Change::Removed(first.lines[1].clone())));
+        assert!(changes.contains(&Change::Added(second.lines[1].clone())));
+        assert!(diff(&first, &first).is_empty());
+    }
+}
@@ -3,11 +3,12 @@
 
 #![allow(missing_docs)]
 
-use bill::{Currency, BillProduct, Tax};
+use bill::{BillItem, Currency, BillProduct, Tax};
 use failure::Fail;
 
 use crate::util::yaml;
-use crate::util::to_currency;
+use crate::util::decimal::Decimal;
+use super::tax_category::TaxCategory;
 
 
 //#[derive(Debug)] // manually implemented
@@ -15,13 +16,22 @@ use crate::util::to_currency;
 ///
 /// Products are mapped to `Bill`s by `BillItems`,
 /// these are implemented by `bill`.
+/// `bill` only knows `f64` amounts and taxes, so the exact ones as read from the project file
+/// are kept alongside, see [`Product::item()`](#method.item).
 #[derive(Copy,Clone,Debug)]
 #[cfg_attr(feature = "serialization", derive(Serialize))]
 pub struct Product<'a> {
     pub name: &'a str,
     pub unit: Option<&'a str>,
+    /// What `bill` groups by, always the same as `rate`
     pub tax: Tax,
-    pub price: Currency
+    pub price: Currency,
+    /// Exact tax rate
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub rate: Decimal,
+    /// Exact amount of the `BillItem` this product is on
+    #[cfg_attr(feature = "serialization", serde(skip))]
+    pub quantity: Decimal,
 }
 
 #[derive(Fail, Debug)]
@@ -36,6 +46,30 @@ pub enum ProductError {
 
     #[fail(display = "missing amount of {:?}", _0)]
     MissingAmount(String),
+
+    #[fail(display = "{:?} is priced per head, but the number of attendees is missing", _0)]
+    MissingAttendees(String),
+
+    #[fail(display = "{:?} needs either a positive percent or amount", _0)]
+    InvalidAdjustment(String),
+
+    #[fail(display = "discounts push the sum of products taxed with {}% below zero", _0)]
+    NegativeTaxGroup(Decimal),
+
+    #[fail(display = "deposit {} needs either a positive percent or amount", _0)]
+    InvalidDeposit(usize),
+
+    #[fail(display = "there is no deposit {}", _0)]
+    UnknownDeposit(usize),
+
+    #[fail(display = "deposits exceed the invoiced products taxed with {}%", _0)]
+    DepositsExceedInvoice(Decimal),
+
+    #[fail(display = "{:?} has an unknown tax_category", _0)]
+    InvalidTaxCategory(String),
+
+    #[fail(display = "expense {:?} needs an amount", _0)]
+    InvalidExpense(String),
     
     #[fail(display = "too much returned of {:?}", _0)]
     TooMuchReturned(String),
@@ -47,40 +81,58 @@ pub enum ProductError {
 
 impl<'a> Product<'a> {
 
-    fn from_old_format<'y>( name: &'y str, values: &'y yaml::Yaml, local_tax: Option<Tax>) -> Result<Product<'y>, ProductError> {
-        let default_tax = crate::CONFIG.get_f64("defaults/tax").map(Tax::new)
-            .expect("Faulty config: field defaults/tax does not contain a value");
+    /// A product taxed with `rate`, not on any bill yet.
+    pub fn new(name: &'a str, unit: Option<&'a str>, rate: Decimal, price: Currency) -> Self {
+        Product { name, unit, tax: Tax::new(rate.as_f64()), price, rate, quantity: Decimal::default() }
+    }
+
+    /// The same product with another tax rate.
+    pub fn with_rate(self, rate: Decimal) -> Self {
+        Product { tax: Tax::new(rate.as_f64()), rate, ..self }
+    }
+
+    /// Puts `quantity` of this product on a bill.
+    pub fn item(self, quantity: Decimal) -> BillItem<Product<'a>> {
+        BillItem { amount: quantity.as_f64(), product: Product { quantity, ..self } }
+    }
+
+    /// Takes `item` back, for credit notes and deductions.
+    pub fn negated(item: &BillItem<Product<'a>>) -> BillItem<Product<'a>> {
+        item.product.item(-item.product.quantity)
+    }
 
-        let product_tax = yaml::get_f64(values, "tax").map(Tax::new);
-        let tax = product_tax.or(local_tax).unwrap_or(default_tax);
+    fn from_old_format<'y>( name: &'y str, values: &'y yaml::Yaml, local_tax: Option<Decimal>) -> Result<Product<'y>, ProductError> {
+        let product_tax = yaml::get_decimal(values, "tax")
+            .or(category_rate(name, values)?);
+        let rate = product_tax.or(local_tax).unwrap_or_else(default_rate);
 
         let unit = yaml::get_str(values, "unit");
-        let price = yaml::get_f64(values, "price")
-            .map(to_currency)
+        let price = yaml::get_decimal(values, "price")
+            .map(Decimal::to_currency)
             .ok_or_else(||ProductError::InvalidPrice(name.to_string()))?;
 
-        Ok(Product { name, unit, price, tax })
+        Ok(Product::new(name, unit, rate, price))
     }
 
-    fn from_new_format<'y>(desc: &'y yaml::Yaml, values: &'y yaml::Yaml, local_tax: Option<Tax>) -> Result<Product<'y>, ProductError> {
+    fn from_new_format<'y>(desc: &'y yaml::Yaml, values: &'y yaml::Yaml, local_tax: Option<Decimal>) -> Result<Product<'y>, ProductError> {
 
-        let default_tax = crate::CONFIG.get_f64("defaults/tax").map(Tax::new)
-            .expect("Faulty config: field defaults/tax does not contain a value");
+        let name = yaml::get_str(desc, "name").unwrap_or("unnamed");
 
-        let desc_tax = yaml::get_f64(desc, "tax").map(Tax::new);
-        let values_tax = yaml::get_f64(values, "tax").map(Tax::new);
-        let tax = values_tax.or(desc_tax).or(local_tax).unwrap_or(default_tax);
+        let desc_tax = yaml::get_decimal(desc, "tax")
+            .or(category_rate(name, desc)?);
+        let values_tax = yaml::get_decimal(values, "tax")
+            .or(category_rate(name, values)?);
+        let rate = values_tax.or(desc_tax).or(local_tax).unwrap_or_else(default_rate);
 
-        let name = yaml::get_str(desc, "name").unwrap_or("unnamed");
-        let price = yaml::get_f64(desc, "price")
+        let price = yaml::get_decimal(desc, "price")
                 .ok_or_else(||ProductError::InvalidPrice(name.to_string()))
-                .map(to_currency)?;
+                .map(Decimal::to_currency)?;
         let unit = yaml::get_str(desc, "unit");
 
-        Ok(Product { name, unit, price, tax })
+        Ok(Product::new(name, unit, rate, price))
     }
 
-    pub fn from_desc_and_value<'y>(desc: &'y yaml::Yaml, values: &'y yaml::Yaml, local_tax: Option<Tax>) -> Result<Product<'y>, ProductError> {
+    pub fn from_desc_and_value<'y>(desc: &'y yaml::Yaml, values: &'y yaml::Yaml, local_tax: Option<Decimal>) -> Result<Product<'y>, ProductError> {
         match *desc {
             yaml::Yaml::String(ref name) => Self::from_old_format(name, values, local_tax),
             yaml::Yaml::Hash(_) => Self::from_new_format(desc, values, local_tax),
@@ -89,6 +141,21 @@ impl<'a> Product<'a> {
     }
 }
 
+/// `defaults/tax` of the config
+fn default_rate() -> Decimal {
+    crate::CONFIG.get("defaults/tax").and_then(Decimal::from_yaml)
+        .expect("Faulty config: field defaults/tax does not contain a value")
+}
+
+/// The rate of the `tax_category` in `yaml`, if it has one.
+fn category_rate(name: &str, yaml: &yaml::Yaml) -> Result<Option<Decimal>, ProductError> {
+    match TaxCategory::from_yaml(yaml) {
+        Some(Ok(category)) => Ok(Some(category.rate())),
+        Some(Err(_)) => Err(ProductError::InvalidTaxCategory(name.to_owned())),
+        None => Ok(None)
+    }
+}
+
 impl<'a> BillProduct for Product<'a>{
     fn price(&self) -> Currency {self.price}
     fn name(&self) -> String {self.name.to_owned()}
@@ -0,0 +1,618 @@
+//! Configurable reminders about projects that need attention.
+//!
+//! Reminders are described by rules in the `reminders` section of the config.
+//! Every rule whose conditions all hold for a project produces one reminder:
+//!
+//! ```yaml
+//! reminders:
+//!   - name: follow_up
+//!     kind: task            # task (VTODO), warning (terminal) or digest (text only)
+//!     when:
+//!       state: invoiced     # or a list like "invoiced, partially_paid"
+//!       invoice: ">= 14"    # invoice was written 14 or more days ago
+//!     due: { after: invoice, days: 21 }
+//!     priority: 3
+//!     summary: 'Inquire about: "{Name}"!'
+//!     description: "{InvoiceNumber} was invoiced {days_since_invoice} days ago."
+//! ```
+//!
+//! ## Conditions
+//!
+//! `state` holds if the [`State`](../state/enum.State.html) of the project is one of the listed states.
+//!
+//! The dates `event`, `offer`, `invoice`, `payed` and `wages` can be
+//! `set`, `missing` or compared by the days that passed since them,
+//! e.g. `14`, `"> 7"`, `"<= 0"` or `"-3..0"` (both ends included).
+//! Dates in the future count negative, so `event: "-3..0"` means "within the next three days".
+//!
+//! Every [`ComputedField`](../enum.ComputedField.html) can be `set`, `empty` or compared to a string,
+//! `canceled` can be `true` or `false`.
+//! Canceled projects are skipped unless a rule asks for them, by `canceled` or by `state: canceled`.
+//!
+//! ## Messages
+//!
+//! `summary` and `description` may contain placeholders:
+//! any computed field like `{Name}` or `{Employees}`, `{email}` of the client,
+//! and for every date `{invoice}`, `{days_since_invoice}`, `{weeks_since_invoice}` and `{days_until_invoice}`.
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::prelude::*;
+use chrono::Duration;
+use failure::{bail, format_err, Error};
+use icalendar::*;
+use yaml_rust::Yaml;
+
+use crate::util::{ical, yaml};
+
+use super::{ComputedField, Project};
+use super::spec::*;
+use super::state::State;
+
+/// Dates of a project that rules can refer to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Anchor {
+    Event,
+    Offer,
+    Invoice,
+    Payed,
+    Wages,
+}
+
+const ANCHORS: [Anchor; 5] = [Anchor::Event, Anchor::Offer, Anchor::Invoice, Anchor::Payed, Anchor::Wages];
+
+impl Anchor {
+    fn name(self) -> &'static str {
+        match self {
+            Anchor::Event => "event",
+            Anchor::Offer => "offer",
+            Anchor::Invoice => "invoice",
+            Anchor::Payed => "payed",
+            Anchor::Wages => "wages",
+        }
+    }
+
+    fn date(self, project: &Project) -> Option<Date<Utc>> {
+        match self {
+            Anchor::Event => project.event_date(),
+            Anchor::Offer => project.offer().date(),
+            Anchor::Invoice => project.invoice().date(),
+            Anchor::Payed => project.payed_date(),
+            Anchor::Wages => project.hours().wages_date(),
+        }
+    }
+}
+
+impl FromStr for Anchor {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        ANCHORS.iter()
+               .cloned()
+               .find(|a| a.name() == s)
+               .ok_or_else(|| format_err!("unknown date {:?}", s))
+    }
+}
+
+/// Range of days that passed since a date.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Days {
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+}
+
+impl Days {
+    fn contains(self, days: i64) -> bool {
+        self.min.map_or(true, |min| days >= min) && self.max.map_or(true, |max| days <= max)
+    }
+}
+
+impl FromStr for Days {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let s = s.trim();
+        let number = |n: &str| n.trim().parse::<i64>().map_err(|_| format_err!("invalid number of days {:?}", s));
+
+        let days = if let Some(pos) = s.find("..") {
+            Days { min: Some(number(&s[..pos])?), max: Some(number(&s[pos + 2..])?) }
+        } else if s.starts_with(">=") {
+            Days { min: Some(number(&s[2..])?), max: None }
+        } else if s.starts_with("<=") {
+            Days { min: None, max: Some(number(&s[2..])?) }
+        } else if s.starts_with('>') {
+            Days { min: Some(number(&s[1..])? + 1), max: None }
+        } else if s.starts_with('<') {
+            Days { min: None, max: Some(number(&s[1..])? - 1) }
+        } else {
+            let n = number(s.trim_start_matches("=="))?;
+            Days { min: Some(n), max: Some(n) }
+        };
+        Ok(days)
+    }
+}
+
+/// What a date has to look like.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DateCondition {
+    Set,
+    Missing,
+    DaysSince(Days),
+}
+
+/// What a computed field has to look like.
+#[derive(Debug, Clone, PartialEq)]
+pub enum FieldCondition {
+    Set,
+    Empty,
+    Equals(String),
+}
+
+/// One condition of a rule.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Date(Anchor, DateCondition),
+    Field(String, FieldCondition),
+    Canceled(bool),
+    State(Vec<State>),
+}
+
+impl Condition {
+    fn from_yaml(key: &str, value: &Yaml) -> Result<Self, Error> {
+        let value = match *value {
+            Yaml::String(ref s) => s.trim().to_owned(),
+            Yaml::Integer(i) => i.to_string(),
+            Yaml::Boolean(b) => b.to_string(),
+            _ => bail!("condition {:?} has an invalid value", key),
+        };
+
+        if key == "canceled" {
+            return match value.as_str() {
+                "true" => Ok(Condition::Canceled(true)),
+                "false" => Ok(Condition::Canceled(false)),
+                _ => bail!("canceled must be true or false"),
+            };
+        }
+
+        if key == "state" {
+            let states = value.split(',')
+                              .map(str::parse::<State>)
+                              .collect::<Result<Vec<_>, _>>()?;
+            return Ok(Condition::State(states));
+        }
+
+        if let Ok(anchor) = key.parse::<Anchor>() {
+            let condition = match value.as_str() {
+                "set" => DateCondition::Set,
+                "missing" => DateCondition::Missing,
+                days => DateCondition::DaysSince(days.parse::<Days>()?),
+            };
+            return Ok(Condition::Date(anchor, condition));
+        }
+
+        if let ComputedField::Invalid = ComputedField::from(key) {
+            bail!("unknown field {:?}", key);
+        }
+        let condition = match value.as_str() {
+            "set" => FieldCondition::Set,
+            "empty" => FieldCondition::Empty,
+            _ => FieldCondition::Equals(value),
+        };
+        Ok(Condition::Field(key.to_owned(), condition))
+    }
+
+    fn holds(&self, project: &Project, state: State, today: Date<Utc>) -> bool {
+        match *self {
+            Condition::Date(anchor, condition) => {
+                match (anchor.date(project), condition) {
+                    (Some(_), DateCondition::Set) | (None, DateCondition::Missing) => true,
+                    (Some(date), DateCondition::DaysSince(days)) => days.contains(days_since(date, today)),
+                    _ => false,
+                }
+            }
+            Condition::Field(ref field, ref condition) => {
+                let value = ComputedField::from(field.as_str())
+                    .get(project)
+                    .filter(|v| !v.trim().is_empty());
+                match (value, condition) {
+                    (Some(_), FieldCondition::Set) | (None, FieldCondition::Empty) => true,
+                    (Some(value), FieldCondition::Equals(expected)) => value == *expected,
+                    _ => false,
+                }
+            }
+            Condition::Canceled(canceled) => project.canceled() == canceled,
+            Condition::State(ref states) => states.contains(&state),
+        }
+    }
+
+    fn asks_for_canceled(&self) -> bool {
+        match *self {
+            Condition::Canceled(_) => true,
+            Condition::State(ref states) => states.contains(&State::Canceled),
+            _ => false,
+        }
+    }
+}
+
+fn days_since(date: Date<Utc>, today: Date<Utc>) -> i64 {
+    today.signed_duration_since(date).num_days()
+}
+
+/// How a reminder is delivered.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    /// Entry in the task calendar
+    Task,
+    /// Printed on the terminal
+    Warning,
+    /// Only part of the digest
+    Digest,
+}
+
+impl FromStr for Kind {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "task" => Ok(Kind::Task),
+            "warning" => Ok(Kind::Warning),
+            "digest" => Ok(Kind::Digest),
+            _ => bail!("unknown reminder kind {:?}", s),
+        }
+    }
+}
+
+/// Produces a reminder for every project that matches all its conditions.
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub kind: Kind,
+    pub conditions: Vec<Condition>,
+    /// Due a number of days after one of the dates
+    pub due: Option<(Anchor, i64)>,
+    pub priority: Option<u32>,
+    pub summary: String,
+    pub description: Option<String>,
+}
+
+impl Rule {
+    fn from_yaml(yaml: &Yaml) -> Result<Self, Error> {
+        let name = yaml::get_string(yaml, "name").ok_or_else(|| format_err!("reminder without a name"))?;
+        let context = |e: Error| format_err!("reminder {:?}: {}", name, e);
+
+        let kind = yaml::get_str(yaml, "kind").unwrap_or("task").parse::<Kind>().map_err(context)?;
+
+        let mut conditions = Vec::new();
+        if let Some(when) = yaml::get_hash(yaml, "when") {
+            for (key, value) in when {
+                let key = key.as_str().ok_or_else(|| context(format_err!("condition without a name")))?;
+                conditions.push(Condition::from_yaml(key, value).map_err(context)?);
+            }
+        }
+
+        let due = match yaml::get(yaml, "due") {
+            Some(due) => {
+                let anchor = yaml::get_str(due, "after")
+                    .ok_or_else(|| context(format_err!("due needs a date to come after")))?
+                    .parse::<Anchor>()
+                    .map_err(context)?;
+                Some((anchor, yaml::get_int(due, "days").unwrap_or(0)))
+            }
+            None => None,
+        };
+
+        Ok(Rule {
+            kind,
+            conditions,
+            due,
+            priority: yaml::get_int(yaml, "priority").map(|p| p as u32),
+            summary: yaml::get_string(yaml, "summary").ok_or_else(|| context(format_err!("summary is missing")))?,
+            description: yaml::get_string(yaml, "description"),
+            name,
+        })
+    }
+
+    /// Whether the rule applies to `project` on `today`.
+    pub fn matches(&self, project: &Project, today: Date<Utc>) -> bool {
+        let state = State::of(project, today);
+        let wants_canceled = self.conditions.iter().any(Condition::asks_for_canceled);
+        (wants_canceled || state != State::Canceled)
+            && self.conditions.iter().all(|c| c.holds(project, state, today))
+    }
+
+    fn remind(&self, project: &Project, today: Date<Utc>) -> Reminder {
+        Reminder {
+            rule: self.name.clone(),
+            kind: self.kind,
+            summary: fill_in(&self.summary, project, today),
+            description: self.description.as_ref().map(|d| fill_in(d, project, today)),
+            due: self.due.and_then(|(anchor, days)| anchor.date(project).map(|date| date + Duration::days(days))),
+            priority: self.priority,
+        }
+    }
+}
+
+/// Replaces every known `{placeholder}` in `template`.
+fn fill_in(template: &str, project: &Project, today: Date<Utc>) -> String {
+    let lookup = |key: &str| -> Option<String> {
+        if key == "email" {
+            return project.client().email().map(ToOwned::to_owned);
+        }
+        for anchor in &ANCHORS {
+            let name = anchor.name();
+            let date = || anchor.date(project);
+            if key == name {
+                return date().map(|d| d.format("%d.%m.%Y").to_string());
+            } else if key.starts_with("days_since_") && &key[11..] == name {
+                return date().map(|d| days_since(d, today).to_string());
+            } else if key.starts_with("weeks_since_") && &key[12..] == name {
+                return date().map(|d| (days_since(d, today) / 7).to_string());
+            } else if key.starts_with("days_until_") && &key[11..] == name {
+                return date().map(|d| (-days_since(d, today)).to_string());
+            }
+        }
+        match ComputedField::from(key) {
+            ComputedField::Invalid => None,
+            field => Some(field.get(project).unwrap_or_default()),
+        }
+    };
+
+    let mut filled = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        filled.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        match after.find('}').and_then(|end| lookup(&after[..end]).map(|value| (end, value))) {
+            Some((end, value)) => {
+                filled.push_str(&value);
+                rest = &after[end + 1..];
+            }
+            None => {
+                filled.push('{');
+                rest = after;
+            }
+        }
+    }
+    filled.push_str(rest);
+    filled
+}
+
+/// Something that needs to be done about a project.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reminder {
+    /// Name of the rule that produced this reminder
+    pub rule: String,
+    pub kind: Kind,
+    pub summary: String,
+    pub description: Option<String>,
+    pub due: Option<Date<Utc>>,
+    pub priority: Option<u32>,
+}
+
+impl Reminder {
+    /// Task for the calendar, `project_uid` is the [`calendar_uid()`](../struct.Project.html#method.calendar_uid) of the project.
+    ///
+    /// Tasks with a due date come with an alarm.
+    pub fn to_todo(&self, project_uid: &str) -> Todo {
+        let mut todo = Todo::new();
+        todo.add_property("UID", &format!("{}-{}@asciii", project_uid, self.rule));
+        todo.summary(&self.summary);
+        if let Some(ref description) = self.description {
+            todo.description(description);
+        }
+        if let Some(due) = self.due {
+            let due = due.and_hms(11, 10, 0);
+            todo.due(&due);
+            ical::add_alarm(&mut todo, &due);
+        }
+        if let Some(priority) = self.priority {
+            todo.priority(priority);
+        }
+        todo.done()
+    }
+}
+
+impl fmt::Display for Reminder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.summary)?;
+        if let Some(due) = self.due {
+            write!(f, " ({})", lformat!("due {}", due.format("%d.%m.%Y")))?;
+        }
+        Ok(())
+    }
+}
+
+/// All configured reminder rules.
+#[derive(Debug, Clone, Default)]
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+impl Rules {
+    /// Reads a list of rules.
+    pub fn from_yaml(yaml: &Yaml) -> Result<Self, Error> {
+        let rules = match *yaml {
+            Yaml::Array(ref rules) => rules.iter().map(Rule::from_yaml).collect::<Result<Vec<_>, _>>()?,
+            Yaml::Null | Yaml::BadValue => Vec::new(),
+            _ => bail!("reminders must be a list of rules"),
+        };
+        Ok(Rules { rules })
+    }
+
+    /// Parses a list of rules, an empty document has none.
+    pub fn from_yaml_str(content: &str) -> Result<Self, Error> {
+        match yaml::parse(content)? {
+            // empty document, or only comments
+            Yaml::String(ref s) if s == "[]" => Ok(Self::default()),
+            rules => Self::from_yaml(&rules)
+        }
+    }
+
+    /// Rules from the `reminders` section of the config.
+    pub fn from_config() -> Result<Self, Error> {
+        crate::CONFIG.get("reminders")
+            .map(Self::from_yaml)
+            .unwrap_or_else(|| Ok(Self::default()))
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    /// Every reminder `project` gives rise to on `today`.
+    pub fn check(&self, project: &Project, today: Date<Utc>) -> Vec<Reminder> {
+        self.rules.iter()
+            .filter(|rule| rule.matches(project, today))
+            .map(|rule| rule.remind(project, today))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static RULES: &str = r#"
+    - name: issue_invoice
+      when: { event: ">= 0", invoice: missing }
+      due: { after: event, days: 14 }
+      priority: 6
+      summary: "Create an Invoice for {Name}"
+    - name: follow_up
+      when: { invoice: 14, payed: missing }
+      priority: 3
+      summary: 'Inquire about: "{Name}"!'
+      description: "invoiced on {invoice} ({days_since_invoice} days ago), ask {email}"
+    - name: payment_late
+      when: { invoice: "> 14", payed: missing }
+      priority: 10
+      summary: "payment is {weeks_since_invoice} weeks late"
+    - name: no_caterers
+      kind: warning
+      when: { event: "-3..0", Employees: empty }
+      summary: "{Name} takes place in {days_until_event} days and has no caterers"
+    "#;
+
+    fn today() -> Date<Utc> {
+        Utc.ymd(2019, 6, 15)
+    }
+
+    fn project(event: &str, invoice: &str, caterers: &str, canceled: bool) -> Project {
+        let doc = format!(r#"
+        client:
+          email: client@example.com
+        event:
+          name: Party
+          dates:
+            - begin: {}
+        canceled: {}
+        invoice:
+          date: {}
+        hours:
+          salary: 8.0
+          caterers: {}
+        "#, event, canceled, invoice, caterers);
+        Project::from_file_content(&doc).unwrap()
+    }
+
+    fn reminded(project: &Project) -> Vec<String> {
+        Rules::from_yaml_str(RULES).unwrap()
+            .check(project, today())
+            .into_iter()
+            .map(|r| r.rule)
+            .collect()
+    }
+
+    #[test]
+    fn parses_days() {
+        assert_eq!("14".parse::<Days>().unwrap(), Days { min: Some(14), max: Some(14) });
+        assert_eq!("> 7".parse::<Days>().unwrap(), Days { min: Some(8), max: None });
+        assert_eq!("<= 0".parse::<Days>().unwrap(), Days { min: None, max: Some(0) });
+        assert_eq!("-3..0".parse::<Days>().unwrap(), Days { min: Some(-3), max: Some(0) });
+        assert!("soon".parse::<Days>().is_err());
+    }
+
+    #[test]
+    fn rejects_broken_rules() {
+        assert!(Rules::from_yaml_str("- name: x\n  summary: y\n  when: { nonsense: set }").is_err());
+        assert!(Rules::from_yaml_str("- name: x\n  summary: y\n  kind: email").is_err());
+        assert!(Rules::from_yaml_str("- name: x").is_err());
+        assert!(Rules::from_yaml_str("").unwrap().rules().is_empty());
+        assert!(Rules::from_yaml_str("# nothing yet\n").unwrap().rules().is_empty());
+    }
+
+    #[test]
+    fn issue_invoice_after_event() {
+        let done = project("01.06.2019", "", "{ Alice: 3 }", false);
+        let rules = Rules::from_yaml_str(RULES).unwrap();
+        let reminders = rules.check(&done, today());
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].summary, "Create an Invoice for Party");
+        assert_eq!(reminders[0].due, Some(Utc.ymd(2019, 6, 15)));
+        assert_eq!(reminders[0].priority, Some(6));
+
+        assert!(reminded(&project("20.06.2019", "", "{ Alice: 3 }", false)).is_empty());
+        assert!(reminded(&project("01.06.2019", "", "{ Alice: 3 }", true)).is_empty());
+    }
+
+    #[test]
+    fn follow_up_and_escalate() {
+        let invoiced = project("01.05.2019", "01.06.2019", "{ Alice: 3 }", false);
+        let reminders = Rules::from_yaml_str(RULES).unwrap().check(&invoiced, today());
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].rule, "follow_up");
+        assert_eq!(reminders[0].description.as_ref().unwrap(),
+                   "invoiced on 01.06.2019 (14 days ago), ask client@example.com");
+
+        let late = project("01.05.2019", "20.05.2019", "{ Alice: 3 }", false);
+        let reminders = Rules::from_yaml_str(RULES).unwrap().check(&late, today());
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].summary, "payment is 3 weeks late");
+        assert_eq!(reminders[0].priority, Some(10));
+    }
+
+    #[test]
+    fn warns_about_missing_caterers() {
+        let soon = project("17.06.2019", "", "{ }", false);
+        let reminders = Rules::from_yaml_str(RULES).unwrap().check(&soon, today());
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].kind, Kind::Warning);
+        assert_eq!(reminders[0].summary, "Party takes place in 2 days and has no caterers");
+
+        assert!(reminded(&project("17.06.2019", "", "{ Alice: 3 }", false)).is_empty());
+        assert!(reminded(&project("30.06.2019", "", "{ }", false)).is_empty());
+    }
+
+    #[test]
+    fn matches_states() {
+        let rules = Rules::from_yaml_str(r#"
+        - name: pay_employees
+          when: { state: "paid, wages_paid" }
+          summary: "Pay {Employees}"
+        - name: canceled
+          when: { state: canceled }
+          summary: "{Name} was canceled"
+        "#).unwrap();
+        let paid = Project::from_file_content(r#"
+        event:
+          name: Party
+          dates:
+            - begin: 01.06.2019
+        invoice: { number: 1, date: 05.06.2019, payed_date: 10.06.2019 }
+        hours:
+          salary: 8.0
+          caterers: { Alice: 3 }
+        "#).unwrap();
+        let reminders = rules.check(&paid, today());
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].rule, "pay_employees");
+
+        let canceled = project("01.06.2019", "", "{ Alice: 3 }", true);
+        assert_eq!(rules.check(&canceled, today())[0].rule, "canceled");
+        assert!(Rules::from_yaml_str("- name: x\n  summary: y\n  when: { state: finished }").is_err());
+    }
+
+    #[test]
+    fn leaves_unknown_placeholders() {
+        let p = project("01.06.2019", "", "{ Alice: 3 }", false);
+        assert_eq!(fill_in("{Name} {nope} {", &p, today()), "Party {nope} {");
+    }
+}
@@ -7,16 +7,21 @@
 
 use std::fmt;
 
-use bill::{Bill, Currency, Tax};
-use chrono::{Date, Utc, NaiveTime};
-use failure::Error;
+use bill::{Bill, Currency};
+use chrono::{Date, Datelike, Timelike, Utc, NaiveDateTime, NaiveTime};
+use failure::{format_err, Error};
 use icalendar::Calendar;
 use semver::Version;
 use yaml_rust::Yaml;
 
 use crate::storage::Storable;
+use crate::util::decimal::Decimal;
 use super::error::{SpecResult, ErrorList};
 use super::product::Product;
+use super::totals::Totals;
+use super::exchange::{self, ExchangeRate};
+use super::tax_category::TaxCategory;
+use super::BillType;
 
 
 pub fn print_spec_result(label: &str, result: &SpecResult) {
@@ -93,6 +98,18 @@ pub trait Offerable {
 
     /// ID of an the offer
     fn number(&self) -> Option<String>;
+
+    /// When the client accepted the offer
+    fn accepted(&self) -> Option<Date<Utc>>;
+
+    /// Which appendix of the offer was accepted
+    fn accepted_appendix(&self) -> Option<i64>;
+
+    /// When the client turned the offer down
+    fn rejected(&self) -> Option<Date<Utc>>;
+
+    /// Why the offer was accepted or rejected
+    fn note(&self) -> Option<&str>;
 }
 
 /// Everything about the client
@@ -122,6 +139,9 @@ pub trait IsClient {
 
     /// Produces a standard salutation field.
     fn addressing(&self) -> Option<String>;
+
+    ///Returns the content of `/client/vat_id`
+    fn vat_id(&self) -> Option<&str>;
 }
 
 /// Stage 2: requirements for an invoice
@@ -142,12 +162,40 @@ pub trait Invoicable {
     fn official(&self) -> Option<String>;
 }
 
+/// Stage 4: an issued invoice is canceled by a credit note
+pub trait Cancelable {
+    /// plain access to `storno/number`, taken from the invoice sequence
+    fn number(&self) -> Option<i64>;
+
+    /// When was the credit note issued
+    fn date(&self) -> Option<Date<Utc>>;
+
+    /// Why the invoice was canceled
+    fn reason(&self) -> Option<String>;
+
+    /// credit note number as a string
+    fn number_str(&self) -> Option<String>;
+
+    /// credit note number as a long string
+    fn number_long_str(&self) -> Option<String>;
+
+    /// If a credit note was issued
+    fn is_issued(&self) -> bool {
+        self.number().is_some()
+    }
+}
+
 /// Represents an Employee
+///
+/// Caterers are either listed as plain `name: hours`, or in detail as
+/// `name: { hours: 4, rate: 12.0, role: lead, start: "18:00", end: "22:00" }`.
+/// `hours` may be left out if `start` and `end` are given.
 pub struct Employee {
     /// Name of the Employee
     pub name: String,
 
-    /// Amount of Currency the employees receives per hour
+    /// Amount of Currency the employees receives per hour,
+    /// either their own `rate` or `hours.salary`
     pub salary: Currency,
 
     /// Number of hours the employee worked on this project
@@ -155,6 +203,40 @@ pub struct Employee {
 
     /// Salary times hours
     pub wage: Currency,
+
+    /// What the employee did, e.g. "lead" or "helper"
+    pub role: Option<String>,
+
+    /// Begin of the shift
+    pub start: Option<NaiveTime>,
+
+    /// End of the shift, may be after midnight
+    pub end: Option<NaiveTime>,
+}
+
+impl Employee {
+    /// Whether the shift overlaps with `time`, `true` if there is no shift given
+    ///
+    /// Both the shift and the event time may end after midnight.
+    pub fn works_during(&self, time: &EventTime) -> bool {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => {
+                let (start, end) = minutes_of_day(start, end);
+                let (begin, finish) = minutes_of_day(time.start, time.end);
+                [-DAY, 0, DAY].iter().any(|day| start <= finish + day && end >= begin + day)
+            }
+            _ => true
+        }
+    }
+}
+
+const DAY: i64 = 24 * 60;
+
+/// Minutes since midnight, an `end` before `start` is on the next day
+fn minutes_of_day(start: NaiveTime, end: NaiveTime) -> (i64, i64) {
+    let minutes = |time: NaiveTime| i64::from(time.num_seconds_from_midnight() / 60);
+    let (start, end) = (minutes(start), minutes(end));
+    if end < start { (start, end + DAY) } else { (start, end) }
 }
 
 /// Something that has employees
@@ -166,29 +248,22 @@ pub trait HasEmployees {
     fn salary(&self) -> Option<Currency>;
 
     /// Tax
-    fn tax(&self) -> Option<Tax>;
+    fn tax(&self) -> Option<Decimal>;
 
-    /// Sum of wages after tax
+    /// Sum of wages after tax, computed from each employee's own rate
     fn net_wages(&self) -> Option<Currency> ;
 
-    /// Sum of wages before tax
+    /// Sum of wages before tax, computed from each employee's own rate
     fn gross_wages(&self) -> Option<Currency> ;
 
 
     /// Full number of service hours
-    ///
-    /// `TODO` test this against old format
     fn total_time(&self) -> Option<f64>;
 
     /// Returns a product from Service
     fn to_product(&self) -> Option<Product<'_>> {
         if let Some(salary) = self.salary() {
-            Some(Product {
-                name: "Service",
-                unit: Some("h"),
-                tax: self.tax().unwrap_or_else(|| Tax::new(0.0)),
-                price: salary
-            })
+            Some(Product::new("Service", Some("h"), self.tax().unwrap_or_default(), salary))
         } else {
             None
         }
@@ -220,15 +295,190 @@ pub trait Redeemable: IsProject {
     /// Returns a bill for the offer and one for the invoice.
     fn bills(&self) -> Result<(Bill<Product<'_>>, Bill<Product<'_>>), Error>;
 
+    /// Returns the invoice with negative amounts, as it is printed on a credit note.
+    fn credit_note(&self) -> Result<Bill<Product<'_>>, Error>;
+
     /// When what is the MWsT of the project.
-    fn tax(&self) -> Option<Tax>;
+    fn tax(&self) -> Option<Decimal>;
+
+    /// Offered and sold amounts of every product.
+    fn product_amounts(&self) -> Result<Vec<ProductAmounts>, Error>;
+
+    /// Discounts and surcharges on the bills.
+    fn adjustments(&self) -> Result<Vec<Adjustment<'_>>, Error>;
+
+    /// Deposit invoices, in the order they are listed.
+    fn deposits(&self) -> Result<Vec<Deposit<'_>>, Error>;
+
+    /// Returns the bill of one deposit, split among the tax groups of the offer.
+    fn deposit_bill(&self, index: usize) -> Result<Bill<Product<'_>>, Error>;
+
+    /// Returns the invoice minus every deposit that has a number.
+    ///
+    /// For canceled projects the cancellation fee takes the place of the invoice.
+    fn final_invoice(&self) -> Result<Bill<Product<'_>>, Error>;
+
+    /// When and on what terms the client canceled, `None` unless the project is canceled.
+    fn cancellation(&self) -> Option<Cancellation>;
+
+    /// Percentage of the offer the client has to pay for canceling, `None` if it is free.
+    fn cancellation_percent(&self) -> Option<Decimal>;
+
+    /// Returns the bill of the cancellation fee, split among the tax groups of the offer.
+    fn cancellation_fee(&self) -> Result<Option<Bill<Product<'_>>>, Error>;
+
+    /// Whether the client has to pay anything, canceled projects only if a fee is due
+    fn is_billable(&self) -> bool {
+        !self.canceled() || self.cancellation_percent().is_some()
+    }
+
+    /// What the client pays before tax, only the cancellation fee if the project was canceled
+    fn revenue(&self) -> Result<Currency, Error> {
+        if self.canceled() {
+            return Ok(self.cancellation_fee()?.map(|fee| Totals::of(&fee).before_tax()).unwrap_or_default());
+        }
+        let (_, invoice) = self.bills()?;
+        Ok(Totals::of(&invoice).before_tax())
+    }
 
     /// Sum of sold products
     fn sum_sold(&self) -> Result<Currency, Error> {
         let (_,invoice) = self.bills()?;
-        Ok(invoice.net_total())
+        Ok(Totals::of(&invoice).after_tax())
+    }
+
+    /// Sum of the credit note, negative
+    fn sum_credited(&self) -> Result<Currency, Error> {
+        Ok(Totals::of(&self.credit_note()?).after_tax())
     }
 
+    /// Sum of the final invoice, what is left after the deposits
+    fn sum_final(&self) -> Result<Currency, Error> {
+        Ok(Totals::of(&self.final_invoice()?).after_tax())
+    }
+
+    /// Sum of one deposit invoice
+    fn sum_deposit(&self, index: usize) -> Result<Currency, Error> {
+        Ok(Totals::of(&self.deposit_bill(index)?).after_tax())
+    }
+
+    /// What the client still has to pay: the final invoice and all deposits that are not payed yet
+    fn sum_open(&self) -> Result<Currency, Error> {
+        let mut open = self.sum_final()?;
+        for (index, deposit) in self.deposits()?.iter().enumerate() {
+            if deposit.is_issued() && !deposit.is_payed() {
+                open = open + self.sum_deposit(index)?;
+            }
+        }
+        Ok(open)
+    }
+
+}
+
+/// Size of a discount or surcharge
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AdjustmentValue {
+    /// Percentage of the products, e.g. `10` for 10%
+    Percent(Decimal),
+
+    /// Fixed amount
+    Amount(Currency),
+}
+
+/// A discount or surcharge, listed under `discounts` or `surcharges`
+#[derive(Debug, Clone, Copy)]
+pub struct Adjustment<'a> {
+    /// Shows up like a product name on the bill
+    pub name: &'a str,
+
+    /// Reduces the bill, a surcharge otherwise
+    pub discount: bool,
+
+    /// Percentage or amount, always positive
+    pub value: AdjustmentValue,
+
+    /// Only applies to the products with this tax rate, otherwise to the whole bill
+    pub tax: Option<Decimal>,
+
+    /// Only applies to either offer or invoice, otherwise to both
+    pub bill: Option<BillType>,
+}
+
+/// A deposit invoice, listed under `deposits`
+#[derive(Debug, Clone, Copy)]
+pub struct Deposit<'a> {
+    /// Shows up like a product name on the deposit and the final invoice
+    pub name: &'a str,
+
+    /// Taken from the invoice sequence, deposits are only deducted once they have one
+    pub number: Option<i64>,
+
+    /// When was the deposit invoice issued
+    pub date: Option<Date<Utc>>,
+
+    /// Percentage of the offer or amount before tax
+    pub value: AdjustmentValue,
+
+    /// When was the deposit payed
+    pub payed_date: Option<Date<Utc>>,
+}
+
+impl<'a> Deposit<'a> {
+    /// deposit number as a string, same sequence as invoices
+    pub fn number_str(&self) -> Option<String> {
+        self.number.map(|n| format!("R{:03}", n))
+    }
+
+    /// deposit number as a long string
+    pub fn number_long_str(&self) -> Option<String> {
+        let year = self.date?.year();
+        self.number.map(|n| format!("R{}-{:03}", year, n))
+    }
+
+    /// If the deposit invoice was issued
+    pub fn is_issued(&self) -> bool {
+        self.number.is_some()
+    }
+
+    /// If the deposit was payed
+    pub fn is_payed(&self) -> bool {
+        self.payed_date.is_some()
+    }
+}
+
+/// The `cancellation` of a canceled project, see [`cancellation`](../cancellation/index.html)
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Cancellation {
+    /// When the client canceled
+    pub date: Option<Date<Utc>>,
+
+    /// Fee as a percentage of the offer, overrides the schedule of the config
+    pub percent: Option<Decimal>,
+
+    /// The cancellation is free no matter when it happened
+    pub waived: bool,
+}
+
+/// How much of one product was brought to an event and how much of it was used
+#[derive(Debug, Clone)]
+pub struct ProductAmounts {
+    /// Name of the product
+    pub name: String,
+
+    /// Unit of the product, e.g. "1l"
+    pub unit: Option<String>,
+
+    /// Amount in the offer
+    pub offered: f64,
+
+    /// Amount that was sold, same as `offered` unless `sold` or `returned` are given
+    pub sold: f64,
+
+    /// Amount per attendee, if the product is priced per head
+    pub per_head: Option<f64>,
+
+    /// What one unit costs us, `purchase_price` in the catalogue
+    pub purchase_price: Option<Currency>,
 }
 
 /// Holds the time of the beginning and end of an event
@@ -267,6 +517,35 @@ impl fmt::Display for Event {
     }
 }
 
+/// How many people come to an event
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Attendees {
+    /// Head count the offer is based on
+    pub expected: Option<u32>,
+
+    /// Head count that actually came
+    pub actual: Option<u32>,
+}
+
+impl Attendees {
+    /// Head count for the offer
+    pub fn for_offer(&self) -> Option<u32> {
+        self.expected.or(self.actual)
+    }
+
+    /// Head count for the invoice
+    pub fn for_invoice(&self) -> Option<u32> {
+        self.actual.or(self.expected)
+    }
+
+    /// Splits `total` among `heads` people.
+    pub fn per_head(total: Currency, heads: Option<u32>) -> Option<Currency> {
+        heads.filter(|&heads| heads > 0)
+             .and_then(|heads| Decimal::from(total).divide(Decimal::from(i64::from(heads)), 2))
+             .map(Decimal::to_currency)
+    }
+}
+
 /// Something that has events
 pub trait HasEvents {
     /// Produces an iCal calendar from this project.
@@ -281,4 +560,239 @@ pub trait HasEvents {
     /// Returns the location of the event
     fn location(&self) -> Option<&str>;
 
+    /// Kind of event, e.g. "wedding" or "conference"
+    fn event_type(&self) -> Option<&str>;
+
+    /// Expected and actual number of attendees
+    fn attendees(&self) -> Attendees;
+
+}
+
+/// A document that is kept together with a project, e.g. a receipt or a signed contract
+#[derive(Debug, Clone)]
+pub struct Attachment {
+    /// File name inside the attachments folder
+    pub file: String,
+
+    /// What kind of document this is, e.g. "receipt" or "contract"
+    pub kind: Option<String>,
+
+    /// Date of the document
+    pub date: Option<Date<Utc>>,
+
+    /// Free form note
+    pub note: Option<String>,
+}
+
+/// A purchase, rental, trip or anything else that was payed for a project
+#[derive(Debug, Clone)]
+pub struct Expense {
+    /// What was payed for
+    pub name: String,
+
+    /// e.g. "purchase", "rental" or "travel"
+    pub kind: Option<String>,
+
+    /// When it was payed
+    pub date: Option<Date<Utc>>,
+
+    /// What was payed, including tax
+    pub amount: Currency,
+
+    /// Tax rate included in `amount`
+    pub tax: Decimal,
+
+    /// Receipt in the attachments folder
+    pub receipt: Option<String>,
+}
+
+impl Expense {
+    /// `amount` without the tax
+    pub fn before_tax(&self) -> Currency {
+        let net = Decimal::from(self.amount).divide(Decimal::from(1) + self.tax, 2).unwrap_or_default();
+        Currency { symbol: self.amount.symbol, value: net.to_cents() }
+    }
+}
+
+/// Something that costs money
+pub trait HasExpenses {
+    /// Everything listed under `expenses`
+    fn expenses(&self) -> Result<Vec<Expense>, Error>;
+
+    /// Expenses whose receipt is not in the attachments folder, none without a project file
+    fn missing_receipts(&self) -> Result<Vec<Expense>, Error>;
+
+    /// Purchase price of all sold products that have one
+    fn cost_of_goods(&self) -> Result<Currency, Error>;
+
+    /// Expenses before tax and cost of goods
+    fn cost(&self) -> Result<Currency, Error>;
+
+    /// Revenue before tax minus cost and wages
+    fn margin(&self) -> Result<Currency, Error>;
+}
+
+/// Something that can be billed in a foreign currency, see [`exchange`](../exchange/index.html)
+pub trait HasCurrency {
+    /// Code of the currency the project is billed in, `None` for the home currency
+    fn currency(&self) -> Option<&str>;
+
+    /// Snapshot of the exchange rate into the home currency
+    fn exchange_rate(&self) -> Option<ExchangeRate>;
+
+    /// Products that are priced in another currency than the project
+    fn mixed_currencies(&self) -> Vec<String>;
+
+    /// Converts an amount of the project's currency, fails for a foreign currency without exchange rate
+    fn to_home_currency(&self, amount: Currency) -> Result<Currency, Error> {
+        match self.currency() {
+            None => Ok(amount),
+            Some(code) => self.exchange_rate()
+                              .map(|rate| rate.convert(amount))
+                              .ok_or_else(|| format_err!("no exchange rate for {}", code))
+        }
+    }
+
+    /// Formats an amount in the project's currency
+    fn format_amount(&self, amount: &Currency) -> String {
+        match self.currency() {
+            None => crate::util::currency_to_string(amount),
+            Some(code) => exchange::format(amount, code)
+        }
+    }
+}
+
+/// A product that names its own `tax_category`
+#[derive(Debug, Clone, PartialEq)]
+pub struct CategorizedProduct<'a> {
+    pub name: &'a str,
+    pub category: TaxCategory,
+    /// `tax_exemption` of the product
+    pub exemption: Option<&'a str>,
+}
+
+/// Something that is taxed by category, see [`tax_category`](../tax_category/index.html)
+pub trait HasTaxCategory {
+    /// `tax_category` of the whole project, `None` if there is none or it is unknown
+    fn tax_category(&self) -> Option<TaxCategory>;
+
+    /// `tax_exemption`, why the project or its exempt products are not taxed
+    fn tax_exemption(&self) -> Option<&str>;
+
+    /// Products that name a known `tax_category` of their own
+    fn categorized_products(&self) -> Vec<CategorizedProduct<'_>>;
+
+    /// Whether the project is not taxed at all, whatever rates the products have
+    fn is_zero_rated(&self) -> bool {
+        self.tax_category().map(TaxCategory::is_zero_rated).unwrap_or(false)
+    }
+
+    /// Whether the client owes the tax
+    fn is_reverse_charge(&self) -> bool {
+        self.tax_category() == Some(TaxCategory::ReverseCharge)
+    }
+
+    /// Reasons for not taxing, each to be stated once on the invoice
+    fn tax_exemptions(&self) -> Vec<&str> {
+        let mut reasons = Vec::new();
+        match self.tax_category() {
+            Some(TaxCategory::Exempt) => reasons.extend(self.tax_exemption()),
+            Some(TaxCategory::ReverseCharge) => {},
+            _ => {
+                let exempt = self.categorized_products()
+                                 .into_iter()
+                                 .filter(|product| product.category == TaxCategory::Exempt);
+                for product in exempt {
+                    if let Some(reason) = product.exemption.or_else(|| self.tax_exemption()) {
+                        if !reasons.contains(&reason) {
+                            reasons.push(reason);
+                        }
+                    }
+                }
+            }
+        }
+        reasons
+    }
+
+    /// Whether categories contradict each other or lack their reason, for `validate()`
+    fn inconsistent_tax_categories(&self) -> bool {
+        let products = self.categorized_products();
+        match self.tax_category() {
+            Some(project) if project.is_zero_rated() => products.iter().any(|product| product.category != project),
+            _ => products.iter().any(|product| product.category == TaxCategory::ReverseCharge)
+        }
+    }
+
+    /// Whether an exempt project or product does not say why
+    fn missing_tax_exemption(&self) -> bool {
+        match self.tax_category() {
+            Some(TaxCategory::Exempt) => self.tax_exemption().is_none(),
+            Some(TaxCategory::ReverseCharge) => false,
+            _ => self.tax_exemption().is_none()
+                 && self.categorized_products()
+                        .iter()
+                        .any(|product| product.category == TaxCategory::Exempt && product.exemption.is_none())
+        }
+    }
+}
+
+/// Something that has attached documents
+pub trait HasAttachments {
+    /// Documents listed in the project file
+    fn attachments(&self) -> Vec<Attachment>;
+
+    /// Listed documents that are not actually in the attachments folder, none without a project file
+    fn missing_attachments(&self) -> Vec<Attachment>;
+}
+
+/// Somebody who is planned to work at the event, before `hours.caterers` is filled in.
+///
+/// Listed in the project file under `staffing`:
+///
+/// ```yaml
+/// staffing:
+///   - { name: Alice, begin: "17:30", end: "23:30", role: bar }
+///   - { name: Bob, date: 02.05.2019 } # times of the event on that date
+/// ```
+///
+/// `date` defaults to the first date of the event, `begin` and `end` to the first time of the event on that date.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub name: String,
+    pub role: Option<String>,
+    pub date: Date<Utc>,
+    pub start: NaiveTime,
+    /// Before `start` if the shift ends after midnight
+    pub end: NaiveTime,
+}
+
+impl Assignment {
+    /// When the shift starts and ends
+    pub fn interval(&self) -> (NaiveDateTime, NaiveDateTime) {
+        let start = self.date.naive_utc().and_time(self.start);
+        let end_date = if self.end <= self.start { self.date.naive_utc().succ() } else { self.date.naive_utc() };
+        (start, end_date.and_time(self.end))
+    }
+
+    /// Length of the shift in hours
+    pub fn hours(&self) -> f64 {
+        let (start, end) = self.interval();
+        end.signed_duration_since(start).num_minutes() as f64 / 60f64
+    }
+
+    /// Whether both shifts are at least partly at the same time
+    pub fn overlaps(&self, other: &Assignment) -> bool {
+        let (start, end) = self.interval();
+        let (other_start, other_end) = other.interval();
+        start < other_end && other_start < end
+    }
+}
+
+/// Something that has planned staff
+pub trait HasStaffing {
+    /// Planned shifts, entries without a date or time to fall back on are left out
+    fn staffing(&self) -> Vec<Assignment>;
+
+    /// Planned hours per person
+    fn planned_hours(&self) -> Vec<(String, f64)>;
 }
@@ -1,6 +1,6 @@
 use std::str::FromStr;
 
-use bill::{Bill, Currency, Tax};
+use bill::{Bill, Currency};
 use icalendar::Event as CalEvent;
 use icalendar::{Component, Calendar};
 use failure::bail;
@@ -8,8 +8,15 @@ use yaml_rust::Yaml;
 
 use super::*;
 use super::spec::*;
+// the export has structs of the same names
+use super::spec::{Cancellation, Deposit};
 use super::product::ProductError;
-use crate::util::{self, yaml, to_currency};
+use super::cancellation::FeeSchedule;
+use super::totals::Totals;
+use super::exchange::{self, ExchangeRate};
+use super::tax_category::TaxCategory;
+use crate::util::{self, yaml};
+use crate::util::decimal::Decimal;
 use crate::util::yaml::parse_dmy_date;
 
 impl YamlProvider for Project {
@@ -71,7 +78,21 @@ impl IsProject for Project {
 impl HasEvents for Project {
     fn to_ical(&self) -> Calendar {
         let mut calendar = Calendar::new();
+        let uid = self.calendar_uid();
+        let modified = self.last_modified();
+
+        // same uid for the same event, changes are told apart by `SEQUENCE`
+        let identify = |cal_event: &mut CalEvent, index: usize| {
+            cal_event.add_property("UID", &format!("{}-{}@asciii", uid, index));
+            cal_event.add_property("STATUS", if self.canceled() { "CANCELLED" } else { "CONFIRMED" });
+            if let Some(modified) = modified {
+                cal_event.add_property("LAST-MODIFIED", &util::ical::format_time(&modified));
+                cal_event.add_property("SEQUENCE", &(modified.timestamp() / 60).to_string());
+            }
+        };
+
         if let Some(events) = self.events() {
+            let mut index = 0;
             for event in events {
                 if event.times.is_empty() {
 
@@ -90,6 +111,8 @@ impl HasEvents for Project {
                     }
 
                     cal_event.summary(&self.name().unwrap_or("unnamed"));
+                    identify(&mut cal_event, index);
+                    index += 1;
                     calendar.push(cal_event);
 
                 } else {
@@ -112,6 +135,8 @@ impl HasEvents for Project {
                         //cal_event.start_date(event.begin);
 
                         cal_event.summary(&self.name().unwrap_or("unnamed"));
+                        identify(&mut cal_event, index);
+                        index += 1;
                         calendar.push(cal_event);
                     }
                 }
@@ -175,17 +200,27 @@ impl HasEvents for Project {
     fn location(&self) -> Option<&str> {
         self.get_str("event.location")
     }
+
+    fn event_type(&self) -> Option<&str> {
+        self.get_str("event.type")
+    }
+
+    fn attendees(&self) -> Attendees {
+        let count = |path: &str| self.get_int(path).filter(|&n| n >= 0).map(|n| n as u32);
+        if let Some(expected) = count("event.attendees") {
+            return Attendees { expected: Some(expected), actual: None };
+        }
+        Attendees {
+            expected: count("event.attendees.expected"),
+            actual: count("event.attendees.actual"),
+        }
+    }
 }
 
 /// Returns a product from Service
 fn service_to_product<'a, T: HasEmployees>(s: &T) -> Result<Product<'a>, Error> {
     if let Some(salary) = s.salary() {
-        Ok(Product {
-                 name: "Service",
-                 unit: Some("h"),
-                 tax: s.tax().unwrap_or_else(|| Tax::new(0.0)),
-                 price: salary,
-             })
+        Ok(Product::new("Service", Some("h"), s.tax().unwrap_or_default(), salary))
     } else {
         bail!(ProductError::InvalidServerSection)
     }
@@ -202,21 +237,26 @@ impl Redeemable for Project {
         self.payed_date().is_some()
     }
 
-    fn tax(&self) -> Option<Tax> {
-        self.get_f64("tax").map(Tax::new)
+    fn tax(&self) -> Option<Decimal> {
+        self.tax_category().map(TaxCategory::rate)
+            .or_else(|| self.get_decimal("tax"))
     }
 
     fn bills(&self) -> Result<(Bill<Product<'_>>, Bill<Product<'_>>), Error> {
         let mut offer: Bill<Product<'_>> = Bill::new();
         let mut invoice: Bill<Product<'_>> = Bill::new();
 
-        let service = service_to_product(&self.hours())?;
+        let mut service = service_to_product(&self.hours())?;
        //  .("cannot create product from employees, salary or tax missing");
+        if self.is_zero_rated() {
+            service = service.with_rate(Decimal::default());
+        }
 
         if let Some(total) = self.hours().total_time() {
-            if total.is_normal() {
-                offer.add_item(total, service);
-                invoice.add_item(total, service);
+            // hours are summed up from shifts, some of them only given by `start` and `end`
+            if let Some(total) = Decimal::from_f64(total).filter(|total| !total.is_zero()) {
+                offer.add(service.item(total));
+                invoice.add(service.item(total));
             }
         }
 
@@ -228,16 +268,460 @@ impl Redeemable for Project {
 
         for (desc, values) in raw_products {
             let (offer_item, invoice_item) = self.item_from_desc_and_value(desc, values)?;
-            if offer_item.amount.is_normal() {
+            if !offer_item.product.quantity.is_zero() {
                 offer.add(offer_item);
             }
-            if invoice_item.amount.is_normal() {
+            if !invoice_item.product.quantity.is_zero() {
                 invoice.add(invoice_item);
             }
         }
 
+        let adjustments = self.adjustments()?;
+        apply_adjustments(&mut offer, &adjustments, BillType::Offer)?;
+        apply_adjustments(&mut invoice, &adjustments, BillType::Invoice)?;
+
         Ok((offer, invoice))
     }
+
+    fn credit_note(&self) -> Result<Bill<Product<'_>>, Error> {
+        let invoice = self.final_invoice()?;
+        let mut credit_note = Bill::new();
+        for (_, item) in invoice.as_items_with_tax() {
+            credit_note.add(Product::negated(item));
+        }
+        Ok(credit_note)
+    }
+
+    fn product_amounts(&self) -> Result<Vec<ProductAmounts>, Error> {
+        let raw_products =
+            self.get_hash("products")
+                .ok_or_else(|| ProductError::UnknownFormat)?;
+
+        let mut amounts = Vec::new();
+        for (desc, values) in raw_products {
+            let (offer_item, invoice_item) = self.item_from_desc_and_value(desc, values)?;
+            amounts.push(ProductAmounts {
+                name: offer_item.product.name.to_owned(),
+                unit: offer_item.product.unit.map(ToOwned::to_owned),
+                offered: offer_item.amount,
+                sold: invoice_item.amount,
+                per_head: self.get_direct(values, "per_head").and_then(|y| y.as_f64().or_else(|| y.as_i64().map(|i| i as f64))),
+                purchase_price: yaml::get_decimal(desc, "purchase_price")
+                                    .or_else(|| yaml::get_decimal(values, "purchase_price"))
+                                    .map(Decimal::to_currency),
+            });
+        }
+        Ok(amounts)
+    }
+
+    fn adjustments(&self) -> Result<Vec<Adjustment<'_>>, Error> {
+        let mut adjustments = Vec::new();
+        for &(key, discount) in &[("discounts", true), ("surcharges", false)] {
+            let entries = match self.get(key).and_then(Yaml::as_vec) {
+                Some(entries) => entries,
+                None => continue
+            };
+            for entry in entries {
+                let name = self.get_direct(entry, "name")
+                               .and_then(Yaml::as_str)
+                               .unwrap_or(if discount { "Discount" } else { "Surcharge" });
+                let number = |key: &str| self.get_direct(entry, key).and_then(Decimal::from_yaml);
+
+                let value = match (number("percent"), number("amount")) {
+                    (Some(percent), None) if percent > Decimal::default() => AdjustmentValue::Percent(percent),
+                    (None, Some(amount)) if amount > Decimal::default() => AdjustmentValue::Amount(amount.to_currency()),
+                    // `bail!` would turn this into a plain message, `validate()` has to downcast it
+                    _ => return Err(ProductError::InvalidAdjustment(name.to_owned()).into())
+                };
+                let bill = match self.get_direct(entry, "bill").and_then(Yaml::as_str) {
+                    Some("offer") => Some(BillType::Offer),
+                    Some("invoice") => Some(BillType::Invoice),
+                    Some(_) => return Err(ProductError::InvalidAdjustment(name.to_owned()).into()),
+                    None => None
+                };
+
+                let tax = number("tax").map(|tax| if self.is_zero_rated() { Decimal::default() } else { tax });
+                adjustments.push(Adjustment { name, discount, value, tax, bill });
+            }
+        }
+        Ok(adjustments)
+    }
+
+    fn deposits(&self) -> Result<Vec<Deposit<'_>>, Error> {
+        let entries = match self.get("deposits").and_then(Yaml::as_vec) {
+            Some(entries) => entries,
+            None => return Ok(Vec::new())
+        };
+        let mut deposits = Vec::new();
+        for (index, entry) in entries.iter().enumerate() {
+            let number = |key: &str| self.get_direct(entry, key).and_then(Decimal::from_yaml);
+            let date = |key: &str| self.get_direct(entry, key).and_then(Yaml::as_str).and_then(parse_dmy_date);
+
+            let value = match (number("percent"), number("amount")) {
+                (Some(percent), None) if percent > Decimal::default() => AdjustmentValue::Percent(percent),
+                (None, Some(amount)) if amount > Decimal::default() => AdjustmentValue::Amount(amount.to_currency()),
+                _ => return Err(ProductError::InvalidDeposit(index + 1).into())
+            };
+
+            deposits.push(Deposit {
+                name: self.get_direct(entry, "name").and_then(Yaml::as_str).unwrap_or("Deposit"),
+                number: self.get_direct(entry, "number").and_then(Yaml::as_i64),
+                date: date("date"),
+                value,
+                payed_date: date("payed_date"),
+            });
+        }
+        Ok(deposits)
+    }
+
+    fn deposit_bill(&self, index: usize) -> Result<Bill<Product<'_>>, Error> {
+        let deposits = self.deposits()?;
+        let deposit = deposits.get(index).ok_or_else(|| ProductError::UnknownDeposit(index + 1))?;
+        let (offer, _) = self.bills()?;
+        Ok(share_of_bill(&offer, deposit.name, deposit.value))
+    }
+
+    fn final_invoice(&self) -> Result<Bill<Product<'_>>, Error> {
+        let (offer, invoice) = self.bills()?;
+        let mut invoice = match self.cancellation_fee()? {
+            Some(fee) => fee,
+            None => invoice
+        };
+        for deposit in self.deposits()?.iter().filter(|d| d.is_issued()) {
+            for (_, item) in share_of_bill(&offer, deposit.name, deposit.value).as_items_with_tax() {
+                invoice.add(Product::negated(item));
+            }
+        }
+
+        if let Some(group) = Totals::of(&invoice).groups.iter().find(|group| group.before_tax.value < 0) {
+            return Err(ProductError::DepositsExceedInvoice(group.percent()).into());
+        }
+        Ok(invoice)
+    }
+
+    fn cancellation(&self) -> Option<Cancellation> {
+        if !self.canceled() {
+            return None;
+        }
+        Some(Cancellation {
+            date: self.get_dmy("cancellation.date"),
+            percent: self.get_decimal("cancellation.percent"),
+            waived: self.get_bool("cancellation.waived").unwrap_or(false),
+        })
+    }
+
+    fn cancellation_percent(&self) -> Option<Decimal> {
+        let cancellation = self.cancellation()?;
+        if cancellation.waived {
+            return None;
+        }
+        let percent = match cancellation.percent {
+            Some(percent) => percent,
+            None => {
+                let days = (self.event_date()? - cancellation.date?).num_days();
+                FeeSchedule::from_config().ok()?.percent(days)?
+            }
+        };
+        Some(percent).filter(|&percent| percent > Decimal::default())
+    }
+
+    fn cancellation_fee(&self) -> Result<Option<Bill<Product<'_>>>, Error> {
+        let percent = match self.cancellation_percent() {
+            Some(percent) => percent,
+            None => return Ok(None)
+        };
+        let (offer, _) = self.bills()?;
+        Ok(Some(share_of_bill(&offer, "Cancellation fee", AdjustmentValue::Percent(percent))))
+    }
+}
+
+/// Splits `value` among the tax groups with the given pre-tax `sums`.
+///
+/// Percentages are taken of each group, amounts are split by the share of each group,
+/// the last group gets what is left after rounding.
+fn split_by_tax(sums: &[(Decimal, Currency)], value: AdjustmentValue) -> Vec<(Decimal, Currency)> {
+    let total = sums.iter().map(|&(_, sum)| sum.value).sum::<i64>();
+    let mut values = Vec::new();
+    match value {
+        AdjustmentValue::Percent(percent) => {
+            for &(tax, sum) in sums {
+                let value = (Decimal::from(sum) * percent.percent()).to_cents();
+                values.push((tax, Currency { symbol: sum.symbol, value }));
+            }
+        }
+        AdjustmentValue::Amount(amount) => {
+            let mut remaining = amount.value;
+            for (index, &(tax, sum)) in sums.iter().enumerate() {
+                let share = if index + 1 == sums.len() || total == 0 {
+                    remaining
+                } else {
+                    (Decimal::from(amount) * Decimal::from(sum.value))
+                        .divide(Decimal::from(total), 2)
+                        .map(Decimal::to_cents)
+                        .unwrap_or(0)
+                };
+                remaining -= share;
+                values.push((tax, Currency { symbol: amount.symbol, value: share }));
+            }
+        }
+    }
+    values
+}
+
+/// Part of `bill` as a bill of its own, with an item called `name` for each tax group,
+/// used for deposits and cancellation fees.
+fn share_of_bill<'a>(bill: &Bill<Product<'a>>, name: &'a str, value: AdjustmentValue) -> Bill<Product<'a>> {
+    let sums = Totals::of(bill).groups
+                               .iter()
+                               .map(|group| (group.rate, group.before_tax))
+                               .collect::<Vec<(Decimal, Currency)>>();
+    let mut share = Bill::new();
+    for (rate, price) in split_by_tax(&sums, value).into_iter().filter(|(_, price)| price.value != 0) {
+        share.add(Product::new(name, None, rate, price).item(Decimal::from(1)));
+    }
+    share
+}
+
+/// Adds discounts and surcharges to `bill` as items of their own, so they are taxed like the products they apply to.
+///
+/// Percentages always refer to the products of a tax group, so the order of entries does not matter.
+/// Amounts on the whole bill are split among the tax groups by their share.
+fn apply_adjustments<'a>(bill: &mut Bill<Product<'a>>, adjustments: &[Adjustment<'a>], bill_type: BillType) -> Result<(), Error> {
+    let sums = Totals::of(bill).groups
+                               .iter()
+                               .map(|group| (group.rate, group.before_tax))
+                               .collect::<Vec<(Decimal, Currency)>>();
+    let mut items = Vec::new();
+
+    for adjustment in adjustments.iter().filter(|a| a.bill.map(|b| b == bill_type).unwrap_or(true)) {
+        let groups = sums.iter()
+                         .filter(|&&(rate, _)| adjustment.tax.map(|t| t == rate).unwrap_or(true))
+                         .cloned()
+                         .collect::<Vec<_>>();
+
+        let values = match adjustment.value {
+            // a fee on its own, e.g. delivery
+            AdjustmentValue::Amount(amount) if groups.is_empty() => vec![(adjustment.tax.unwrap_or_default(), amount)],
+            value => split_by_tax(&groups, value)
+        };
+
+        for (rate, value) in values.into_iter().filter(|(_, value)| value.value != 0) {
+            let price = if adjustment.discount { Currency { symbol: value.symbol, value: -value.value } } else { value };
+            items.push(Product::new(adjustment.name, None, rate, price).item(Decimal::from(1)));
+        }
+    }
+
+    for item in items {
+        bill.add(item);
+    }
+
+    if let Some(group) = Totals::of(bill).groups.iter().find(|group| group.before_tax.value < 0) {
+        // `bail!` would turn this into a plain message, `validate()` has to downcast it
+        return Err(ProductError::NegativeTaxGroup(group.percent()).into());
+    }
+    Ok(())
+}
+
+impl HasAttachments for Project {
+    fn attachments(&self) -> Vec<Attachment> {
+        self.get("attachments")
+            .and_then(Yaml::as_vec)
+            .map(|entries| entries.iter()
+                 .filter_map(|entry| Some(Attachment {
+                     file: self.get_direct(entry, "file").and_then(Yaml::as_str)?.to_owned(),
+                     kind: self.get_direct(entry, "kind").and_then(Yaml::as_str).map(ToOwned::to_owned),
+                     date: self.get_direct(entry, "date").and_then(Yaml::as_str).and_then(parse_dmy_date),
+                     note: self.get_direct(entry, "note").and_then(Yaml::as_str).map(ToOwned::to_owned),
+                 }))
+                 .collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    fn missing_attachments(&self) -> Vec<Attachment> {
+        let dir = match self.attachments_dir() {
+            Some(dir) => dir,
+            None => return Vec::new()
+        };
+        let backend = self.backend();
+        self.attachments()
+            .into_iter()
+            .filter(|attachment| {
+                let file = dir.join(&attachment.file);
+                !backend.exists(&file) || backend.is_dir(&file)
+            })
+            .collect()
+    }
+}
+
+impl HasExpenses for Project {
+    fn expenses(&self) -> Result<Vec<Expense>, Error> {
+        let entries = match self.get("expenses").and_then(Yaml::as_vec) {
+            Some(entries) => entries,
+            None => return Ok(Vec::new())
+        };
+        let default_tax = crate::CONFIG.get("defaults/tax").and_then(Decimal::from_yaml).unwrap_or_default();
+
+        let mut expenses = Vec::new();
+        for entry in entries {
+            let text = |key: &str| self.get_direct(entry, key).and_then(Yaml::as_str).map(ToOwned::to_owned);
+            let name = text("name").unwrap_or_else(|| lformat!("expense"));
+            let amount = match self.get_direct(entry, "amount").and_then(Decimal::from_yaml) {
+                Some(amount) => amount.to_currency(),
+                // `bail!` would turn this into a plain message, `validate()` has to downcast it
+                None => return Err(ProductError::InvalidExpense(name).into())
+            };
+
+            expenses.push(Expense {
+                kind: text("kind"),
+                date: text("date").as_ref().and_then(|d| parse_dmy_date(d)),
+                amount,
+                tax: self.get_direct(entry, "tax").and_then(Decimal::from_yaml).unwrap_or(default_tax),
+                receipt: text("receipt"),
+                name,
+            });
+        }
+        Ok(expenses)
+    }
+
+    fn missing_receipts(&self) -> Result<Vec<Expense>, Error> {
+        let dir = match self.attachments_dir() {
+            Some(dir) => dir,
+            None => return Ok(Vec::new())
+        };
+        let backend = self.backend();
+        let is_missing = |file: &String| {
+            let file = dir.join(file);
+            !backend.exists(&file) || backend.is_dir(&file)
+        };
+        Ok(self.expenses()?
+               .into_iter()
+               .filter(|expense| expense.receipt.as_ref().map(is_missing).unwrap_or(false))
+               .collect())
+    }
+
+    fn cost_of_goods(&self) -> Result<Currency, Error> {
+        if self.canceled() {
+            // nothing was bought for the event
+            return Ok(Currency::default());
+        }
+        Ok(self.product_amounts()?
+               .iter()
+               .filter_map(|product| product.purchase_price.map(|price| multiply(price, product.sold)))
+               .fold(Currency::default(), |acc, x| acc + x))
+    }
+
+    fn cost(&self) -> Result<Currency, Error> {
+        Ok(self.expenses()?
+               .iter()
+               .map(Expense::before_tax)
+               .fold(self.cost_of_goods()?, |acc, x| acc + x))
+    }
+
+    fn margin(&self) -> Result<Currency, Error> {
+        let revenue = self.revenue()?;
+        let cost = self.cost()?;
+        let wages = self.hours().gross_wages().unwrap_or_default();
+        Ok(Currency { symbol: revenue.symbol, value: revenue.value - cost.value - wages.value })
+    }
+}
+
+impl HasCurrency for Project {
+    fn currency(&self) -> Option<&str> {
+        self.get_str("currency").filter(|code| !exchange::is_home(code))
+    }
+
+    fn exchange_rate(&self) -> Option<ExchangeRate> {
+        self.get("exchange_rate").and_then(ExchangeRate::from_yaml)
+    }
+
+    fn mixed_currencies(&self) -> Vec<String> {
+        let project_currency = self.get_str("currency").unwrap_or_else(|| crate::CONFIG.get_str("currency"));
+        self.get_hash("products")
+            .map(|products| products.iter()
+                 .filter(|(desc, values)| {
+                     self.get_direct(values, "currency")
+                         .or_else(|| self.get_direct(desc, "currency"))
+                         .and_then(Yaml::as_str)
+                         .map(|code| !exchange::same_currency(code, project_currency))
+                         .unwrap_or(false)
+                 })
+                 .map(|(desc, _)| desc.as_str()
+                                      .or_else(|| self.get_direct(desc, "name").and_then(Yaml::as_str))
+                                      .unwrap_or("unnamed")
+                                      .to_owned())
+                 .collect())
+            .unwrap_or_else(Vec::new)
+    }
+}
+
+impl HasTaxCategory for Project {
+    fn tax_category(&self) -> Option<TaxCategory> {
+        TaxCategory::from_yaml(self.data()).and_then(Result::ok)
+    }
+
+    fn tax_exemption(&self) -> Option<&str> {
+        self.get_str("tax_exemption")
+    }
+
+    fn categorized_products(&self) -> Vec<CategorizedProduct<'_>> {
+        self.get_hash("products")
+            .map(|products| products.iter()
+                 .filter_map(|(desc, values)| {
+                     let category = TaxCategory::from_yaml(values)
+                         .or_else(|| TaxCategory::from_yaml(desc))
+                         .and_then(Result::ok)?;
+                     Some(CategorizedProduct {
+                         name: desc.as_str()
+                                   .or_else(|| self.get_direct(desc, "name").and_then(Yaml::as_str))
+                                   .unwrap_or("unnamed"),
+                         category,
+                         exemption: self.get_direct(values, "tax_exemption")
+                                        .or_else(|| self.get_direct(desc, "tax_exemption"))
+                                        .and_then(Yaml::as_str),
+                     })
+                 })
+                 .collect())
+            .unwrap_or_else(Vec::new)
+    }
+}
+
+impl HasStaffing for Project {
+    fn staffing(&self) -> Vec<Assignment> {
+        let events = self.events().unwrap_or_else(Vec::new);
+        self.get("staffing")
+            .and_then(Yaml::as_vec)
+            .map(|entries| entries.iter()
+                 .filter_map(|entry| {
+                     let date = self.get_direct(entry, "date")
+                                    .and_then(Yaml::as_str)
+                                    .and_then(parse_dmy_date)
+                                    .or_else(|| events.first().map(|e| e.begin))?;
+                     let event_time = events.iter()
+                                            .find(|e| e.begin <= date && date <= e.end.unwrap_or(e.begin))
+                                            .and_then(|e| e.times.first());
+                     let time = |key: &str| self.get_direct(entry, key).and_then(Yaml::as_str).and_then(util::naive_time_from_str);
+                     Some(Assignment {
+                         name: self.get_direct(entry, "name").and_then(Yaml::as_str)?.trim().to_owned(),
+                         role: self.get_direct(entry, "role").and_then(Yaml::as_str).map(ToOwned::to_owned),
+                         date,
+                         start: time("begin").or_else(|| event_time.map(|t| t.start))?,
+                         end: time("end").or_else(|| event_time.map(|t| t.end))?,
+                     })
+                 })
+                 .collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    fn planned_hours(&self) -> Vec<(String, f64)> {
+        let mut hours: Vec<(String, f64)> = Vec::new();
+        for assignment in self.staffing() {
+            match hours.iter_mut().find(|(name, _)| *name == assignment.name) {
+                Some((_, sum)) => *sum += assignment.hours(),
+                None => hours.push((assignment.name.clone(), assignment.hours())),
+            }
+        }
+        hours
+    }
 }
 
 impl Validatable for Project {
@@ -255,6 +739,45 @@ impl Validatable for Project {
         if self.format().is_none() {
             errors.push("format")
         }
+        if self.attendees().for_offer().is_none() && self.has_per_head_products() {
+            errors.push("attendees")
+        }
+        let mut unknown_category = TaxCategory::from_yaml(self.data()).map(|c| c.is_err()).unwrap_or(false);
+        if let Err(error) = self.bills() {
+            match error.downcast_ref::<ProductError>() {
+                Some(ProductError::NegativeTaxGroup(_)) |
+                Some(ProductError::InvalidAdjustment(_)) => errors.push("discounts"),
+                Some(ProductError::InvalidTaxCategory(_)) => unknown_category = true,
+                _ => {}
+            }
+        }
+        if let Err(error) = self.final_invoice() {
+            match error.downcast_ref::<ProductError>() {
+                Some(ProductError::InvalidDeposit(_)) |
+                Some(ProductError::DepositsExceedInvoice(_)) => errors.push("deposits"),
+                _ => {}
+            }
+        }
+        if let Err(error) = self.expenses() {
+            if let Some(ProductError::InvalidExpense(_)) = error.downcast_ref::<ProductError>() {
+                errors.push("expenses")
+            }
+        }
+        if !self.mixed_currencies().is_empty() {
+            errors.push("currency")
+        }
+        if unknown_category || self.inconsistent_tax_categories() {
+            errors.push("tax_category")
+        }
+        if self.missing_tax_exemption() {
+            errors.push("tax_exemption")
+        }
+        if self.is_reverse_charge() && self.client().vat_id().is_none() {
+            errors.push("client/vat_id")
+        }
+        if !self.missing_attachments().is_empty() {
+            errors.push("attachments")
+        }
         //if hours::salary().is_none(){errors.push("salary")}
 
         if !errors.is_empty() {
@@ -326,6 +849,10 @@ impl<'a> IsClient for Client<'a> {
         .or_else(|| self.get_str("client").and_then(|c|c.lines().nth(1)))
     }
 
+    fn vat_id(&self) -> Option<&str> {
+        self.get_str("client/vat_id")
+    }
+
     fn full_name(&self) -> Option<String> {
         let first = self.first_name();
         let last = self.last_name();
@@ -388,6 +915,22 @@ impl<'a> Offerable for Offer<'a> {
         self.get_dmy("offer.date")
     }
 
+    fn accepted(&self) -> Option<Date<Utc>> {
+        self.get_dmy("offer.accepted")
+    }
+
+    fn accepted_appendix(&self) -> Option<i64> {
+        self.get_int("offer.accepted_appendix")
+    }
+
+    fn rejected(&self) -> Option<Date<Utc>> {
+        self.get_dmy("offer.rejected")
+    }
+
+    fn note(&self) -> Option<&str> {
+        self.get_str("offer.note")
+    }
+
     fn number(&self) -> Option<String> {
         let num = self.appendix().unwrap_or(1);
         Offerable::date(self)
@@ -456,8 +999,52 @@ impl<'a> Invoicable for Invoice<'a> {
 
 impl<'a> Validatable for Invoice<'a> {
     fn validate(&self) -> SpecResult {
-        search_errors(self, &["invoice.number"], field_is_integer)
+        let mut errors = search_errors(self, &["invoice.number"], field_is_integer)
             .chain(search_errors(self, &["invoice.date|invoice_date"], field_is_dmy))
+            .collect::<ErrorList>();
+
+        // the tax has to be stated in the home currency too
+        if self.inner.currency().is_some() && self.inner.exchange_rate().is_none() {
+            errors.push("exchange_rate");
+        }
+        errors.into()
+    }
+}
+
+impl<'a> YamlProvider for Storno<'a> {
+    fn data(&self) -> &Yaml {
+        self.inner.data()
+    }
+}
+
+impl<'a> Cancelable for Storno<'a> {
+    fn number(&self) -> Option<i64> {
+        self.get_int("storno.number")
+    }
+
+    fn date(&self) -> Option<Date<Utc>> {
+        self.get_dmy("storno.date")
+    }
+
+    fn reason(&self) -> Option<String> {
+        self.get_str("storno.reason").map(ToOwned::to_owned)
+    }
+
+    // same sequence as invoices
+    fn number_str(&self) -> Option<String> {
+        self.number().map(|n| format!("R{:03}", n))
+    }
+
+    fn number_long_str(&self) -> Option<String> {
+        let year = self.date()?.year();
+        self.number().map(|n| format!("R{}-{:03}", year, n))
+    }
+}
+
+impl<'a> Validatable for Storno<'a> {
+    fn validate(&self) -> SpecResult {
+        search_errors(self, &["storno.number"], field_is_integer)
+            .chain(search_errors(self, &["storno.date"], field_is_dmy))
             .collect::<ErrorList>()
             .into()
     }
@@ -477,30 +1064,27 @@ impl<'a> HasEmployees for Hours<'a> {
     }
 
     fn salary(&self) -> Option<Currency> {
-        self.get_f64("hours.salary").map(to_currency)
+        self.get_decimal("hours.salary").map(Decimal::to_currency)
     }
 
-    fn tax(&self) -> Option<Tax> {
-        self.get_f64("hours.tax").map(Tax::new)
+    fn tax(&self) -> Option<Decimal> {
+        self.get_decimal("hours.tax")
     }
 
     fn net_wages(&self) -> Option<Currency> {
-        let triple = (self.total_time(), self.salary(), self.tax());
-        match triple {
-            (Some(total_time), Some(salary), Some(tax)) => Some(total_time * salary * (tax.value() + 1f64)),
+        let gross_wages = self.gross_wages()?;
+        match self.tax() {
+            Some(tax) => {
+                let rate = Decimal::from(1) + tax;
+                Some((Decimal::from(gross_wages) * rate).to_currency())
+            }
             // covering the legacy case where Services always had Tax=0%
-            (Some(total_time), Some(salary), None) => Some(total_time * salary),
-            _ => None,
+            None => Some(gross_wages),
         }
     }
 
     fn gross_wages(&self) -> Option<Currency> {
-        let tuple = (self.total_time(), self.salary());
-        if let (Some(total_time), Some(salary)) = tuple {
-            Some(total_time * salary)
-        } else {
-            None
-        }
+        self.wages()
     }
 
     fn total_time(&self) -> Option<f64> {
@@ -517,10 +1101,11 @@ impl<'a> HasEmployees for Hours<'a> {
             e.iter()
              .filter(|e| e.time as u32 > 0)
              .map(|e| {
-                      format!("{}: ({}h {})",
+                      format!("{}{}: ({}h {})",
                               e.name,
+                              e.role.as_ref().map(|r| format!(" [{}]", r)).unwrap_or_default(),
                               e.time,
-                              (e.salary * e.time).postfix())
+                              self.inner.format_amount(&e.wage))
                   })
              .collect::<Vec<String>>()
              .join(", ")
@@ -529,27 +1114,13 @@ impl<'a> HasEmployees for Hours<'a> {
 
     fn employees(&self) -> Option<Vec<Employee>> {
         let employees = self.get_hash("hours.caterers")
-                            .or_else(|| self.get_hash("hours.employees"));
+                            .or_else(|| self.get_hash("hours.employees"))?;
 
-        if let Some(employees) = employees {
-            employees.iter()
-                     .map(|(c, h)| {(c.as_str().unwrap_or("").into(), make_float(h))
-                     })
-                     .filter(|&(_, h)| h > 0f64)
-                     .map(|(name, time)| {
-                let wage = self.salary()? * time;
-                let salary = self.salary()?;
-                Some(Employee {
-                         name,
-                         time,
-                         wage,
-                         salary,
-                     })
-            })
-                     .collect::<Option<Vec<Employee>>>()
-        } else {
-            None
-        }
+        employees.iter()
+                 .map(|(c, h)| (c.as_str().unwrap_or("").into(), h))
+                 .filter(|&(_, h)| self.shift_time(h) > 0f64)
+                 .map(|(name, h)| self.make_employee(name, h))
+                 .collect::<Option<Vec<Employee>>>()
     }
 
     fn employees_payed(&self) -> bool {
@@ -557,12 +1128,108 @@ impl<'a> HasEmployees for Hours<'a> {
     }
 
     fn wages(&self) -> Option<Currency> {
-        if let (Some(total), Some(salary)) = (self.total_time(), self.salary()) {
-            Some(total * salary)
+        let employees = self.employees()?;
+        let salary = employees.first().map(|e| e.salary).or_else(|| self.salary())?;
+        Some(employees.iter()
+                      .fold(Currency { symbol: salary.symbol, value: 0 }, |acc, e| acc + e.wage))
+    }
+}
+
+impl<'a> Hours<'a> {
+    /// Hours of a caterer entry, either given directly or from `start` and `end`
+    fn shift_time(&self, entry: &Yaml) -> f64 {
+        if let Yaml::Hash(_) = entry {
+            self.get_direct(entry, "hours")
+                .map(make_float)
+                .or_else(|| {
+                    let start = self.get_direct(entry, "start").and_then(make_time)?;
+                    let end = self.get_direct(entry, "end").and_then(make_time)?;
+                    Some(shift_length(start, end))
+                })
+                .unwrap_or(0f64)
         } else {
-            None
+            make_float(entry)
         }
     }
+
+    /// Reads both `name: hours` and `name: { hours, rate, role, start, end }`
+    fn make_employee(&self, name: String, entry: &Yaml) -> Option<Employee> {
+        let time = self.shift_time(entry);
+        if let Yaml::Hash(_) = entry {
+            let start = self.get_direct(entry, "start").and_then(make_time);
+            let end = self.get_direct(entry, "end").and_then(make_time);
+            let salary = self.get_direct(entry, "rate")
+                             .and_then(Decimal::from_yaml)
+                             .map(Decimal::to_currency)
+                             .or_else(|| self.salary())?;
+            Some(Employee {
+                     name,
+                     time,
+                     wage: multiply(salary, time),
+                     salary,
+                     role: self.get_direct(entry, "role").and_then(Yaml::as_str).map(ToOwned::to_owned),
+                     start,
+                     end,
+                 })
+        } else {
+            let salary = self.salary()?;
+            Some(Employee {
+                     name,
+                     time,
+                     wage: multiply(salary, time),
+                     salary,
+                     role: None,
+                     start: None,
+                     end: None,
+                 })
+        }
+    }
+
+    /// Employees whose shift does not overlap with any of the event's times
+    ///
+    /// This is only a warning, see `asciii show --errors`.
+    pub fn employees_off_shift(&self) -> Vec<String> {
+        let times = self.inner.events()
+                        .unwrap_or_else(Vec::new)
+                        .into_iter()
+                        .flat_map(|event| event.times)
+                        .collect::<Vec<EventTime>>();
+        if times.is_empty() {
+            return Vec::new();
+        }
+
+        self.employees()
+            .unwrap_or_else(Vec::new)
+            .into_iter()
+            .filter(|employee| !times.iter().any(|time| employee.works_during(time)))
+            .map(|employee| employee.name)
+            .collect()
+    }
+}
+
+// helper for HasEmployees::employees()
+fn make_time(t: &Yaml) -> Option<NaiveTime> {
+    match *t {
+        Yaml::String(ref s) => util::naive_time_from_str(s),
+        Yaml::Real(ref s) => util::naive_time_from_str(s),
+        Yaml::Integer(h) => util::naive_time_from_str(&h.to_string()),
+        _ => None
+    }
+}
+
+// helper for HasEmployees::employees()
+fn shift_length(start: NaiveTime, end: NaiveTime) -> f64 {
+    let mut minutes = end.signed_duration_since(start).num_minutes();
+    if minutes <= 0 {
+        // shift ends after midnight
+        minutes += 24 * 60;
+    }
+    minutes as f64 / 60f64
+}
+
+/// `price × factor` without `f64` rounding errors, rounded to cents
+fn multiply(price: Currency, factor: f64) -> Currency {
+    (Decimal::from(price) * Decimal::from_f64(factor).unwrap_or_default()).to_currency()
 }
 
 // helper for HasEmployees::employees()
@@ -0,0 +1,146 @@
+//! Where a project stands in its lifecycle.
+//!
+//! ```text
+//! draft → offered → accepted → held → invoiced → partially_paid → paid → wages_paid → archived
+//! ```
+//!
+//! Steps may be skipped where it makes sense, see [`State::next()`](enum.State.html#method.next).
+//! Until it is invoiced a project can be `canceled`, canceled projects can only be archived.
+//! Once it is invoiced the invoice can be taken back by a credit note, `stornoed` projects can only be archived as well.
+//!
+//! The state is not stored, it follows from the project file:
+//!
+//! * `offered`: the offer is dated and complete, see `Project::is_ready_for_offer()`
+//! * `accepted`: `offer/accepted` holds the date the client agreed
+//! * `held`: the event is over
+//! * `invoiced`: the invoice has a number and a date
+//! * `partially_paid`: invoiced, and a deposit is payed
+//! * `paid`: `invoice/payed_date` is set
+//! * `wages_paid`: `hours/wages_date` is set too, or nobody has to be payed
+//! * `archived`: the storage opened the project from the archive
+//! * `stornoed`: `storno/number` is set, whatever happened to the invoice before
+//!
+//! Only `wages_paid`, `canceled` and `stornoed` projects can be archived,
+//! see [`Project::is_ready_for_archive()`](../struct.Project.html#method.is_ready_for_archive).
+//!
+//! `asciii transition` writes these fields, see [`actions::transition()`](../../actions/fn.transition.html).
+
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::prelude::*;
+use failure::{format_err, Error};
+
+use super::Project;
+use super::spec::*;
+
+/// The states of a project, in the order they are usually passed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum State {
+    Draft,
+    Offered,
+    Accepted,
+    Held,
+    Invoiced,
+    PartiallyPaid,
+    Paid,
+    WagesPaid,
+    Archived,
+    Canceled,
+    Stornoed,
+}
+
+/// Every state, in order
+pub const STATES: [State; 11] = [State::Draft, State::Offered, State::Accepted, State::Held, State::Invoiced,
+                                 State::PartiallyPaid, State::Paid, State::WagesPaid, State::Archived, State::Canceled,
+                                 State::Stornoed];
+
+impl State {
+    /// Reads the state from the project file, `today` decides whether the event is over.
+    pub fn of(project: &Project, today: Date<Utc>) -> Self {
+        if project.is_archived() {
+            State::Archived
+        } else if project.canceled() {
+            State::Canceled
+        } else if project.storno().is_issued() {
+            State::Stornoed
+        } else if project.is_payed() {
+            if project.hours().employees_payed() { State::WagesPaid } else { State::Paid }
+        } else if project.invoice().number().is_some() && project.invoice().date().is_some() {
+            let deposit_payed = project.deposits()
+                                       .map(|deposits| deposits.iter().any(|d| d.payed_date.is_some()))
+                                       .unwrap_or(false);
+            if deposit_payed { State::PartiallyPaid } else { State::Invoiced }
+        } else if project.event_date().map(|date| date < today).unwrap_or(false) {
+            State::Held
+        } else if project.offer().accepted().is_some() {
+            State::Accepted
+        } else if project.offer().date().is_some() && project.is_ready_for_offer().is_ok() {
+            State::Offered
+        } else {
+            State::Draft
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            State::Draft => "draft",
+            State::Offered => "offered",
+            State::Accepted => "accepted",
+            State::Held => "held",
+            State::Invoiced => "invoiced",
+            State::PartiallyPaid => "partially_paid",
+            State::Paid => "paid",
+            State::WagesPaid => "wages_paid",
+            State::Archived => "archived",
+            State::Canceled => "canceled",
+            State::Stornoed => "stornoed",
+        }
+    }
+
+    /// The states a project may go to from here.
+    pub fn next(self) -> &'static [State] {
+        match self {
+            State::Draft => &[State::Offered, State::Canceled],
+            State::Offered => &[State::Accepted, State::Held, State::Canceled],
+            State::Accepted => &[State::Held, State::Invoiced, State::Canceled],
+            State::Held => &[State::Invoiced, State::Canceled],
+            State::Invoiced => &[State::PartiallyPaid, State::Paid, State::Stornoed],
+            State::PartiallyPaid => &[State::Paid, State::Stornoed],
+            State::Paid => &[State::WagesPaid, State::Stornoed],
+            State::WagesPaid => &[State::Archived, State::Stornoed],
+            State::Archived => &[],
+            State::Canceled => &[State::Archived],
+            State::Stornoed => &[State::Archived],
+        }
+    }
+
+    pub fn can_become(self, next: State) -> bool {
+        self.next().contains(&next)
+    }
+
+    /// Whether an invoice was written, canceled projects excluded
+    pub fn is_invoiced(self) -> bool {
+        self >= State::Invoiced && self != State::Canceled
+    }
+}
+
+impl FromStr for State {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        let name = name.trim().replace('-', "_").to_lowercase();
+        STATES.iter()
+              .cloned()
+              .find(|state| state.name() == name)
+              .ok_or_else(|| format_err!("unknown state {:?}, expected one of {}",
+                                         name,
+                                         STATES.iter().map(|s| s.name()).collect::<Vec<_>>().join(", ")))
+    }
+}
+
+impl fmt::Display for State {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
@@ -0,0 +1,109 @@
+//! Named tax categories instead of bare tax rates.
+//!
+//! A product or a whole project can name its `tax_category` instead of a `tax` rate:
+//!
+//! ```yaml
+//! tax_category: exempt                 # standard, reduced, exempt or reverse_charge
+//! tax_exemption: § 4 Nr. 22 UStG       # why, required for exempt
+//! client:
+//!   vat_id: ATU12345678                # required for reverse_charge
+//! cataloge:
+//!   product: &lieferung { name: Lieferung, price: 25, tax_category: reduced }
+//! ```
+//!
+//! `standard` and `reduced` are taxed with `defaults/tax` and `defaults/reduced_tax` of the config,
+//! `exempt` and `reverse_charge` are not taxed at all and need a note on the invoice.
+//! If the project is `exempt` or `reverse_charge`, this applies to every product,
+//! whatever rate it is listed with in the catalog.
+
+use std::fmt;
+use std::str::FromStr;
+
+use failure::{format_err, Error};
+use yaml_rust::Yaml;
+
+use crate::util::yaml;
+use crate::util::decimal::Decimal;
+
+/// How something is taxed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TaxCategory {
+    /// The usual rate, `defaults/tax`
+    Standard,
+    /// The reduced rate, `defaults/reduced_tax`, e.g. for food that is only delivered
+    Reduced,
+    /// Not taxed, for a reason that has to be stated
+    Exempt,
+    /// Not taxed, because the client owes the tax (EU reverse charge)
+    ReverseCharge,
+}
+
+impl TaxCategory {
+    /// Reads `tax_category` of a product or project, `None` if there is none.
+    pub fn from_yaml(yaml: &Yaml) -> Option<Result<Self, Error>> {
+        yaml::get_str(yaml, "tax_category").map(str::parse)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TaxCategory::Standard => "standard",
+            TaxCategory::Reduced => "reduced",
+            TaxCategory::Exempt => "exempt",
+            TaxCategory::ReverseCharge => "reverse_charge",
+        }
+    }
+
+    /// The rate of this category as configured.
+    pub fn rate(self) -> Decimal {
+        let configured = |key| crate::CONFIG.get(key).and_then(Decimal::from_yaml).unwrap_or_default();
+        match self {
+            TaxCategory::Standard => configured("defaults/tax"),
+            TaxCategory::Reduced => configured("defaults/reduced_tax"),
+            TaxCategory::Exempt | TaxCategory::ReverseCharge => Decimal::default(),
+        }
+    }
+
+    /// Whether no tax is charged at all
+    pub fn is_zero_rated(self) -> bool {
+        match self {
+            TaxCategory::Exempt | TaxCategory::ReverseCharge => true,
+            TaxCategory::Standard | TaxCategory::Reduced => false,
+        }
+    }
+}
+
+impl FromStr for TaxCategory {
+    type Err = Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name.trim() {
+            "standard" => Ok(TaxCategory::Standard),
+            "reduced" => Ok(TaxCategory::Reduced),
+            "exempt" => Ok(TaxCategory::Exempt),
+            "reverse_charge" => Ok(TaxCategory::ReverseCharge),
+            other => Err(format_err!("unknown tax category {:?}, expected standard, reduced, exempt or reverse_charge", other))
+        }
+    }
+}
+
+impl fmt::Display for TaxCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_names() {
+        for &category in &[TaxCategory::Standard, TaxCategory::Reduced, TaxCategory::Exempt, TaxCategory::ReverseCharge] {
+            assert_eq!(category.name().parse::<TaxCategory>().unwrap(), category);
+        }
+        assert!("zero".parse::<TaxCategory>().is_err());
+        assert!(TaxCategory::ReverseCharge.rate().is_zero());
+        assert!(TaxCategory::Exempt.is_zero_rated());
+        assert!(!TaxCategory::Reduced.is_zero_rated());
+    }
+}
@@ -185,6 +185,995 @@ pub mod invoice {
   }
 }
 
+pub mod hours {
+  use super::*;
+
+  #[test]
+  fn legacy_caterers() {
+      let doc = r#"
+      hours:
+        salary: 8.0
+        caterers:
+          Alice: 3
+          Bob: 2.5
+          nobody: 0
+      "#;
+
+      let project = parse_project(doc);
+      let employees = project.hours().employees().unwrap();
+      assert_eq!(employees.len(), 2);
+      assert_eq!(project.hours().total_time(), Some(5.5));
+      assert_eq!(project.hours().wages().map(|w| w.value()), Some(44_00));
+  }
+
+  #[test]
+  fn detailed_caterers() {
+      let doc = r#"
+      event:
+        dates:
+          - begin: 01.05.2019
+            times:
+              - { begin: "18:00", end: "23:00" }
+      hours:
+        salary: 8.0
+        tax: 0.25
+        caterers:
+          Alice: { hours: 4, rate: 12.0, role: lead }
+          Bob: { start: "22:00", end: "01:30" }
+          Carl: 2
+      "#;
+
+      let project = parse_project(doc);
+      let employees = project.hours().employees().unwrap();
+      let alice = &employees[0];
+      assert_eq!(alice.role.as_ref().map(String::as_str), Some("lead"));
+      assert_eq!(alice.wage.value(), 48_00);
+      let bob = &employees[1];
+      assert_eq!(bob.time, 3.5);
+      assert_eq!(bob.wage.value(), 28_00);
+
+      assert_eq!(project.hours().total_time(), Some(9.5));
+      assert_eq!(project.hours().gross_wages().map(|w| w.value()), Some(92_00));
+      assert_eq!(project.hours().net_wages().map(|w| w.value()), Some(115_00));
+      assert!(project.hours().employees_off_shift().is_empty());
+  }
+
+  #[test]
+  fn shift_outside_event() {
+      let doc = r#"
+      event:
+        dates:
+          - begin: 01.05.2019
+            times:
+              - { begin: "18:00", end: "23:00" }
+      hours:
+        salary: 8.0
+        caterers:
+          Alice: { start: "08:00", end: "12:00" }
+      "#;
+
+      let project = parse_project(doc);
+      assert_eq!(project.hours().employees_off_shift(), vec![String::from("Alice")]);
+      // only a warning
+      assert_eq!(project.hours().validate().unwrap_err().into_vec(), vec![String::from("employees_payed")]);
+  }
+
+  #[test]
+  fn shifts_after_midnight() {
+      let doc = r#"
+      event:
+        dates:
+          - begin: 01.05.2019
+            times:
+              - { begin: "20:00", end: "02:00" }
+      hours:
+        salary: 8.0
+        caterers:
+          Alice: { start: "19:00", end: "23:00" }
+          Bob: { start: "01:00", end: "03:00" }
+          Carl: { start: "23:00", end: "04:00" }
+          Dora: { start: "03:00", end: "06:00" }
+      "#;
+
+      let project = parse_project(doc);
+      assert_eq!(project.hours().employees_off_shift(), vec![String::from("Dora")]);
+  }
+}
+
+pub mod history {
+  use std::path::PathBuf;
+  use crate::project::history::changelog;
+  use crate::storage::repo::Revision;
+
+  static VERSION1: &str = r#"
+  cataloge:
+    product: &coffee { name: Kaffee, price: 2.5, unit: 1l }
+  hours:
+    salary: 8.0
+  products:
+    *coffee: { amount: 20 }
+  "#;
+
+  fn revision(commit: &str, path: &str) -> Revision {
+    Revision {
+      commit: commit.into(),
+      author: String::from("Hendrik"),
+      date: String::from("2019-05-01T12:00:00+02:00"),
+      path: PathBuf::from(path),
+    }
+  }
+
+  #[test]
+  fn records_amounts_and_invoice_number() {
+    let version2 = VERSION1.replace("{ amount: 20 }", "{ amount: 20, sold: 12 }");
+    let version3 = format!("{}\n  invoice:\n    number: 42\n", version2);
+
+    let entries = changelog(&[
+      (revision("a", "working/test/test.yml"), VERSION1.to_owned()),
+      (revision("b", "working/test/test.yml"), version2),
+      (revision("c", "working/test/test.yml"), version3.clone()),
+      (revision("d", "archive/2019/test/test.yml"), version3),
+    ]);
+
+    assert_eq!(entries.len(), 4);
+
+    let sold = &entries[1].changes;
+    assert_eq!(sold.len(), 1);
+    assert_eq!(sold[0].field, "products/Kaffee/sold");
+    assert_eq!(sold[0].before.as_ref().map(String::as_str), Some("20"));
+    assert_eq!(sold[0].after.as_ref().map(String::as_str), Some("12"));
+    assert!(entries[1].sum_sold_delta.is_some());
+
+    let invoice = &entries[2].changes;
+    assert_eq!(invoice[0].field, "invoice/number");
+    assert_eq!(invoice[0].after.as_ref().map(String::as_str), Some("42"));
+    assert_eq!(entries[2].sum_sold_delta, None);
+
+    assert_eq!(entries[3].changes[0].field, "file");
+  }
+}
+
+pub mod attachments {
+  use super::*;
+  use crate::project::ATTACHMENTS_DIR;
+
+  static DOC: &str = r#"
+  event:
+    name: Summer Party
+    dates:
+      - begin: 01.06.2019
+  manager: somebody
+  format: 2.0.0
+  products: {}
+  attachments:
+    - { file: signed.pdf, kind: offer, date: 10.05.2019 }
+  "#;
+
+  #[test]
+  fn validation_reports_missing_files() {
+    let dir = tempdir::TempDir::new("attachments").unwrap();
+    let project = Project::open_content(&dir.path().join("project.yml"), DOC).unwrap();
+    assert_eq!(project.missing_attachments().len(), 1);
+    assert!(project.validate().unwrap_err().contains(&String::from("attachments")));
+
+    std::fs::create_dir(dir.path().join(ATTACHMENTS_DIR)).unwrap();
+    std::fs::write(dir.path().join(ATTACHMENTS_DIR).join("signed.pdf"), "").unwrap();
+    assert!(project.missing_attachments().is_empty());
+
+    // without a file there is no folder to look in
+    assert!(parse_project(DOC).missing_attachments().is_empty());
+  }
+}
+
+pub mod calendar {
+  use super::*;
+  use crate::util::ical;
+
+  static DOC: &str = r#"
+  created: 18.04.2019
+  offer:
+    date: 20.04.2019
+  event:
+    name: Birthday Party
+    dates:
+      - begin: 01.05.2019
+        times:
+          - { begin: "18:00", end: "20:00" }
+          - { begin: "21:00", end: "23:00" }
+  canceled: true
+  "#;
+
+  #[test]
+  fn stable_uids_and_status() {
+    let project = parse_project(DOC);
+    assert_eq!(project.calendar_uid(), "birthday-party-20190418");
+    // writing the offer or the invoice does not change it
+    assert_eq!(parse_project(&DOC.replace("date: 20.04.2019", "date: 22.04.2019\n    appendix: 2")).calendar_uid(),
+               "birthday-party-20190418");
+    assert_eq!(parse_project(&DOC.replace("  created: 18.04.2019\n", "")).calendar_uid(), "birthday-party-20190501");
+
+    let first = ical::to_string(&project.to_ical());
+    let second = ical::to_string(&project.to_ical());
+    let uids = |cal: &str| cal.lines().filter(|l| l.starts_with("UID:")).map(ToOwned::to_owned).collect::<Vec<_>>();
+    assert_eq!(uids(&first), vec!["UID:birthday-party-20190418-0@asciii", "UID:birthday-party-20190418-1@asciii"]);
+    assert_eq!(uids(&first), uids(&second));
+    assert_eq!(first.matches("STATUS:CANCELLED").count(), 2);
+  }
+}
+
+pub mod attendees {
+  use super::*;
+  use crate::util::to_currency;
+
+  static DOC: &str = r#"
+  event:
+    name: Conference
+    attendees: { expected: 40, actual: 50 }
+  cataloge:
+    product: &kuchen { name: Kuchen, price: 2.0, unit: stk }
+    product: &kaffee { name: Kaffee, price: 2.5, unit: 1l }
+  products:
+    *kuchen: { per_head: 1.5 }
+    *kaffee: { amount: 4 }
+  hours:
+    salary: 8.0
+  tax: 0.0
+  "#;
+
+  #[test]
+  fn per_head_amounts() {
+    let project = parse_project(DOC);
+    assert_eq!(project.attendees(), Attendees { expected: Some(40), actual: Some(50) });
+
+    let amounts = project.product_amounts().unwrap();
+    assert_eq!(amounts[0].per_head, Some(1.5));
+    assert_eq!(amounts[0].offered, 60.0);
+    assert_eq!(amounts[0].sold, 75.0);
+    assert_eq!(amounts[1].per_head, None);
+    assert_eq!(amounts[1].sold, 4.0);
+
+    assert_eq!(project.field("Attendees"), Some("50".to_owned()));
+    assert_eq!(Attendees::per_head(project.sum_sold().unwrap(), project.attendees().for_invoice()),
+               Some(to_currency(3.2)));
+  }
+
+  #[test]
+  fn missing_attendees() {
+    let project = parse_project(&DOC.replace("attendees: { expected: 40, actual: 50 }", "location: somewhere"));
+    assert!(project.bills().is_err());
+    let errors = project.validate().unwrap_err();
+    assert!(errors.errors.iter().any(|e| e == "attendees"));
+
+    let project = parse_project(&DOC.replace("{ expected: 40, actual: 50 }", "30"));
+    assert_eq!(project.attendees().for_invoice(), Some(30));
+    assert_eq!(project.product_amounts().unwrap()[0].sold, 45.0);
+  }
+}
+
+pub mod discounts {
+  use super::*;
+  use crate::util::to_currency;
+  use crate::util::decimal::Decimal;
+
+  static DOC: &str = r#"
+  cataloge:
+    product: &kaffee { name: Kaffee, price: 2.5, unit: 1l, tax: 0.19 }
+    product: &buch   { name: Buch,   price: 10.0, unit: stk, tax: 0.07 }
+  products:
+    *kaffee: { amount: 40 }
+    *buch:   { amount: 10 }
+  discounts:
+    - { name: Studentenrat, percent: 10 }
+    - { name: Kulanz, amount: 20, bill: invoice }
+  surcharges:
+    - { name: Anfahrt, amount: 15, tax: 0.07 }
+  hours:
+    salary: 8.0
+  "#;
+
+  fn group_sums(bill: &bill::Bill<crate::project::product::Product<'_>>) -> Vec<(f64, bill::Currency)> {
+    bill.iter().map(|(tax, list)| (tax.value(), list.gross_sum())).collect()
+  }
+
+  #[test]
+  fn applied_per_tax_group() {
+    let project = parse_project(DOC);
+    let adjustments = project.adjustments().unwrap();
+    assert_eq!(adjustments.len(), 3);
+    assert!(adjustments[0].discount);
+    assert_eq!(adjustments[0].value, AdjustmentValue::Percent(Decimal::from(10)));
+    assert_eq!(adjustments[2].tax, Some("0.07".parse().unwrap()));
+
+    let (offer, invoice) = project.bills().unwrap();
+    assert_eq!(group_sums(&offer), vec![(0.07, to_currency(105.0)), (0.19, to_currency(90.0))]);
+    assert_eq!(group_sums(&invoice), vec![(0.07, to_currency(95.0)), (0.19, to_currency(80.0))]);
+    assert_eq!(invoice.as_items_with_tax().iter().filter(|(_, item)| item.product.name == "Kulanz").count(), 2);
+  }
+
+  #[test]
+  fn rejects_negative_groups() {
+    let project = parse_project(&DOC.replace("amount: 20, bill: invoice", "amount: 500"));
+    let error = project.bills().unwrap_err().to_string();
+    assert!(error.ends_with("taxed with 7% below zero") || error.ends_with("taxed with 19% below zero"), "{}", error);
+    let errors = project.validate().unwrap_err();
+    assert!(errors.errors.iter().any(|e| e == "discounts"));
+
+    let project = parse_project(&DOC.replace("percent: 10", "percent: 10, amount: 3"));
+    assert!(project.adjustments().is_err());
+  }
+
+  #[test]
+  fn reports_invalid_adjustments() {
+    use crate::project::export::{Complete, ExportTarget};
+
+    let dir = tempdir::TempDir::new("discounts").unwrap();
+    let project = Project::open_content(&dir.path().join("project.yml"), &DOC.replace("bill: invoice", "bill: everything")).unwrap();
+    assert!(project.adjustments().is_err());
+    let errors = project.validate().unwrap_err();
+    assert!(errors.errors.iter().any(|e| e == "discounts"));
+
+    // exported with empty bills instead of panicking
+    let _complete: Complete = project.export();
+  }
+}
+
+pub mod storno {
+  use super::*;
+  use crate::project::Exportable;
+
+  static DOC: &str = r#"
+  event:
+    name: Summer Party
+  invoice:
+    number: 42
+    date: 01.06.2019
+  storno:
+    number: 43
+    date: 15.06.2019
+    reason: event canceled by the client
+  cataloge:
+    product: &kaffee { name: Kaffee, price: 2.5, unit: 1l }
+  products:
+    *kaffee: { amount: 10, sold: 8 }
+  hours:
+    salary: 8.0
+  "#;
+
+  #[test]
+  fn credit_note_mirrors_invoice() {
+    let project = parse_project(DOC);
+    assert!(project.storno().is_issued());
+    assert_eq!(project.storno().number_long_str(), Some("R2019-043".to_owned()));
+    assert_eq!(project.storno().reason(), Some("event canceled by the client".to_owned()));
+    assert!(project.storno().validate().is_ok());
+
+    let credit_note = project.credit_note().unwrap();
+    assert_eq!(credit_note.as_items()[0].amount, -8.0);
+    let sold = project.sum_sold().unwrap();
+    let credited = project.sum_credited().unwrap();
+    assert_eq!(credited.value, -sold.value);
+    assert_eq!(project.storno_file_name("tex"), Some("R043 Storno summer-party 2019-06-15.tex".to_owned()));
+  }
+
+  #[test]
+  fn not_issued() {
+    let project = parse_project(&DOC.replace("  storno:\n    number: 43\n    date: 15.06.2019\n", "  storno:\n"));
+    assert!(!project.storno().is_issued());
+    assert!(project.storno().validate().is_err());
+    assert_eq!(project.storno_file_name("tex"), None);
+  }
+}
+
+pub mod deposits {
+  use super::*;
+  use crate::project::{BillType, Exportable};
+  use crate::util::to_currency;
+
+  static DOC: &str = r#"
+  event:
+    name: Summer Party
+  invoice:
+    number: 42
+    date: 01.06.2019
+  cataloge:
+    product: &kaffee { name: Kaffee, price: 2.5, unit: 1l, tax: 0.19 }
+    product: &buch   { name: Buch,   price: 10.0, unit: stk, tax: 0.07 }
+  products:
+    *kaffee: { amount: 40, sold: 32 }
+    *buch:   { amount: 10 }
+  deposits:
+    - { name: Abschlag, number: 40, date: 01.05.2019, percent: 30, payed_date: 10.05.2019 }
+    - { number: 41, date: 15.05.2019, amount: 50 }
+    - { amount: 20 }
+  hours:
+    salary: 8.0
+  "#;
+
+  fn group_sums(bill: &bill::Bill<crate::project::product::Product<'_>>) -> Vec<(f64, bill::Currency)> {
+    bill.iter().map(|(tax, list)| (tax.value(), list.gross_sum())).collect()
+  }
+
+  #[test]
+  fn split_by_tax() {
+    let project = parse_project(DOC);
+    let deposits = project.deposits().unwrap();
+    assert_eq!(deposits.len(), 3);
+    assert_eq!(deposits[1].name, "Deposit");
+    assert_eq!(deposits[0].number_long_str(), Some("R2019-040".to_owned()));
+    assert!(deposits[0].is_payed());
+    assert!(!deposits[2].is_issued());
+
+    assert_eq!(group_sums(&project.deposit_bill(0).unwrap()), vec![(0.07, to_currency(30.0)), (0.19, to_currency(30.0))]);
+    assert_eq!(group_sums(&project.deposit_bill(1).unwrap()), vec![(0.07, to_currency(25.0)), (0.19, to_currency(25.0))]);
+    assert!(project.deposit_bill(3).is_err());
+  }
+
+  #[test]
+  fn deducted_from_final_invoice() {
+    let project = parse_project(DOC);
+    let final_invoice = project.final_invoice().unwrap();
+    assert_eq!(group_sums(&final_invoice), vec![(0.07, to_currency(45.0)), (0.19, to_currency(25.0))]);
+    assert_eq!(project.sum_sold().unwrap().value, project.bills().unwrap().1.net_total().value);
+
+    // the second deposit is not payed yet
+    let open = project.sum_open().unwrap();
+    assert_eq!(open.value, project.sum_final().unwrap().value + project.sum_deposit(1).unwrap().value);
+
+    let csv = project.to_csv(BillType::Invoice).unwrap();
+    assert!(csv.contains("Abschlag"));
+  }
+
+  #[test]
+  fn file_names() {
+    let project = parse_project(DOC);
+    assert_eq!(project.deposit_file_name(0, "tex"), Some("R040 Abschlag summer-party 2019-05-01.tex".to_owned()));
+    assert_eq!(project.deposit_file_name(2, "tex"), None);
+    assert!(project.is_ready_for_deposit(1).is_err()); // no offer
+  }
+
+  #[test]
+  fn rejects_too_much() {
+    let project = parse_project(&DOC.replace("amount: 50", "amount: 500"));
+    assert!(project.final_invoice().is_err());
+    let errors = project.validate().unwrap_err();
+    assert!(errors.errors.iter().any(|e| e == "deposits"));
+    #[cfg(feature="serialization")] {
+      use crate::project::export::{ExportTarget, Invoice, Bills};
+      // exported empty instead of panicking
+      let _: Invoice = project.export();
+      let _: Bills = project.export();
+    }
+
+    let project = parse_project(&DOC.replace("{ amount: 20 }", "{ percent: 0 }"));
+    assert!(project.deposits().is_err());
+    assert!(project.validate().unwrap_err().errors.iter().any(|e| e == "deposits"));
+    #[cfg(feature="serialization")] {
+      use crate::project::export::{ExportTarget, Deposit};
+      let deposits: Vec<Deposit> = project.export();
+      assert!(deposits.is_empty());
+    }
+  }
+}
+
+pub mod cancellation {
+  use super::*;
+  use chrono::prelude::*;
+  use crate::util::to_currency;
+  use crate::util::decimal::Decimal;
+
+  static DOC: &str = r#"
+  event:
+    name: Summer Party
+    dates:
+      - begin: 01.06.2019
+  invoice:
+    payed_date: 20.06.2019
+  canceled: yes
+  cancellation:
+    date: 28.05.2019
+    percent: 40
+  cataloge:
+    product: &kaffee { name: Kaffee, price: 2.5, unit: 1l, tax: 0.19 }
+    product: &buch   { name: Buch,   price: 10.0, unit: stk, tax: 0.07 }
+  products:
+    *kaffee: { amount: 40 }
+    *buch:   { amount: 10 }
+  hours:
+    salary: 8.0
+    caterers:
+      unknown: 0
+  "#;
+
+  fn group_sums(bill: &bill::Bill<crate::project::product::Product<'_>>) -> Vec<(f64, bill::Currency)> {
+    bill.iter().map(|(tax, list)| (tax.value(), list.gross_sum())).collect()
+  }
+
+  #[test]
+  fn fee_replaces_invoice() {
+    let project = parse_project(DOC);
+    assert_eq!(project.cancellation_percent(), Some(Decimal::from(40)));
+    assert!(project.is_billable());
+    let fee = project.final_invoice().unwrap();
+    assert_eq!(group_sums(&fee), vec![(0.07, to_currency(40.0)), (0.19, to_currency(40.0))]);
+    assert_eq!(fee.as_items()[0].product.name, "Cancellation fee");
+    assert!(project.is_ready_for_archive().is_ok());
+
+    let unpayed = parse_project(&DOC.replace("  invoice:\n    payed_date: 20.06.2019\n", ""));
+    assert!(unpayed.is_ready_for_archive().is_err());
+  }
+
+  #[test]
+  fn fee_from_schedule() {
+    // four days before the event
+    let project = parse_project(&DOC.replace("    percent: 40\n", ""));
+    assert_eq!(project.cancellation().unwrap().date, Some(Utc.ymd(2019, 5, 28)));
+    assert_eq!(project.cancellation_percent(), Some(Decimal::from(50)));
+  }
+
+  #[test]
+  fn free_cancellations() {
+    let waived = parse_project(&DOC.replace("percent: 40", "waived: yes"));
+    assert_eq!(waived.cancellation_percent(), None);
+    assert!(!waived.is_billable());
+    assert!(waived.cancellation_fee().unwrap().is_none());
+    assert_eq!(waived.final_invoice().unwrap().net_total(), waived.bills().unwrap().1.net_total());
+
+    let early = parse_project(&DOC.replace("    date: 28.05.2019\n    percent: 40\n", "    date: 01.01.2019\n"));
+    assert_eq!(early.cancellation_percent(), None);
+
+    let legacy = parse_project(&DOC.replace("  cancellation:\n    date: 28.05.2019\n    percent: 40\n", ""));
+    assert!(legacy.canceled());
+    assert!(!legacy.is_billable());
+    assert!(legacy.is_ready_for_archive().is_ok());
+
+    let not_canceled = parse_project(&DOC.replace("canceled: yes", "canceled: no"));
+    assert_eq!(not_canceled.cancellation(), None);
+    assert!(not_canceled.is_billable());
+  }
+}
+
+pub mod expenses {
+  use super::*;
+  use crate::project::{ComputedField, ATTACHMENTS_DIR};
+  use crate::util::to_currency;
+
+  static DOC: &str = r#"
+  event:
+    name: Summer Party
+  cataloge:
+    product: &kaffee { name: Kaffee, price: 2.5, purchase_price: 0.8, unit: 1l, tax: 0.19 }
+    product: &buch   { name: Buch,   price: 10.0, unit: stk, tax: 0.07 }
+  products:
+    *kaffee: { amount: 40, sold: 30 }
+    *buch:   { amount: 10 }
+  expenses:
+    - { name: Getränkemarkt, kind: purchase, amount: 119, tax: 0.19, date: 31.05.2019, receipt: bon.pdf }
+    - { name: Zeltverleih, amount: 107, tax: 0.07 }
+  hours:
+    salary: 10.0
+    caterers:
+      Alice: 4
+  "#;
+
+  #[test]
+  fn cost_and_margin() {
+    let project = parse_project(DOC);
+    let expenses = project.expenses().unwrap();
+    assert_eq!(expenses.len(), 2);
+    assert_eq!(expenses[0].kind, Some("purchase".to_owned()));
+    assert_eq!(expenses[0].before_tax(), to_currency(100.0));
+    assert_eq!(expenses[1].before_tax(), to_currency(100.0));
+    assert!(project.missing_receipts().unwrap().is_empty());
+
+    assert_eq!(project.product_amounts().unwrap()[0].purchase_price, Some(to_currency(0.8)));
+    assert_eq!(project.cost_of_goods().unwrap(), to_currency(24.0));
+    assert_eq!(project.cost().unwrap(), to_currency(224.0));
+
+    // 30 coffee, 10 books and 4 hours of service
+    assert_eq!(project.revenue().unwrap(), to_currency(215.0));
+    let wages = project.hours().gross_wages().unwrap();
+    assert_eq!(project.margin().unwrap().value, to_currency(215.0).value - to_currency(224.0).value - wages.value);
+    assert!(ComputedField::Margin.get(&project).is_some());
+  }
+
+  #[test]
+  fn missing_receipts() {
+    let dir = tempdir::TempDir::new("expenses").unwrap();
+    let project = Project::open_content(&dir.path().join("project.yml"), DOC).unwrap();
+    assert_eq!(project.missing_receipts().unwrap().len(), 1);
+
+    std::fs::create_dir(dir.path().join(ATTACHMENTS_DIR)).unwrap();
+    std::fs::write(dir.path().join(ATTACHMENTS_DIR).join("bon.pdf"), "").unwrap();
+    assert!(project.missing_receipts().unwrap().is_empty());
+  }
+
+  #[test]
+  fn rejects_expenses_without_amount() {
+    let project = parse_project(&DOC.replace("amount: 107, ", ""));
+    assert!(project.expenses().is_err());
+    assert!(project.cost().is_err());
+    assert!(ComputedField::Cost.get(&project).is_none());
+    assert!(project.validate().unwrap_err().errors.iter().any(|e| e == "expenses"));
+  }
+}
+
+pub mod currency {
+  use super::*;
+  use chrono::prelude::*;
+  use crate::util::to_currency;
+
+  static DOC: &str = r#"
+  event:
+    name: Summer Party
+  currency: CHF
+  exchange_rate:
+    rate: 0.9
+    date: 03.06.2019
+  invoice:
+    number: 1
+    date: 05.06.2019
+  cataloge:
+    product: &kaffee { name: Kaffee, price: 2.5, unit: 1l, tax: 0.19 }
+  products:
+    *kaffee: { amount: 40 }
+  "#;
+
+  #[test]
+  fn converts_to_home_currency() {
+    let project = parse_project(DOC);
+    assert_eq!(project.currency(), Some("CHF"));
+    assert_eq!(project.exchange_rate().unwrap().date, Some(Utc.ymd(2019, 6, 3)));
+    assert_eq!(project.to_home_currency(to_currency(100.0)).unwrap(), to_currency(90.0));
+    assert_eq!(project.format_amount(&to_currency(12.5)), "12,50 CHF");
+
+    let home = parse_project(&DOC.replace("currency: CHF", "currency: EUR"));
+    assert_eq!(home.currency(), None);
+    assert_eq!(home.to_home_currency(to_currency(100.0)).unwrap(), to_currency(100.0));
+  }
+
+  #[test]
+  fn formats_wages_in_project_currency() {
+    let project = parse_project(&DOC.replace("  products:", "  hours:\n    salary: 10.0\n    caterers: { Hendrik: 2 }\n  products:"));
+    assert_eq!(project.hours().employees_string().unwrap(), "Hendrik: (2h 20,00 CHF)");
+  }
+
+  #[test]
+  fn invoice_needs_exchange_rate() {
+    let without_rate = parse_project(&DOC.replace("rate: 0.9", "rate: 0"));
+    assert!(without_rate.to_home_currency(to_currency(100.0)).is_err());
+    let errors = without_rate.invoice().validate().unwrap_err();
+    assert!(errors.errors.iter().any(|e| e == "exchange_rate"));
+    assert!(parse_project(DOC).invoice().validate().is_ok());
+  }
+
+  #[test]
+  fn rejects_mixed_currencies() {
+    let project = parse_project(DOC);
+    assert!(project.mixed_currencies().is_empty());
+
+    let mixed = parse_project(&DOC.replace("*kaffee: { amount: 40 }", "*kaffee: { amount: 40, currency: USD }"));
+    assert_eq!(mixed.mixed_currencies().len(), 1);
+    let errors = mixed.validate().unwrap_err();
+    assert!(errors.errors.iter().any(|e| e == "currency"));
+  }
+}
+
+pub mod tax_categories {
+  use super::*;
+  use crate::project::totals::Totals;
+  use crate::project::tax_category::TaxCategory;
+
+  static DOC: &str = r#"
+  event:
+    name: Summer Party
+  client:
+    last_name: Graf
+  tax: 0.19
+  cataloge:
+    product: &kaffee    { name: Kaffee, price: 2.5, unit: 1l, tax: 0.19 }
+    product: &lieferung { name: Lieferung, price: 30.0, tax_category: reduced }
+  products:
+    *kaffee: { amount: 40 }
+    *lieferung: { amount: 1 }
+  hours:
+    salary: 8.0
+    caterers:
+      unknown: 0
+  "#;
+
+  fn taxes(project: &Project) -> Vec<f64> {
+    Totals::of(&project.bills().unwrap().1).groups.iter().map(|group| group.tax.value()).collect()
+  }
+
+  fn errors(project: &Project) -> Vec<String> {
+    project.validate().err().map(|errors| errors.into_vec()).unwrap_or_else(Vec::new)
+  }
+
+  #[test]
+  fn reduced_products() {
+    let project = parse_project(DOC);
+    assert_eq!(taxes(&project), vec![0.07, 0.19]);
+    assert_eq!(project.categorized_products()[0].category, TaxCategory::Reduced);
+    assert!(project.tax_exemptions().is_empty());
+    assert!(!errors(&project).iter().any(|e| e.starts_with("tax") || e == "client/vat_id"));
+  }
+
+  #[test]
+  fn reverse_charge() {
+    let project = parse_project(&DOC.replace("tax: 0.19
+  cataloge", "tax_category: reverse_charge
+  cataloge")
+                                    .replace(", tax_category: reduced", ""));
+    assert!(project.is_reverse_charge());
+    assert_eq!(taxes(&project), vec![0.0]);
+    assert!(errors(&project).iter().any(|e| e == "client/vat_id"));
+
+    let with_vat_id = parse_project(&DOC.replace("tax: 0.19
+  cataloge", "tax_category: reverse_charge
+  cataloge")
+                                        .replace(", tax_category: reduced", "")
+                                        .replace("last_name: Graf", "last_name: Graf
+    vat_id: ATU12345678"));
+    assert_eq!(with_vat_id.client().vat_id(), Some("ATU12345678"));
+    assert!(!errors(&with_vat_id).iter().any(|e| e.starts_with("tax") || e == "client/vat_id"));
+
+    // the reduced product contradicts the project
+    let mixed = parse_project(&DOC.replace("tax: 0.19
+  cataloge", "tax_category: reverse_charge
+  cataloge"));
+    assert!(errors(&mixed).iter().any(|e| e == "tax_category"));
+  }
+
+  #[test]
+  fn exemptions_need_a_reason() {
+    let exempt = parse_project(&DOC.replace("tax_category: reduced", "tax_category: exempt"));
+    assert_eq!(taxes(&exempt), vec![0.0, 0.19]);
+    assert!(errors(&exempt).iter().any(|e| e == "tax_exemption"));
+
+    let with_reason = parse_project(&DOC.replace("tax_category: reduced", "tax_category: exempt, tax_exemption: § 4 Nr. 22 UStG"));
+    assert_eq!(with_reason.tax_exemptions(), vec!["§ 4 Nr. 22 UStG"]);
+    assert!(!errors(&with_reason).iter().any(|e| e.starts_with("tax")));
+  }
+
+  #[test]
+  fn rejects_unknown_categories() {
+    let unknown = parse_project(&DOC.replace("tax_category: reduced", "tax_category: zero"));
+    assert!(unknown.bills().is_err());
+    assert!(errors(&unknown).iter().any(|e| e == "tax_category"));
+
+    let reverse_product = parse_project(&DOC.replace("tax_category: reduced", "tax_category: reverse_charge"));
+    assert!(errors(&reverse_product).iter().any(|e| e == "tax_category"));
+  }
+}
+
+pub mod state {
+  use super::*;
+  use chrono::prelude::*;
+  use crate::project::state::State;
+  use crate::project::ComputedField;
+
+  static DOC: &str = r#"
+  client:
+    title:      Herr
+    first_name: Graf
+    last_name:  Zahl
+    email: graf@example.com
+    address: |
+      Graf Zahl
+      Nummernhöllenstraße 666
+  event:
+    name: Summer Party
+    dates:
+      - begin: 01.06.2019
+  manager: somebody
+  format: 2.0.0
+  offer:
+    date: 01.05.2019
+    appendix: 1
+  invoice: {}
+  cataloge:
+    product: &kaffee { name: Kaffee, price: 2.5, unit: 1l, tax: 0.19 }
+  products:
+    *kaffee: { amount: 40 }
+  hours:
+    salary: 8.0
+    caterers:
+      Alice: 4
+  canceled: no
+  "#;
+
+  fn state(doc: &str, today: Date<Utc>) -> State {
+    State::of(&parse_project(doc), today)
+  }
+
+  #[test]
+  fn follows_from_the_file() {
+    let before = Utc.ymd(2019, 5, 15);
+    let after = Utc.ymd(2019, 6, 15);
+    let invoiced = DOC.replace("invoice: {}", "invoice: { number: 1, date: 05.06.2019 }");
+
+    assert_eq!(state(&DOC.replace("manager: somebody", ""), before), State::Draft);
+    assert_eq!(state(DOC, before), State::Offered);
+    assert_eq!(state(&DOC.replace("appendix: 1", "appendix: 1\n    accepted: 10.05.2019"), before), State::Accepted);
+    assert_eq!(state(DOC, after), State::Held);
+    assert_eq!(state(&invoiced, after), State::Invoiced);
+    assert_eq!(state(&invoiced.replace("canceled: no", "canceled: no\n  deposits:\n    - { name: Abschlag, number: 2, date: 01.05.2019, percent: 30, payed_date: 10.05.2019 }"), after),
+               State::PartiallyPaid);
+
+    let paid = invoiced.replace("date: 05.06.2019 }", "date: 05.06.2019, payed_date: 10.06.2019 }");
+    assert_eq!(state(&paid, after), State::Paid);
+    assert_eq!(state(&paid.replace("salary: 8.0", "salary: 8.0\n    wages_date: 12.06.2019"), after), State::WagesPaid);
+    assert_eq!(state(&paid.replace("      Alice: 4\n", ""), after), State::WagesPaid);
+    assert_eq!(state(&DOC.replace("canceled: no", "canceled: yes"), before), State::Canceled);
+    let stornoed = paid.replace("canceled: no", "canceled: no\n  storno: { number: 2, date: 20.06.2019 }");
+    assert_eq!(state(&stornoed, after), State::Stornoed);
+
+    assert_eq!(ComputedField::State.get(&parse_project(&paid)), Some(String::from("paid")));
+  }
+
+  #[test]
+  fn allowed_transitions() {
+    assert!(State::Draft.can_become(State::Offered));
+    assert!(State::Accepted.can_become(State::Invoiced));
+    assert!(State::Held.can_become(State::Canceled));
+    assert!(!State::Invoiced.can_become(State::Canceled));
+    assert!(!State::Paid.can_become(State::Offered));
+    assert!(!State::Paid.can_become(State::Archived));
+    assert!(State::Archived.next().is_empty());
+    assert!(State::Paid.is_invoiced());
+    assert!(!State::Canceled.is_invoiced());
+    assert!(State::Paid.can_become(State::Stornoed));
+    assert!(!State::Held.can_become(State::Stornoed));
+    assert_eq!(State::Stornoed.next(), &[State::Archived]);
+    assert!(State::Stornoed.is_invoiced());
+    assert_eq!("partially-paid".parse::<State>().unwrap(), State::PartiallyPaid);
+    assert!("done".parse::<State>().is_err());
+  }
+
+  #[test]
+  fn archiving_needs_payed_wages() {
+    let paid = DOC.replace("invoice: {}", "invoice: { number: 1, date: 05.06.2019, payed_date: 10.06.2019 }");
+    let project = parse_project(&paid);
+    assert_eq!(project.state(), State::Paid);
+    assert!(project.is_ready_for_archive().unwrap_err().contains(&String::from("state")));
+
+    let done = parse_project(&paid.replace("salary: 8.0", "salary: 8.0\n    wages_date: 12.06.2019"));
+    assert_eq!(done.state(), State::WagesPaid);
+    assert!(done.is_ready_for_archive().is_ok());
+  }
+
+  #[test]
+  fn archiving_stornoed_invoices() {
+    let stornoed = DOC.replace("invoice: {}", "invoice: { number: 1, date: 05.06.2019 }")
+                      .replace("canceled: no", "canceled: no\n  storno: { number: 2, date: 20.06.2019 }");
+    let project = parse_project(&stornoed);
+    assert_eq!(project.state(), State::Stornoed);
+    assert!(project.is_ready_for_archive().unwrap_err().contains(&String::from("employees_payed")));
+
+    let done = parse_project(&stornoed.replace("salary: 8.0", "salary: 8.0\n    wages_date: 12.06.2019"));
+    assert!(done.is_ready_for_archive().is_ok());
+  }
+}
+
+#[cfg(feature="serialization")]
+pub mod offer_versions {
+  use super::*;
+  use crate::project::export::{Complete, ExportTarget};
+  use crate::project::offer_versions::{diff, Change, Version};
+
+  static DOC: &str = r#"
+  client:
+    title:      Herr
+    first_name: Graf
+    last_name:  Zahl
+    email: graf@example.com
+    address: |
+      Graf Zahl
+      Nummernhöllenstraße 666
+  event:
+    name: Summer Party
+    dates:
+      - begin: 01.06.2019
+  manager: somebody
+  format: 2.0.0
+  offer:
+    date: 01.05.2019
+    appendix: 1
+    accepted: 10.05.2019
+    accepted_appendix: 1
+    note: "without tea"
+  invoice: { number: 1, date: 05.06.2019 }
+  cataloge:
+    product: &kaffee { name: Kaffee, price: 2.5, unit: 1l, tax: 0.19 }
+    product: &tee    { name: Tee,    price: 2.0, unit: 1l, tax: 0.19 }
+  products:
+    *kaffee: { amount: 40 }
+  hours:
+    salary: 8.0
+    caterers:
+      Alice: 4
+  "#;
+
+  /// The export needs the project folder, so the project gets a file path.
+  fn stored_project(doc: &str) -> Project {
+    let dir = tempdir::TempDir::new("offer_versions").unwrap();
+    Project::open_content(&dir.path().join("project.yml"), doc).unwrap()
+  }
+
+  fn snapshot(doc: &str) -> Version {
+    let project = stored_project(doc);
+    let complete: Complete = project.export();
+    let stored = serde_json::json!({
+      "number": project.offer().number(),
+      "appendix": project.offer().appendix(),
+      "pdf": "offer.pdf",
+      "document": complete
+    });
+    Version::from_json(&stored.to_string()).unwrap()
+  }
+
+  #[test]
+  fn reads_the_answer() {
+    let project = parse_project(DOC);
+    assert_eq!(project.offer().accepted_appendix(), Some(1));
+    assert_eq!(project.offer().note(), Some("without tea"));
+    assert!(project.offer().rejected().is_none());
+  }
+
+  #[test]
+  fn compares_offer_versions() {
+    let first = snapshot(DOC);
+    let second = snapshot(&DOC.replace("    appendix: 1\n", "    appendix: 2\n")
+                              .replace("*kaffee: { amount: 40 }", "*kaffee: { amount: 50 }\n    *tee: { amount: 10 }"));
+    assert_eq!(first.appendix, 1);
+    assert_eq!(second.appendix, 2);
+    assert_eq!(first.pdf.as_ref().map(String::as_str), Some("offer.pdf"));
+
+    let changes = diff(&first, &second);
+    assert!(changes.iter().any(|c| match c { Change::Changed { after, .. } => after.name == "Kaffee" && after.amount == 50.0, _ => false }));
+    assert!(changes.iter().any(|c| match c { Change::Added(line) => line.name == "Tee", _ => false }));
+    assert!(changes.iter().any(|c| match c { Change::Total { .. } => true, _ => false }));
+    assert!(diff(&first, &first).is_empty());
+  }
+
+  #[test]
+  fn compares_invoice_with_accepted_offer() {
+    let offer = snapshot(DOC);
+    let sold = stored_project(&DOC.replace("*kaffee: { amount: 40 }", "*kaffee: { amount: 40, sold: 30 }"));
+    let invoice = Version::of_invoice(&sold).unwrap();
+    assert_eq!(invoice.appendix, 0);
+
+    let changes = diff(&offer, &invoice);
+    assert!(changes.iter().any(|c| match c { Change::Changed { before, after } => before.amount == 40.0 && after.amount == 30.0, _ => false }));
+    assert!(changes.iter().any(|c| match c { Change::Total { .. } => true, _ => false }));
+    assert!(diff(&offer, &Version::of_invoice(&stored_project(DOC)).unwrap()).is_empty());
+  }
+
+  #[test]
+  fn keeps_versions_in_the_backend() {
+    use std::sync::Arc;
+    use crate::project::offer_versions::{store, versions};
+    use crate::storage::{Backend, MemoryBackend};
+
+    let backend = Arc::new(MemoryBackend::new());
+    let dir = Path::new("/storage/summer_party");
+    backend.create_dir(Path::new("/storage")).unwrap();
+    backend.create_dir(dir).unwrap();
+    backend.write(&dir.join("summer_party.yml"), DOC).unwrap();
+    let mut project = Project::open_content(&dir.join("summer_party.yml"), DOC).unwrap();
+    project.set_backend(backend.clone());
+
+    let complete: Complete = project.export();
+    let stored = store(&project, &complete, Path::new("offer.pdf")).unwrap();
+    assert!(backend.exists(&stored));
+    assert_eq!(versions(&project).unwrap().len(), 1);
+
+    project.write_content(&DOC.replace("without tea", "with tea")).unwrap();
+    assert!(backend.read(&dir.join("summer_party.yml")).unwrap().contains("note: \"with tea\""));
+    assert!(!dir.exists());
+  }
+}
+
 /*
 
 mod product {
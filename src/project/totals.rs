@@ -0,0 +1,165 @@
+//! Exact sums of a `Bill`.
+//!
+//! `bill` multiplies `Currency` with `f64` and cuts off what is left below a cent,
+//! so its sums can be a cent off from what is printed line by line.
+//! Here every line is `price × amount` in exact [`Decimal`](../../util/decimal/struct.Decimal.html)s,
+//! and what gets rounded when is configured under `defaults/rounding`:
+//!
+//! * `line` (default): every line is rounded to cents, the sums add up the lines as printed.
+//! * `total`: lines are summed up exactly, only the sum of each tax group is rounded.
+//!
+//! Either way the tax is calculated once per tax rate from the rounded sum of the group,
+//! as German invoices require, and the total is the sum of what is printed.
+//! Halves are always rounded away from zero.
+
+use bill::{Bill, BillItem, Currency, Tax};
+
+use crate::util::decimal::Decimal;
+use super::product::Product;
+
+/// When to round to cents
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    /// Every line of a bill
+    Line,
+    /// Only the sums of the tax groups
+    Total,
+}
+
+impl Rounding {
+    /// Reads `defaults/rounding`, anything but `total` rounds every line.
+    pub fn from_config() -> Self {
+        match crate::CONFIG.get_str_or("defaults/rounding") {
+            Some("total") => Rounding::Total,
+            _ => Rounding::Line
+        }
+    }
+}
+
+/// `price × amount` of one line, rounded to cents with `Rounding::Line`
+pub fn line_total(item: &BillItem<Product<'_>>, rounding: Rounding) -> Decimal {
+    let total = Decimal::from(item.product.price) * item.product.quantity;
+    match rounding {
+        Rounding::Line => total.round(2),
+        Rounding::Total => total
+    }
+}
+
+/// What is printed as the price of a line
+pub fn line_cost(item: &BillItem<Product<'_>>) -> Currency {
+    line_total(item, Rounding::Line).to_currency()
+}
+
+/// The sums of one tax group
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TaxGroup {
+    pub tax: Tax,
+    /// The exact rate behind `tax`
+    pub rate: Decimal,
+    pub before_tax: Currency,
+    pub tax_amount: Currency,
+}
+
+impl TaxGroup {
+    pub fn after_tax(&self) -> Currency {
+        self.before_tax + self.tax_amount
+    }
+
+    /// The tax rate in percent, e.g. `19` or `7`
+    pub fn percent(&self) -> Decimal {
+        self.rate * Decimal::from(100)
+    }
+}
+
+/// The sums of a whole bill, one group per tax rate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Totals {
+    pub groups: Vec<TaxGroup>,
+}
+
+impl Totals {
+    /// Sums up `bill` as configured.
+    pub fn of(bill: &Bill<Product<'_>>) -> Self {
+        Self::with_rounding(bill, Rounding::from_config())
+    }
+
+    pub fn with_rounding(bill: &Bill<Product<'_>>, rounding: Rounding) -> Self {
+        let groups = bill.iter()
+                         .map(|(&tax, list)| {
+                             let before_tax = list.iter()
+                                                  .map(|item| line_total(item, rounding))
+                                                  .sum::<Decimal>()
+                                                  .round(2);
+                             let rate = list.first().map(|item| item.product.rate).unwrap_or_default();
+                             TaxGroup {
+                                 tax,
+                                 rate,
+                                 before_tax: before_tax.to_currency(),
+                                 tax_amount: (before_tax * rate).to_currency(),
+                             }
+                         })
+                         .collect();
+        Totals { groups }
+    }
+
+    fn sum(&self, part: impl Fn(&TaxGroup) -> Currency) -> Currency {
+        self.groups.iter()
+            .map(part)
+            .fold(Decimal::default().to_currency(), |acc, x| acc + x)
+    }
+
+    pub fn before_tax(&self) -> Currency {
+        self.sum(|group| group.before_tax)
+    }
+
+    pub fn tax(&self) -> Currency {
+        self.sum(|group| group.tax_amount)
+    }
+
+    /// What the client pays
+    pub fn after_tax(&self) -> Currency {
+        self.sum(TaxGroup::after_tax)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::to_currency;
+
+    fn dec(text: &str) -> Decimal {
+        text.parse().unwrap()
+    }
+
+    fn product(name: &'static str, price: &str, rate: &str) -> Product<'static> {
+        Product::new(name, None, dec(rate), dec(price).to_currency())
+    }
+
+    #[test]
+    fn rounds_lines_or_totals() {
+        let mut bill = Bill::new();
+        bill.add(product("milk", "0.05", "0.19").item(dec("0.5")));
+        bill.add(product("sugar", "0.05", "0.19").item(dec("0.5")));
+        bill.add(product("spoon", "0.10", "0.07").item(dec("1")));
+
+        // 0.025 becomes 0.03 on each line
+        let by_line = Totals::with_rounding(&bill, Rounding::Line);
+        assert_eq!(by_line.before_tax(), to_currency(0.16));
+        let by_total = Totals::with_rounding(&bill, Rounding::Total);
+        assert_eq!(by_total.before_tax(), to_currency(0.15));
+    }
+
+    #[test]
+    fn taxes_each_group_once() {
+        let mut bill = Bill::new();
+        for &name in &["coffee", "tea", "cocoa"] {
+            bill.add(product(name, "0.35", "0.19").item(dec("1")));
+        }
+        let totals = Totals::with_rounding(&bill, Rounding::Line);
+        assert_eq!(totals.groups[0].percent(), dec("19"));
+        // 3 × 0.0665 would be 0.21, but 1.05 × 0.19 = 0.1995
+        assert_eq!(totals.tax(), to_currency(0.20));
+        assert_eq!(totals.after_tax(), to_currency(1.25));
+        assert_eq!(totals.after_tax().value, totals.before_tax().value + totals.tax().value);
+    }
+}
@@ -1,6 +1,7 @@
 use chrono::prelude::*;
 use yaml_rust::{Yaml, yaml::Hash as YamlHash};
 
+use crate::util::decimal::Decimal;
 use crate::util::yaml::parse_dmy_date;
 
 use super::error::ErrorList;
@@ -130,6 +131,11 @@ pub trait YamlProvider {
             .and_then(|y| y.as_f64().or_else(|| y.as_i64().map(|y| y as f64)))
     }
 
+    /// Gets a number as exact `Decimal`, read from its source text.
+    fn get_decimal(&self, path: &str) -> Option<Decimal> {
+        self.get(path).and_then(Decimal::from_yaml)
+    }
+
     #[deprecated]
     fn field_exists<'a>(&'a self, paths: &[&'a str]) -> ErrorList {
         let mut errors = ErrorList::new();
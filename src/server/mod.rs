@@ -1,12 +1,22 @@
 #![cfg(feature = "server")]
 #![allow(clippy::new_without_default)]
 use log::debug;
+use failure::Error;
 
 use linked_hash_map::LinkedHashMap;
 use itertools::Itertools;
 
+use std::path::PathBuf;
+
+use chrono::prelude::*;
+use icalendar::Calendar;
+
 use crate::project::Project;
+use crate::project::spec::{HasAttachments, HasEvents, IsProject};
+use crate::project::reminders::Rules;
+use crate::project::history::{self, HistoryEntry};
 use crate::storage::{self, ProjectList, Storage, StorageDir, Storable};
+use crate::util::ical;
 
 pub struct ProjectLoader {
     pub storage: Storage<Project>,
@@ -62,4 +72,64 @@ impl<'a> ProjectLoader {
         debug!("updating projects");
         self.state = reinitialize(&self.storage);
     }
+
+    /// Semantic changelog of the project mapped to `name`, `None` if there is no such project.
+    pub fn history(&self, name: &str) -> Option<Result<Vec<HistoryEntry>, Error>> {
+        let project = self.state.mapped.get(name)?;
+        Some(storage::setup_with_git::<Project>()
+             .and_then(|storage| history::of_project(project, storage.get_repository()?)))
+    }
+
+    /// Path of an attachment of the project mapped to `name`.
+    ///
+    /// Only files that are listed in the project and actually exist are returned.
+    pub fn attachment(&self, name: &str, file: &str) -> Option<PathBuf> {
+        let project = self.state.mapped.get(name)?;
+        if file.contains(|c| c == '/' || c == '\\') || file.starts_with('.') {
+            return None;
+        }
+        let dir = project.attachments_dir()?;
+        project.attachments()
+            .iter()
+            .find(|attachment| attachment.file == file)
+            .map(|attachment| dir.join(&attachment.file))
+            .filter(|path| path.is_file())
+    }
+
+    /// Calendar feed of events and tasks for whoever `token` belongs to.
+    ///
+    /// Tokens are configured in `server/calendar_tokens`, each maps to a responsible person
+    /// whose projects are included, or to `*` for all projects.
+    /// Events of this and last year are listed, tasks only for projects in the working directory.
+    /// Returns `None` if the token is unknown.
+    pub fn calendar(&self, token: &str) -> Option<Result<String, Error>> {
+        let owner = crate::CONFIG.get("server/calendar_tokens")
+            .and_then(|tokens| tokens.as_hash())
+            .and_then(|tokens| tokens.iter().find(|(t, _)| t.as_str() == Some(token)))
+            .and_then(|(_, owner)| owner.as_str())?
+            .trim()
+            .to_lowercase();
+
+        let belongs_to_owner = |project: &Project| {
+            owner == "*" || project.responsible().map_or(false, |r| r.trim().to_lowercase() == owner)
+        };
+
+        let rules = match Rules::from_config() {
+            Ok(rules) => rules,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let year = Utc::today().year();
+        let mut calendar = Calendar::new();
+        for project in self.state.all.iter()
+                           .filter(|p| Storable::year(*p).map_or(false, |y| y >= year - 1))
+                           .filter(|p| belongs_to_owner(*p)) {
+            calendar.append(&mut project.to_ical());
+        }
+        for project in self.state.working.values().filter(|p| belongs_to_owner(*p)) {
+            calendar.append(&mut project.to_tasks(&rules));
+        }
+
+        Some(Ok(ical::to_string(&calendar)))
+    }
 }
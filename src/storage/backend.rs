@@ -0,0 +1,311 @@
+//! Backends that `Storage` keeps its files in.
+//!
+//! `Storage` only knows about the directory layout (`working/`, `archive/<year>/`, `templates/` ...),
+//! everything that actually touches files goes through a [`Backend`](trait.Backend.html).
+//!
+//! * [`FsBackend`](struct.FsBackend.html) is the plain filesystem and the default.
+//! * [`MemoryBackend`](struct.MemoryBackend.html) keeps everything in memory, handy for tests.
+//! * [`FileDbBackend`](struct.FileDbBackend.html) keeps the whole storage in one single file.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+use chrono::{DateTime, Utc};
+use failure::{bail, format_err, Error};
+use log::trace;
+
+use super::StorageError;
+use super::list_path_content;
+
+/// Everything `Storage` needs to do with files.
+///
+/// Paths are always absolute and include the storage root.
+/// Projects keep a handle to the backend they were opened from, hence `'static`.
+pub trait Backend: Send + Sync + 'static {
+    /// Whether there is a file or directory at `path`.
+    fn exists(&self, path: &Path) -> bool;
+
+    /// Whether there is a directory at `path`.
+    fn is_dir(&self, path: &Path) -> bool;
+
+    /// Basically `ls`, lists direct children of a directory.
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>, Error>;
+
+    /// Creates a single directory.
+    fn create_dir(&self, path: &Path) -> Result<(), Error>;
+
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>, Error>;
+
+    /// Creates or replaces a file.
+    fn write_bytes(&self, path: &Path, content: &[u8]) -> Result<(), Error>;
+
+    /// Reads a text file.
+    fn read(&self, path: &Path) -> Result<String, Error> {
+        String::from_utf8(self.read_bytes(path)?)
+            .map_err(|_| format_err!("{} is not a text file", path.display()))
+    }
+
+    /// Creates or replaces a text file.
+    fn write(&self, path: &Path, content: &str) -> Result<(), Error> {
+        self.write_bytes(path, content.as_bytes())
+    }
+
+    /// Moves a file or directory with all of its content.
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error>;
+
+    fn remove_file(&self, path: &Path) -> Result<(), Error>;
+
+    /// When the file at `path` was last written to, `None` if the backend does not keep track of that.
+    fn modified(&self, _path: &Path) -> Option<DateTime<Utc>> { None }
+
+    /// Copies the file `from` outside of the storage to `to` inside of it.
+    fn import(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        let content = fs::read(from)
+            .map_err(|e| format_err!("can't import {}: {}", from.display(), e))?;
+        self.write_bytes(to, &content)
+    }
+}
+
+/// The plain filesystem.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct FsBackend;
+
+impl Backend for FsBackend {
+    fn exists(&self, path: &Path) -> bool { path.exists() }
+
+    fn is_dir(&self, path: &Path) -> bool { path.is_dir() }
+
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>, Error> { list_path_content(path) }
+
+    fn create_dir(&self, path: &Path) -> Result<(), Error> { Ok(fs::create_dir(path)?) }
+
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>, Error> { Ok(fs::read(path)?) }
+
+    fn write_bytes(&self, path: &Path, content: &[u8]) -> Result<(), Error> { Ok(fs::write(path, content)?) }
+
+    fn read(&self, path: &Path) -> Result<String, Error> { Ok(fs::read_to_string(path)?) }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> { Ok(fs::rename(from, to)?) }
+
+    fn remove_file(&self, path: &Path) -> Result<(), Error> { Ok(fs::remove_file(path)?) }
+
+    fn modified(&self, path: &Path) -> Option<DateTime<Utc>> {
+        fs::metadata(path).and_then(|meta| meta.modified()).ok().map(DateTime::<Utc>::from)
+    }
+
+    fn import(&self, from: &Path, to: &Path) -> Result<(), Error> { fs::copy(from, to)?; Ok(()) }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Entry {
+    Dir,
+    File(Vec<u8>),
+}
+
+/// Keeps all files in memory, nothing is ever written to disk.
+#[derive(Debug, Default)]
+pub struct MemoryBackend {
+    entries: RwLock<BTreeMap<PathBuf, Entry>>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn ensure_parent_dir(entries: &BTreeMap<PathBuf, Entry>, path: &Path) -> Result<(), Error> {
+        match path.parent() {
+            Some(parent) if entries.get(parent) != Some(&Entry::Dir) => bail!(StorageError::PathNotFound(parent.to_owned())),
+            _ => Ok(())
+        }
+    }
+}
+
+impl Backend for MemoryBackend {
+    fn exists(&self, path: &Path) -> bool {
+        self.entries.read().unwrap().contains_key(path)
+    }
+
+    fn is_dir(&self, path: &Path) -> bool {
+        self.entries.read().unwrap().get(path) == Some(&Entry::Dir)
+    }
+
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>, Error> {
+        let entries = self.entries.read().unwrap();
+        if entries.get(path) != Some(&Entry::Dir) {
+            bail!(StorageError::PathNotFound(path.to_owned()));
+        }
+        Ok(entries.keys()
+                  .filter(|key| key.parent() == Some(path))
+                  .cloned()
+                  .collect())
+    }
+
+    fn create_dir(&self, path: &Path) -> Result<(), Error> {
+        let mut entries = self.entries.write().unwrap();
+        if entries.contains_key(path) {
+            bail!(StorageError::PathExists(path.to_owned()));
+        }
+        entries.insert(path.to_owned(), Entry::Dir);
+        Ok(())
+    }
+
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>, Error> {
+        match self.entries.read().unwrap().get(path) {
+            Some(Entry::File(content)) => Ok(content.to_owned()),
+            _ => bail!(StorageError::PathNotFound(path.to_owned()))
+        }
+    }
+
+    fn write_bytes(&self, path: &Path, content: &[u8]) -> Result<(), Error> {
+        let mut entries = self.entries.write().unwrap();
+        Self::ensure_parent_dir(&entries, path)?;
+        if entries.get(path) == Some(&Entry::Dir) {
+            bail!(StorageError::PathExists(path.to_owned()));
+        }
+        entries.insert(path.to_owned(), Entry::File(content.to_owned()));
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        trace!("memory: mv {} {}", from.display(), to.display());
+        let mut entries = self.entries.write().unwrap();
+        if !entries.contains_key(from) {
+            bail!(StorageError::PathNotFound(from.to_owned()));
+        }
+        if entries.contains_key(to) {
+            bail!(StorageError::PathExists(to.to_owned()));
+        }
+        Self::ensure_parent_dir(&entries, to)?;
+
+        let moved = entries.keys()
+                           .filter(|key| key.starts_with(from))
+                           .cloned()
+                           .collect::<Vec<_>>();
+        for old in moved {
+            let entry = entries.remove(&old).unwrap();
+            let new = to.join(old.strip_prefix(from).unwrap());
+            entries.insert(new, entry);
+        }
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), Error> {
+        let mut entries = self.entries.write().unwrap();
+        match entries.get(path) {
+            Some(Entry::File(_)) => { entries.remove(path); Ok(()) },
+            _ => bail!(StorageError::PathNotFound(path.to_owned()))
+        }
+    }
+}
+
+/// Keeps the entire storage in one single database file.
+///
+/// All content is held in memory, every change is written to the database file right away.
+/// The file is a header line per entry, either `dir <path>`
+/// or `file <length> <path>` followed by `<length>` bytes of content, which may be binary.
+#[derive(Debug)]
+pub struct FileDbBackend {
+    db_file: PathBuf,
+    inner: MemoryBackend,
+}
+
+impl FileDbBackend {
+    /// Opens the database at `db_file`, it is created on the first change if it does not exist.
+    pub fn open<P: AsRef<Path>>(db_file: P) -> Result<Self, Error> {
+        let db_file = db_file.as_ref().to_owned();
+        let inner = MemoryBackend::new();
+        if db_file.exists() {
+            *inner.entries.write().unwrap() = Self::parse(&fs::read(&db_file)?)?;
+        }
+        Ok(FileDbBackend { db_file, inner })
+    }
+
+    /// Path of the database file.
+    pub fn db_file(&self) -> &Path {
+        &self.db_file
+    }
+
+    fn parse(content: &[u8]) -> Result<BTreeMap<PathBuf, Entry>, Error> {
+        let mut entries = BTreeMap::new();
+        let mut rest = content;
+        while !rest.is_empty() {
+            let line_end = rest.iter().position(|&b| b == b'\n').ok_or_else(|| format_err!("truncated database"))?;
+            let header = std::str::from_utf8(&rest[..line_end])
+                .map_err(|_| format_err!("broken database entry header"))?;
+            rest = &rest[line_end + 1..];
+
+            if header.starts_with("dir ") {
+                entries.insert(PathBuf::from(&header[4..]), Entry::Dir);
+            } else if header.starts_with("file ") {
+                let mut fields = header[5..].splitn(2, ' ');
+                let length = fields.next().and_then(|l| l.parse::<usize>().ok())
+                                   .ok_or_else(|| format_err!("broken database entry {:?}", header))?;
+                let path = fields.next().ok_or_else(|| format_err!("broken database entry {:?}", header))?;
+                if rest.len() < length {
+                    bail!(format_err!("truncated database"));
+                }
+                entries.insert(PathBuf::from(path), Entry::File(rest[..length].to_owned()));
+                rest = &rest[length..];
+                if rest.first() == Some(&b'\n') {
+                    rest = &rest[1..];
+                }
+            } else {
+                bail!(format_err!("broken database entry {:?}", header));
+            }
+        }
+        Ok(entries)
+    }
+
+    fn persist(&self) -> Result<(), Error> {
+        let mut content = Vec::new();
+        for (path, entry) in self.inner.entries.read().unwrap().iter() {
+            match entry {
+                Entry::Dir => content.extend_from_slice(format!("dir {}\n", path.display()).as_bytes()),
+                Entry::File(file) => {
+                    content.extend_from_slice(format!("file {} {}\n", file.len(), path.display()).as_bytes());
+                    content.extend_from_slice(file);
+                    content.push(b'\n');
+                },
+            }
+        }
+
+        // write a copy first, so a crash can't leave a half written database behind
+        let temp = self.db_file.with_extension("tmp");
+        fs::write(&temp, content)?;
+        fs::rename(&temp, &self.db_file)?;
+        Ok(())
+    }
+}
+
+impl Backend for FileDbBackend {
+    fn exists(&self, path: &Path) -> bool { self.inner.exists(path) }
+
+    fn is_dir(&self, path: &Path) -> bool { self.inner.is_dir(path) }
+
+    fn list(&self, path: &Path) -> Result<Vec<PathBuf>, Error> { self.inner.list(path) }
+
+    fn create_dir(&self, path: &Path) -> Result<(), Error> {
+        self.inner.create_dir(path)?;
+        self.persist()
+    }
+
+    fn read_bytes(&self, path: &Path) -> Result<Vec<u8>, Error> { self.inner.read_bytes(path) }
+
+    fn write_bytes(&self, path: &Path, content: &[u8]) -> Result<(), Error> {
+        self.inner.write_bytes(path, content)?;
+        self.persist()
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<(), Error> {
+        self.inner.rename(from, to)?;
+        self.persist()
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<(), Error> {
+        self.inner.remove_file(path)?;
+        self.persist()
+    }
+}
@@ -54,4 +54,10 @@ pub enum StorageError {
     #[fail(display = "Nothing found for {:?}", _0)]
     NothingFound(Vec<String>),
 
+    #[fail(display = "{:?} does not exist.", _0)]
+    PathNotFound(PathBuf),
+
+    #[fail(display = "{:?} already exists.", _0)]
+    PathExists(PathBuf),
+
 }
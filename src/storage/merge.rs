@@ -0,0 +1,332 @@
+//! Field-level three-way merge of yaml documents.
+//!
+//! Used by `asciii sync` to resolve git conflicts in project files.
+//! Both sides are compared against their common ancestor, a field is only considered
+//! conflicting if it was changed differently on both sides.
+//! Hashes are merged recursively, so a changed `client/email` on one side and a
+//! changed `invoice/number` on the other merge cleanly.
+
+use std::fmt;
+
+use log::warn;
+use yaml_rust::Yaml;
+use yaml_rust::yaml::Hash as YamlHash;
+
+use crate::util::yaml;
+
+/// Which version of a conflicting field to keep.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Side {
+    /// The local version.
+    Ours,
+    /// The version that was just pulled.
+    Theirs,
+}
+
+/// A field that was changed on both sides.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Conflict {
+    /// Keys leading to the conflicting field.
+    pub path: Vec<Yaml>,
+    pub base: Option<Yaml>,
+    pub ours: Option<Yaml>,
+    pub theirs: Option<Yaml>,
+}
+
+impl Conflict {
+    /// Human readable path, like `hours/caterers/Maxime`.
+    pub fn path_string(&self) -> String {
+        self.path.iter().map(key_to_string).collect::<Vec<_>>().join("/")
+    }
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.path_string())?;
+        writeln!(f, "  base:   {}", value_to_string(self.base.as_ref()))?;
+        writeln!(f, "  ours:   {}", value_to_string(self.ours.as_ref()))?;
+        write!(f, "  theirs: {}", value_to_string(self.theirs.as_ref()))
+    }
+}
+
+/// Result of `merge()`.
+#[derive(Debug)]
+pub struct Merge {
+    /// The merged document, conflicting fields hold our version until resolved.
+    pub merged: Yaml,
+    /// Fields that need a decision.
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Merge {
+    /// Decides a conflict by picking one side.
+    pub fn resolve(&mut self, conflict: &Conflict, side: Side) {
+        let value = match side {
+            Side::Ours => conflict.ours.clone(),
+            Side::Theirs => conflict.theirs.clone(),
+        };
+        set_path(&mut self.merged, &conflict.path, value);
+    }
+
+    /// Writes the merged document by changing `ours`, the text of the local version.
+    ///
+    /// Only the fields that differ from our version are replaced, like `util::yaml::set_value()` does,
+    /// so comments and anchors of the file survive.
+    /// If that does not reproduce the merged document the whole document is emitted instead.
+    pub fn to_yaml_string(&self, ours: &str) -> Result<String, failure::Error> {
+        let mut changes = Vec::new();
+        collect_changes(&mut Vec::new(), Some(&yaml::parse(ours)?), Some(&self.merged), &mut changes);
+
+        if changes.iter().all(|(path, _)| !path.is_empty()) {
+            let mut content = ours.to_owned();
+            for (path, value) in &changes {
+                let keys = path.iter().map(String::as_str).collect::<Vec<&str>>();
+                content = match *value {
+                    Some(ref value) => yaml::set_value_at(&content, &keys, &value_to_yaml(value)?)?,
+                    None => yaml::remove_value_at(&content, &keys)?
+                };
+            }
+            if yaml::parse(&content).map(|doc| same(&doc, &self.merged)).unwrap_or(false) {
+                return Ok(content);
+            }
+            warn!("could not keep the layout of the merged file, comments and anchors are lost");
+        }
+
+        let mut buf = value_to_yaml(&self.merged)?;
+        if buf.starts_with('\n') {
+            buf.remove(0);
+        }
+        buf.push_str("\n");
+        Ok(buf)
+    }
+}
+
+/// Merges `ours` and `theirs` with respect to their common ancestor `base`.
+pub fn merge(base: &Yaml, ours: &Yaml, theirs: &Yaml) -> Merge {
+    let mut conflicts = Vec::new();
+    let merged = merge_values(&mut Vec::new(), Some(base), Some(ours), Some(theirs), &mut conflicts)
+        .unwrap_or(Yaml::Null);
+    Merge { merged, conflicts }
+}
+
+fn merge_values(path: &mut Vec<Yaml>,
+                base: Option<&Yaml>,
+                ours: Option<&Yaml>,
+                theirs: Option<&Yaml>,
+                conflicts: &mut Vec<Conflict>) -> Option<Yaml> {
+    if ours == theirs {
+        return ours.cloned();
+    }
+    if base == ours {
+        return theirs.cloned();
+    }
+    if base == theirs {
+        return ours.cloned();
+    }
+
+    if let (Some(&Yaml::Hash(ref ours_hash)), Some(&Yaml::Hash(ref theirs_hash))) = (ours, theirs) {
+        let empty = YamlHash::new();
+        let base_hash = base.and_then(Yaml::as_hash).unwrap_or(&empty);
+
+        let mut merged = YamlHash::new();
+        let keys = ours_hash.keys()
+                            .chain(theirs_hash.keys().filter(|k| !ours_hash.contains_key(k)))
+                            .cloned()
+                            .collect::<Vec<Yaml>>();
+        for key in keys {
+            path.push(key.clone());
+            if let Some(value) = merge_values(path,
+                                              base_hash.get(&key),
+                                              ours_hash.get(&key),
+                                              theirs_hash.get(&key),
+                                              conflicts) {
+                merged.insert(key, value);
+            }
+            path.pop();
+        }
+        return Some(Yaml::Hash(merged));
+    }
+
+    conflicts.push(Conflict {
+        path: path.clone(),
+        base: base.cloned(),
+        ours: ours.cloned(),
+        theirs: theirs.cloned(),
+    });
+    ours.cloned()
+}
+
+fn set_path(doc: &mut Yaml, path: &[Yaml], value: Option<Yaml>) {
+    if let Some((key, remainder)) = path.split_first() {
+        if let Yaml::Hash(ref mut hash) = *doc {
+            if remainder.is_empty() {
+                match value {
+                    Some(value) => { hash.insert(key.clone(), value); },
+                    None => { hash.remove(key); }
+                }
+            } else if let Some(child) = hash.get_mut(key) {
+                set_path(child, remainder, value);
+            }
+        }
+    } else if let Some(value) = value {
+        *doc = value;
+    }
+}
+
+/// Fields of `merged` that differ from `ours`, `None` for removed ones.
+///
+/// Hashes with plain keys are descended into, anything else is replaced as a whole.
+fn collect_changes(path: &mut Vec<String>,
+                   ours: Option<&Yaml>,
+                   merged: Option<&Yaml>,
+                   changes: &mut Vec<(Vec<String>, Option<Yaml>)>) {
+    if ours == merged {
+        return;
+    }
+
+    if let (Some(&Yaml::Hash(ref ours_hash)), Some(&Yaml::Hash(ref merged_hash))) = (ours, merged) {
+        let keys = ours_hash.keys()
+                            .chain(merged_hash.keys().filter(|k| !ours_hash.contains_key(k)))
+                            .collect::<Vec<&Yaml>>();
+        if keys.iter().all(|key| plain_key(key).is_some()) {
+            for key in keys {
+                path.push(plain_key(key).unwrap_or_default());
+                collect_changes(path, ours_hash.get(key), merged_hash.get(key), changes);
+                path.pop();
+            }
+            return;
+        }
+    }
+
+    changes.push((path.clone(), merged.cloned()));
+}
+
+fn plain_key(key: &Yaml) -> Option<String> {
+    match *key {
+        Yaml::String(ref s) | Yaml::Real(ref s) => Some(s.to_owned()),
+        Yaml::Integer(i) => Some(i.to_string()),
+        Yaml::Boolean(b) => Some(b.to_string()),
+        _ => None
+    }
+}
+
+/// A scalar as it would be written after `key: `, anything else as a block starting with a newline.
+fn value_to_yaml(value: &Yaml) -> Result<String, failure::Error> {
+    use yaml_rust::emitter::YamlEmitter;
+    let mut buf = String::new();
+    {
+        let mut emitter = YamlEmitter::new(&mut buf);
+        emitter.dump(value)
+               .map_err(|e| failure::format_err!("{:?}", e))?;
+    }
+    let body = buf.trim_start_matches("---").trim();
+    Ok(match *value {
+        Yaml::Hash(ref hash) if !hash.is_empty() => format!("\n{}", body),
+        Yaml::Array(ref array) if !array.is_empty() => format!("\n{}", body),
+        _ => body.to_owned()
+    })
+}
+
+/// Equality that does not care about the order of keys.
+fn same(a: &Yaml, b: &Yaml) -> bool {
+    match (a, b) {
+        (&Yaml::Hash(ref a), &Yaml::Hash(ref b)) => {
+            a.len() == b.len() && a.iter().all(|(key, value)| b.get(key).map_or(false, |other| same(value, other)))
+        },
+        (&Yaml::Array(ref a), &Yaml::Array(ref b)) => {
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| same(a, b))
+        },
+        _ => a == b
+    }
+}
+
+fn key_to_string(key: &Yaml) -> String {
+    match *key {
+        Yaml::String(ref s) | Yaml::Real(ref s) => s.to_owned(),
+        Yaml::Integer(i) => i.to_string(),
+        // products are keyed by their catalogue entry
+        Yaml::Hash(_) => yaml::get_to_string(key, "name").unwrap_or_else(|| String::from("?")),
+        _ => format!("{:?}", key),
+    }
+}
+
+fn value_to_string(value: Option<&Yaml>) -> String {
+    match value {
+        None => String::from("(removed)"),
+        Some(&Yaml::String(ref s)) | Some(&Yaml::Real(ref s)) => s.to_owned(),
+        Some(&Yaml::Integer(i)) => i.to_string(),
+        Some(&Yaml::Boolean(b)) => b.to_string(),
+        Some(other) => format!("{:?}", other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::yaml::parse;
+
+    static BASE: &str = r#"
+    client:
+      email: old@example.com
+    invoice:
+      number: 41
+    hours:
+      salary: 8.0
+      caterers:
+        Maxime: 2
+    "#;
+
+    #[test]
+    fn merges_different_fields() {
+        let ours = BASE.replace("old@example.com", "new@example.com");
+        let theirs = BASE.replace("number: 41", "number: 42");
+
+        let merge = merge(&parse(BASE).unwrap(), &parse(&ours).unwrap(), &parse(&theirs).unwrap());
+
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(crate::util::yaml::get_str(&merge.merged, "client/email"), Some("new@example.com"));
+        assert_eq!(crate::util::yaml::get_int(&merge.merged, "invoice/number"), Some(42));
+    }
+
+    #[test]
+    fn merges_added_keys() {
+        let ours = BASE.replace("Maxime: 2", "Maxime: 2\n        Sebastian: 3");
+        let theirs = BASE.replace("salary: 8.0", "salary: 9.0");
+
+        let merge = merge(&parse(BASE).unwrap(), &parse(&ours).unwrap(), &parse(&theirs).unwrap());
+
+        assert!(merge.conflicts.is_empty());
+        assert_eq!(crate::util::yaml::get_int(&merge.merged, "hours/caterers/Sebastian"), Some(3));
+        assert_eq!(crate::util::yaml::get_f64(&merge.merged, "hours/salary"), Some(9.0));
+    }
+
+    #[test]
+    fn detects_and_resolves_conflicts() {
+        let ours = BASE.replace("number: 41", "number: 42");
+        let theirs = BASE.replace("number: 41", "number: 43");
+
+        let mut merge = merge(&parse(BASE).unwrap(), &parse(&ours).unwrap(), &parse(&theirs).unwrap());
+
+        assert_eq!(merge.conflicts.len(), 1);
+        let conflict = merge.conflicts[0].clone();
+        assert_eq!(conflict.path_string(), "invoice/number");
+
+        merge.resolve(&conflict, Side::Theirs);
+        assert_eq!(crate::util::yaml::get_int(&merge.merged, "invoice/number"), Some(43));
+
+        let reparsed = parse(&merge.to_yaml_string(&ours).unwrap()).unwrap();
+        assert_eq!(crate::util::yaml::get_int(&reparsed, "invoice/number"), Some(43));
+    }
+
+    #[test]
+    fn keeps_comments_and_anchors() {
+        static DOC: &str = "# the summer party\nclient:\n  email: old@example.com # ask first\ninvoice:\n  number: 41\nhours:\n  salary: &salary 8.0\n  caterers:\n    Maxime: 2\n    Sebastian: 1\n  drivers_salary: *salary\n";
+        let ours = DOC.replace("old@example.com", "new@example.com");
+        let theirs = DOC.replace("number: 41", "number: 42").replace("    Sebastian: 1\n", "");
+
+        let merge = merge(&parse(DOC).unwrap(), &parse(&ours).unwrap(), &parse(&theirs).unwrap());
+        let merged = merge.to_yaml_string(&ours).unwrap();
+
+        assert_eq!(merged, "# the summer party\nclient:\n  email: new@example.com # ask first\ninvoice:\n  number: 42\nhours:\n  salary: &salary 8.0\n  caterers:\n    Maxime: 2\n  drivers_salary: *salary\n");
+    }
+}
@@ -8,18 +8,25 @@
 //! # root dir
 //! ├── working
 //! │   └── Project1
-//! │       └── Project1.yml
+//! │       ├── Project1.yml
+//! │       └── attachments
+//! │           └── receipt.pdf
 //! ├── archive
 //! │   ├── 2013
 //! │   └── 2014
 //! │       └── R036_Project3
 //! │           ├── Project3.yml
 //! │           └── R036 Project3 2014-10-08.tex
+//! ├── trash
+//! │   └── 20141009120000_Project4
 //! ...
 //! ```
 //!
+//! Archiving, unarchiving and deleting are journaled, see [`transaction`](transaction/index.html).
+//!
 
 use rayon::prelude::*;
+use chrono::Utc;
 use dirs::home_dir;
 use log::{info, debug, trace, error, warn};
 use failure::{bail, ensure, Error};
@@ -28,6 +35,7 @@ use std::fs;
 use std::env::{self, current_dir};
 use std::path::{Path, PathBuf};
 use std::marker::PhantomData;
+use std::sync::Arc;
 
 /// Year = `i32`
 pub type Year =  i32;
@@ -38,6 +46,11 @@ pub type Year =  i32;
 mod project_list;
 pub use self::project_list::{ProjectList, ProjectsByYear, Projects};
 pub mod repo;
+pub mod merge;
+pub mod transaction;
+use self::transaction::Transaction;
+pub mod backend;
+pub use self::backend::{Backend, FsBackend, MemoryBackend, FileDbBackend};
 pub mod error;
 pub use self::error::StorageError;
 pub mod storable;
@@ -54,7 +67,9 @@ pub use self::storable::*;
 /// * listing templates
 /// * archiving and unarchiving projects
 /// * git interaction
-pub struct Storage<L:Storable> {
+///
+/// All file access goes through a [`Backend`](backend/trait.Backend.html), the filesystem by default.
+pub struct Storage<L:Storable, B:Backend = FsBackend> {
     /// Root of the entire Structure.
     root:  PathBuf,
 
@@ -72,7 +87,9 @@ pub struct Storage<L:Storable> {
 
     project_type: PhantomData<L>,
 
-    repository: Option<Repository>
+    repository: Option<Repository>,
+
+    backend: Arc<B>,
 }
 
 /// Used to identify what directory you are talking about.
@@ -214,10 +231,27 @@ use linked_hash_map::LinkedHashMap;
 use slug;
 fn slugify(string:&str) -> String{ slug::slugify(string) }
 
-impl<L:Storable> Storage<L> {
+impl<L:Storable> Storage<L, FsBackend> {
 
     /// Inits storage, does not check existence, yet. TODO
     pub fn try_new<P: AsRef<Path>>(root:P, working:&str, archive:&str, template:&str) -> Result<Self, Error> {
+        Self::try_new_with_backend(root, working, archive, template, FsBackend)
+    }
+
+    /// Inits storage with git capabilities.
+    pub fn try_new_with_git<P: AsRef<Path>>(root:P, working:&str, archive:&str, template:&str) -> Result<Self, Error> {
+        trace!("initializing storage, with git");
+        Ok( Storage{
+            repository: Some(Repository::try_new(root.as_ref())?),
+            .. Self::try_new(root, working, archive, template)?
+        })
+    }
+}
+
+impl<L:Storable, B:Backend> Storage<L, B> {
+
+    /// Inits storage on top of any `Backend`, does not check existence, yet.
+    pub fn try_new_with_backend<P: AsRef<Path>>(root:P, working:&str, archive:&str, template:&str, backend: B) -> Result<Self, Error> {
         trace!("initializing storage, root: {}", root.as_ref().display());
         let root = root.as_ref();
         if root.is_absolute(){
@@ -229,21 +263,13 @@ impl<L:Storable> Storage<L> {
                 extras:    root.join("extras"),
                 project_type: PhantomData,
                 repository: None,
+                backend: Arc::new(backend),
             })
         } else {
             bail!(StorageError::StoragePathNotAbsolute)
         }
     }
 
-    /// Inits storage with git capabilities.
-    pub fn try_new_with_git<P: AsRef<Path>>(root:P, working:&str, archive:&str, template:&str) -> Result<Self, Error> {
-        trace!("initializing storage, with git");
-        Ok( Storage{
-            repository: Some(Repository::try_new(root.as_ref())?),
-            .. Self::try_new(root, working, archive, template)?
-        })
-    }
-
     /// Checks whether the folder structure is as it's supposed to be.
     pub fn health_check(&self) -> Result<(), Error> {
         let r = self.root_dir();
@@ -251,11 +277,12 @@ impl<L:Storable> Storage<L> {
         let a = self.archive_dir();
         let t = self.templates_dir();
 
-        if r.exists() && w.exists() && a.exists() && t.exists() {
+        let exists = |path| self.backend.exists(path);
+        if exists(r) && exists(w) && exists(a) && exists(t) {
             Ok(())
         } else {
             for f in &[r,w,a,t]{
-                if !f.exists() { warn!("{} does not exist", f.display())}
+                if !exists(f) { warn!("{} does not exist", f.display())}
             }
             bail!(StorageError::InvalidDirStructure)
         }
@@ -286,6 +313,16 @@ impl<L:Storable> Storage<L> {
         self.extras.as_ref()
     }
 
+    /// Where deleted projects are moved to.
+    pub fn trash_dir(&self) -> PathBuf {
+        self.root.join(transaction::TRASH_DIR)
+    }
+
+    /// Getter for Storage::backend.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
     /// Getter for Storage::templates.
     pub fn repository(&self) -> Option<&Repository> {
         self.repository.as_ref()
@@ -321,10 +358,10 @@ impl<L:Storable> Storage<L> {
         trace!("creating storage directories");
         ensure!(self.root_dir().is_absolute(), StorageError::StoragePathNotAbsolute);
 
-        if !self.root_dir().exists()  { fs::create_dir(&self.root_dir())?;  }
-        if !self.working_dir().exists()  { fs::create_dir(&self.working_dir())?;  }
-        if !self.archive_dir().exists()  { fs::create_dir(&self.archive_dir())?;  }
-        if !self.templates_dir().exists() { fs::create_dir(&self.templates_dir())?; }
+        for dir in &[self.root_dir(), self.working_dir(), self.archive_dir(), self.templates_dir()] {
+            if !self.backend.exists(dir) { self.backend.create_dir(dir)?; }
+        }
+        transaction::ignore_in_git(&*self.backend, self.root_dir())?;
 
         Ok(())
     }
@@ -339,11 +376,11 @@ impl<L:Storable> Storage<L> {
     ///</pre>
     pub fn create_archive(&self, year:Year) -> Result<PathBuf, Error> {
         trace!("creating archive directory: {}", year);
-        assert!(self.archive_dir().exists());
+        assert!(self.backend.exists(self.archive_dir()));
         let archive = &self.archive_dir().join(year.to_string());
 
-        if self.archive_dir().exists() && !archive.exists() {
-            fs::create_dir(archive)?;
+        if !self.backend.exists(archive) {
+            self.backend.create_dir(archive)?;
         }
         Ok(archive.to_owned())
     }
@@ -351,7 +388,7 @@ impl<L:Storable> Storage<L> {
     /// Produces a list of files in the `extras_dir()`
     pub fn list_extra_files(&self) -> Result<Vec<PathBuf>, Error> {
         trace!("listing extra files");
-        list_path_content(&self.extras_dir())
+        self.backend.list(&self.extras_dir())
     }
 
     /// Returns the Path to the extra file by the given name, maybe.
@@ -368,7 +405,7 @@ impl<L:Storable> Storage<L> {
         let template_file_extension = crate::CONFIG.get_str("extensions/project_template");
         trace!("listing template files (.{})", template_file_extension);
         let template_files =
-        list_path_content(&self.templates_dir())?
+        self.backend.list(&self.templates_dir())?
             .into_iter()
             .filter(|p|p.extension()
                         .unwrap_or_else(|| OsStr::new("")) == OsStr::new(template_file_extension)
@@ -404,7 +441,7 @@ impl<L:Storable> Storage<L> {
     /// an invoice number etc.
     pub fn list_archives(&self) -> Result<Vec<PathBuf>, Error> {
         trace!("listing archives files");
-        list_path_content(self.archive_dir())
+        self.backend.list(self.archive_dir())
     }
 
     /// Produces a list of years for which there is an archive.
@@ -428,13 +465,13 @@ impl<L:Storable> Storage<L> {
                name = project_name,
                tmpl = template_name
                );
-        if !self.working_dir().exists(){
+        if !self.backend.exists(self.working_dir()){
             error!("working directory does not exist");
             bail!(StorageError::NoWorkingDir)
         };
         let slugged_name = slugify(project_name);
         let project_dir  = self.working_dir().join(&slugged_name);
-        if project_dir.exists() {
+        if self.backend.exists(&project_dir) {
             error!("project directory already exists");
             bail!(StorageError::ProjectDirExists);
         }
@@ -445,16 +482,18 @@ impl<L:Storable> Storage<L> {
             .join(&(slugged_name + "." + &L::file_extension()));
 
         let template_path = self.get_template_file(template_name)?;
+        let template_content = self.backend.read(&template_path)?;
 
         trace!("creating project using concrete Project implementation of from_template");
-        let mut project = L::from_template(&project_name, &template_path, &fill_data)?;
+        let mut project = L::from_template(&project_name, &template_path, &template_content, &fill_data)?;
 
         // TODO: Hand of creation entirely to Storable implementation
         //      Storage it self should only concern itself with Project folders!
-        fs::create_dir(&project_dir)?;
-        fs::copy(project.file(), &target_file)?;
+        self.backend.create_dir(&project_dir)?;
+        self.backend.write(&target_file, &fs::read_to_string(project.file())?)?;
         trace!("copied project file successfully");
         project.set_file(&target_file);
+        project.set_backend(self.backend.clone());
 
         Ok(project.storable)
     }
@@ -478,7 +517,7 @@ impl<L:Storable> Storage<L> {
         let target = archive.join(&name_in_archive);
         trace!(" moving file into {:?}", target);
 
-        fs::rename(&project_folder, &target)?;
+        self.backend.rename(&project_folder, &target)?;
 
         Ok(target)
     }
@@ -495,6 +534,18 @@ impl<L:Storable> Storage<L> {
     // TODO: write extra tests
     // TODO: make year optional and default to project.year()
     pub fn archive_project(&self, project:&L, year:Year) -> Result<Vec<PathBuf>, Error> {
+        let mut transaction = self.begin_transaction("archive");
+        let result = self.archive_project_within(&mut transaction, project, year);
+        let moved_files = transaction.finish(result)?;
+
+        if let Some(repo) = self.repository() {
+            repo.add(&moved_files);
+        }
+
+        Ok(moved_files)
+    }
+
+    fn archive_project_within(&self, transaction: &mut Transaction<'_, B>, project:&L, year:Year) -> Result<Vec<PathBuf>, Error> {
         debug!("trying archiving {:?} into {:?}", project.short_desc(), year);
 
         let mut moved_files = Vec::new();
@@ -508,22 +559,19 @@ impl<L:Storable> Storage<L> {
         let project_folder = project.dir();
         let target = archive.join(&name_in_archive);
 
-        fs::rename(&project_folder, &target)?;
+        transaction.rename(&project_folder, &target)?;
         info!("successfully archived {:?} to {:?}", project.short_desc() ,target);
 
         moved_files.push(project.dir());
         moved_files.push(target);
 
-        if let Some(repo) = self.repository() {
-            repo.add(&moved_files);
-        }
-
         Ok(moved_files)
     }
 
 
     /// Moves projects found through `search_terms` from the `Working` directory to the `Archive`/`year` directory.
     ///
+    /// This happens in one transaction, if one project can't be moved all others are moved back.
     /// Returns list of old and new paths.
     pub fn archive_projects_if<F>(&self, search_terms:&[&str], manual_year:Option<i32>, confirm:F) -> Result<Vec<PathBuf>, Error>
         where F: Fn()->bool
@@ -533,22 +581,26 @@ impl<L:Storable> Storage<L> {
 
         ensure!(!projects.is_empty(), StorageError:: ProjectDoesNotExist);
 
-        let mut moved_files = Vec::new();
-
-        for project in projects {
-            if force {warn!("you are using --force")};
-            if project.is_ready_for_archive() || force {
-                info!("project {:?} is ready to be archived", project.short_desc());
-                let year = manual_year.or_else(|| project.year()).unwrap();
-                info!("archiving {} ({})",  project.ident(), project.year().unwrap());
-                let mut archive_target = self.archive_project(&project, year)?;
-                moved_files.push(project.dir());
-                moved_files.append(&mut archive_target);
-            }
-            else {
-                warn!("project {:?} is not ready to be archived", project.short_desc());
-            }
-        };
+        let mut transaction = self.begin_transaction("archive");
+        let result = projects.iter()
+            .map(|project| -> Result<Vec<PathBuf>, Error> {
+                if force {warn!("you are using --force")};
+                if project.is_ready_for_archive() || force {
+                    info!("project {:?} is ready to be archived", project.short_desc());
+                    let year = manual_year.or_else(|| project.year()).unwrap();
+                    info!("archiving {} ({})",  project.ident(), project.year().unwrap());
+                    let mut moved_files = vec![project.dir()];
+                    moved_files.append(&mut self.archive_project_within(&mut transaction, project, year)?);
+                    Ok(moved_files)
+                }
+                else {
+                    warn!("project {:?} is not ready to be archived", project.short_desc());
+                    Ok(Vec::new())
+                }
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .map(|moved| moved.concat());
+        let moved_files = transaction.finish(result)?;
 
         if let Some(repo) = self.repository() {
             repo.add(&moved_files);
@@ -557,11 +609,27 @@ impl<L:Storable> Storage<L> {
         Ok(moved_files)
     }
 
+    /// Moves the project folder into the trash if the passed in closure returns `true`.
+    ///
+    /// Use `undo()` to get it back.
     pub fn delete_project_if<F>(&self, project:&L, confirmed:F) -> Result<(), Error>
         where F: Fn() -> bool
     {
+        if !confirmed() {
+            return Ok(());
+        }
+
         debug!("deleting {}", project.dir().display());
-        project.delete_project_dir_if(confirmed)?;
+        let trash = self.trash_dir();
+        if !self.backend.exists(&trash) {
+            self.backend.create_dir(&trash)?;
+        }
+
+        let target = trash.join(format!("{}_{}", Utc::now().format("%Y%m%d%H%M%S"), project.ident()));
+        let mut transaction = self.begin_transaction("delete");
+        let result = transaction.rename(&project.dir(), &target);
+        transaction.finish(result)?;
+
         if let Some(ref repo) = self.repository {
             if !repo.add(&[project.dir()]).success() {
                 debug!("adding {} to git", project.dir().display());
@@ -574,17 +642,21 @@ impl<L:Storable> Storage<L> {
 
     /// Moves projects found through `search_terms` from the `year` back to the `Working` directory.
     ///
+    /// This happens in one transaction, if one project can't be moved all others are moved back.
     /// Returns list of old and new paths.
     pub fn unarchive_projects(&self, year:i32, search_terms:&[&str]) -> Result<Vec<(PathBuf)>, Error> {
         let projects = self.search_projects_any(StorageDir::Archive(year), search_terms)?;
 
-        let mut moved_files = Vec::new();
-        for project in projects {
-            println!("unarchiving {:?}", project.short_desc());
-            let unarchive_target = self.unarchive_project(&project).unwrap();
-            moved_files.push(project.dir());
-            moved_files.push(unarchive_target);
-        };
+        let mut transaction = self.begin_transaction("unarchive");
+        let result = projects.iter()
+            .map(|project| -> Result<Vec<PathBuf>, Error> {
+                println!("unarchiving {:?}", project.short_desc());
+                let unarchive_target = self.unarchive_project_dir_within(&mut transaction, &project.dir())?;
+                Ok(vec![project.dir(), unarchive_target])
+            })
+            .collect::<Result<Vec<_>, Error>>()
+            .map(|moved| moved.concat());
+        let moved_files = transaction.finish(result)?;
 
         if let Some(repo) = self.repository() {
             repo.add(&moved_files);
@@ -600,6 +672,12 @@ impl<L:Storable> Storage<L> {
 
     /// Moves a project folder from `/working` dir to `/archive/$year`.
     pub fn unarchive_project_dir(&self, archived_dir:&Path) -> Result<PathBuf, Error> {
+        let mut transaction = self.begin_transaction("unarchive");
+        let result = self.unarchive_project_dir_within(&mut transaction, archived_dir);
+        transaction.finish(result)
+    }
+
+    fn unarchive_project_dir_within(&self, transaction: &mut Transaction<'_, B>, archived_dir:&Path) -> Result<PathBuf, Error> {
         debug!("trying unarchiving {:?}", archived_dir);
 
         // has to be in archive_dir
@@ -616,11 +694,11 @@ impl<L:Storable> Storage<L> {
 
         let name = self.get_project_name(archived_dir)?;
         let target = self.working_dir().join(&name);
-        ensure!(!target.exists(), StorageError::ProjectFileExists);
+        ensure!(!self.backend.exists(&target), StorageError::ProjectFileExists);
         info!("unarchiving project from {:?} to {:?}", archived_dir, target);
 
         if child_of_archive && !archive_itself && parent_is_num{
-            fs::rename(&archived_dir, &target)?;
+            transaction.rename(&archived_dir, &target)?;
         } else {
             error!("moving out of archive failed");
             bail!(StorageError::InvalidDirStructure);
@@ -629,6 +707,33 @@ impl<L:Storable> Storage<L> {
         Ok(target)
     }
 
+    fn begin_transaction(&self, operation: &str) -> Transaction<'_, B> {
+        Transaction::begin(&self.backend, self.root_dir(), operation)
+    }
+
+    /// Reverts the last archive, unarchive or delete.
+    ///
+    /// Returns the paths that were moved back, empty if there was nothing to undo.
+    pub fn undo(&self) -> Result<Vec<PathBuf>, Error> {
+        let moved_files = match transaction::undo(&*self.backend, self.root_dir())? {
+            Some((operation, moves)) => {
+                info!("reverted {}", operation);
+                moves.into_iter()
+                     .flat_map(|m| vec![m.from, m.to])
+                     .collect::<Vec<_>>()
+            },
+            None => Vec::new()
+        };
+
+        if let Some(repo) = self.repository() {
+            if !moved_files.is_empty() {
+                repo.add(&moved_files);
+            }
+        }
+
+        Ok(moved_files)
+    }
+
     /// Matches StorageDir's content against a term and returns matching project files.
     ///
     /// This only searches by name
@@ -684,7 +789,7 @@ impl<L:Storable> Storage<L> {
             StorageDir::Archive(year) => self.get_project_dir_from_archive(name, year),
             _ => bail!(StorageError::BadChoice)
         }{
-            if path.exists(){
+            if self.backend.exists(&path){
                 return Ok(path);
             }
         }
@@ -695,11 +800,7 @@ impl<L:Storable> Storage<L> {
     ///
     /// This is the first file with the `super::PROJECT_FILE_EXTENSION` in the folder
     pub fn get_project_file(&self, directory:&Path) -> Result<PathBuf, Error> {
-        trace!("getting project file from {:?}", directory);
-        list_path_content(directory)?.iter()
-            .filter(|f|f.extension().unwrap_or_else(||OsStr::new("")) == L::file_extension().as_str())
-            .nth(0).map(ToOwned::to_owned)
-            .ok_or_else(|| StorageError::ProjectDoesNotExist.into())
+        find_project_file::<L, B>(&self.backend, directory)
     }
 
     fn get_project_name(&self, directory:&Path) -> Result<String, Error> {
@@ -723,18 +824,18 @@ impl<L:Storable> Storage<L> {
     pub fn list_project_folders(&self, directory:StorageDir) -> Result<Vec<PathBuf>, Error> {
         trace!("listing project folders in {:?}-directory", directory);
         match directory{
-            StorageDir::Working       => list_path_content(self.working_dir()),
+            StorageDir::Working       => self.backend.list(self.working_dir()),
             StorageDir::Archive(year) => {
                 let path = self.archive_dir().join(year.to_string());
-                let list = list_path_content(&path).unwrap_or_else(|_| Vec::new());
+                let list = self.backend.list(&path).unwrap_or_else(|_| Vec::new());
                 Ok(list)
             },
             StorageDir::All           => {
                 let mut all:Vec<PathBuf> = Vec::new();
                 for year in self.list_years()? {
-                    all.append(&mut list_path_content(&self.archive_dir().join(year.to_string()))?);
+                    all.append(&mut self.backend.list(&self.archive_dir().join(year.to_string()))?);
                 }
-                all.append(&mut list_path_content(&self.working_dir())?);
+                all.append(&mut self.backend.list(&self.working_dir())?);
                 Ok(all)
             },
             _ => bail!(StorageError::BadChoice)
@@ -796,15 +897,16 @@ impl<L:Storable> Storage<L> {
 
     fn open_paths(&self, paths: &[PathBuf]) -> ProjectList<L> {
         trace!("open_paths({:?})", paths);
+        let backend = &self.backend;
+        let archive_dir = self.archive_dir();
         let mut projects = paths.par_iter()
-            .filter(|path|
-                if let Ok(meta) = path.metadata() {
-                    meta.is_dir()
-                } else {
-                    false
-                }
-            )
-            .filter_map(|path| Self::open_project(path).ok())
+            .filter(|path| backend.is_dir(path))
+            .filter_map(|path| open_project::<L, B>(&**backend, path).ok())
+            .map(|mut project| {
+                project.set_archived(project.dir().starts_with(archive_dir));
+                project.set_backend(backend.clone());
+                project
+            })
             .collect::<Vec<L>>();
 
         if cfg!(feature="git_statuses") {
@@ -865,17 +967,30 @@ impl<L:Storable> Storage<L> {
         })
     }
 
-    fn open_project(path: &PathBuf) -> Result<L, Error> {
-        let project = L::open_folder(path);
-        if let Err(ref err) = project {
-            warn!("{}", err);
-        }
-        project
-    }
+}
 
+/// Locates the project file inside a folder.
+///
+/// This is the first file with the `Storable::file_extension()` in the folder
+fn find_project_file<L:Storable, B:Backend>(backend: &B, directory:&Path) -> Result<PathBuf, Error> {
+    trace!("getting project file from {:?}", directory);
+    backend.list(directory)?.iter()
+        .filter(|f|f.extension().unwrap_or_else(||OsStr::new("")) == L::file_extension().as_str())
+        .nth(0).map(ToOwned::to_owned)
+        .ok_or_else(|| StorageError::ProjectDoesNotExist.into())
+}
+
+fn open_project<L:Storable, B:Backend>(backend: &B, path: &Path) -> Result<L, Error> {
+    let project = find_project_file::<L, B>(backend, path)
+        .map_err(|_| Error::from(StorageError::NoProjectFile(path.to_owned())))
+        .and_then(|file| L::open_content(&file, &backend.read(&file)?));
+    if let Err(ref err) = project {
+        warn!("{}", err);
+    }
+    project
 }
 
-impl<P:Storable> fmt::Debug for Storage<P>{
+impl<P:Storable, B:Backend> fmt::Debug for Storage<P, B>{
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result
     {
         write!(f, "Storage: storage  = {storage:?}
@@ -94,6 +94,18 @@ impl From<git2::Status> for GitStatus{
     }
 }
 
+/// A commit that touched a file, see `Repository::file_history()`.
+#[derive(Debug, Clone)]
+pub struct Revision {
+    /// Full commit hash
+    pub commit: String,
+    pub author: String,
+    /// Author date in strict ISO 8601
+    pub date: String,
+    /// Path of the file in that commit, relative to the repository
+    pub path: PathBuf,
+}
+
 /// Convenience Wrapper for `git2::Repository`
 #[cfg(feature="git_statuses")]
 pub struct Repository{
@@ -190,6 +202,26 @@ impl Repository {
             .unwrap_or_else(|e| { panic!("failed to execute process: {}", e) })
     }
 
+    /// Like `execute_git()` but captures `stdout` instead of passing it through.
+    fn git_output(&self, command:&str, args:&[&str], paths: &[PathBuf]) -> Option<String> {
+        let gitdir  = self.workdir.join(".git");
+        let output = Command::new("git")
+            .args(&["--work-tree", self.workdir.to_str().unwrap()])
+            .args(&["--git-dir",   gitdir.to_str().unwrap()])
+            .arg(command)
+            .args(args)
+            .args(paths)
+            .output()
+            .unwrap_or_else(|e| { panic!("failed to execute process: {}", e) });
+
+        if output.status.success() {
+            String::from_utf8(output.stdout).ok()
+        } else {
+            debug!("git {} failed: {}", command, String::from_utf8_lossy(&output.stderr));
+            None
+        }
+    }
+
     pub fn add(&self, paths:&[PathBuf]) -> ExitStatus {
         info!("adding to git: {:?}", paths);
         self.execute_git("add", &[], paths)
@@ -242,6 +274,73 @@ impl Repository {
         self.execute_git("pull", &["origin", "master", "--rebase"], &[])
     }
 
+    /// Pulls without opening an editor for the merge commit.
+    pub fn pull_no_edit(&self) -> ExitStatus {
+        self.execute_git("pull", &["origin", "master", "--no-rebase", "--no-edit"], &[])
+    }
+
+    /// Commits whatever is staged with the given message, without opening an editor.
+    pub fn commit_with_message(&self, message: &str) -> ExitStatus {
+        self.execute_git("commit", &["--message", message], &[])
+    }
+
+    /// Commits only `paths` with the given message, without opening an editor.
+    pub fn commit_paths_with_message(&self, paths: &[PathBuf], message: &str) -> ExitStatus {
+        self.execute_git("commit", &["--message", message, "--"], paths)
+    }
+
+    /// Lists files that are currently in an unmerged state.
+    pub fn conflicted_files(&self) -> Vec<PathBuf> {
+        self.git_output("diff", &["--name-only", "--diff-filter=U"], &[])
+            .map(|out| out.lines()
+                          .filter(|l| !l.is_empty())
+                          .map(|l| self.workdir.join(l))
+                          .collect())
+            .unwrap_or_else(Vec::new)
+    }
+
+    /// Content of a file in one of the stages of a conflicted merge.
+    ///
+    /// Stage `1` is the common ancestor, `2` is ours and `3` is theirs.
+    /// Returns `None` if the file does not exist in that stage.
+    pub fn show_stage(&self, stage: u8, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.workdir).unwrap_or(path);
+        let object = format!(":{}:{}", stage, relative.display());
+        self.git_output("show", &[&object], &[])
+    }
+
+    /// Lists all commits that touched `path`, oldest first.
+    ///
+    /// Follows renames, so history from before `archive` moved the file is included.
+    pub fn file_history(&self, path: &Path) -> Vec<Revision> {
+        let output = match self.git_output("log", &["--follow", "--name-only", "--format=%x00%H%x09%an%x09%aI", "--"], &[path.to_owned()]) {
+            Some(output) => output,
+            None => return Vec::new()
+        };
+
+        let mut revisions = output.split('\0')
+            .filter_map(|chunk| {
+                let mut lines = chunk.lines().filter(|l| !l.is_empty());
+                let mut header = lines.next()?.splitn(3, '\t');
+                let path = lines.next()?;
+                Some(Revision {
+                    commit: header.next()?.to_owned(),
+                    author: header.next()?.to_owned(),
+                    date:   header.next()?.to_owned(),
+                    path:   PathBuf::from(path),
+                })
+            })
+            .collect::<Vec<_>>();
+        revisions.reverse();
+        revisions
+    }
+
+    /// Content of a file as it was in `revision`.
+    pub fn show_revision(&self, revision: &Revision) -> Option<String> {
+        let object = format!("{}:{}", revision.commit, revision.path.display());
+        self.git_output("show", &[&object], &[])
+    }
+
     pub fn remote(&self) -> ExitStatus {
         self.execute_git("remote", &[], &[])
     }
@@ -5,12 +5,14 @@ use std::{fs,io};
 use std::ops::{Deref, DerefMut};
 use std::path::{Path, PathBuf};
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use chrono::{Date, Utc, Datelike};
 use failure::Error;
 use tempdir::TempDir;
 use log::debug;
 
+use super::Backend;
 use super::repo::GitStatus;
 
 pub type FilePath = Path;
@@ -25,8 +27,13 @@ pub trait Storable: Send+Sync {
     fn open_folder(_: &FolderPath) -> Result<Self, Error> where Self: Sized;
     fn open_file(_: &FilePath) -> Result<Self, Error> where Self: Sized;
 
-    /// creates in tempfile
-    fn from_template(project_name: &str, template: &Path, data: &HashMap<&str, String>) -> Result<StorableAndTempDir<Self>, Error> where Self: Sized;
+    /// opens a project file from content that was read through a storage `Backend`
+    fn open_content(path: &FilePath, _content: &str) -> Result<Self, Error> where Self: Sized {
+        Self::open_file(path)
+    }
+
+    /// creates in tempfile, `template_content` is the content of the file at `template`
+    fn from_template(project_name: &str, template: &Path, template_content: &str, data: &HashMap<&str, String>) -> Result<StorableAndTempDir<Self>, Error> where Self: Sized;
 
     /// For file names
     fn ident(&self) -> String{ self.dir().file_stem().and_then(std::ffi::OsStr::to_str).unwrap().to_owned() }
@@ -36,6 +43,7 @@ pub trait Storable: Send+Sync {
     fn year(&self) -> Option<i32>{ self.modified_date().map(|d|d.year()) }
 
     /// Deletes the project if the passed in closure returns `true`
+    #[deprecated(note="use `Storage::delete_project_if()`, it moves the project into the trash and can be undone")]
     fn delete_project_dir_if(&self, confirmed: impl Fn()->bool) -> io::Result<()> {
         let folder = self.dir();
         if confirmed(){
@@ -53,6 +61,12 @@ pub trait Storable: Send+Sync {
     /// Sets the project File
     fn set_file(&mut self, new_file:&Path);
 
+    /// Tell a project whether it was opened from the archive
+    fn set_archived(&mut self, _: bool){}
+
+    /// Tell a project which `Backend` its files are kept in
+    fn set_backend(&mut self, _: Arc<dyn Backend>){}
+
     /// Tell a project its own git status after opening
     ///
     /// This depends on the feature `git_statuses`
@@ -6,7 +6,6 @@ use chrono::prelude::*;
 use tempdir::TempDir;
 use maplit::hashmap;
 
-use crate::util;
 use super::*;
 
 // TODO: add tests for file or directories in return values
@@ -14,21 +13,23 @@ use super::*;
 #[allow(dead_code)]
 pub struct TestProject {
     file_path: PathBuf,
+    archived: bool,
 }
 
 impl Storable for TestProject{
     // creates in tempfile
-    fn from_template(project_name: &str, template: &Path, _fill: &HashMap<&str, String>) -> Result<StorableAndTempDir<Self>, Error> where Self: Sized {
+    fn from_template(project_name: &str, _template: &Path, template_content: &str, _fill: &HashMap<&str, String>) -> Result<StorableAndTempDir<Self>, Error> where Self: Sized {
         // generates a temp file
         let temp_dir  = TempDir::new_in("./target/debug/build/",&project_name).unwrap();
         let temp_file = temp_dir.path().join(project_name);
 
         // just copy over template
-        fs::copy(template, &temp_file)?;
+        fs::write(&temp_file, template_content)?;
 
         // project now lives in the temp_file
         let project = TestProject {
             file_path: temp_file,
+            archived: false,
         };
 
         Ok(StorableAndTempDir {
@@ -41,6 +42,7 @@ impl Storable for TestProject{
     fn modified_date(&self) -> Option<Date<Utc>>{ Some(Utc::today()) }
     fn file(&self) -> PathBuf{ self.file_path.to_owned() }
     fn set_file(&mut self, new_file:&Path){ self.file_path = new_file.to_owned(); }
+    fn set_archived(&mut self, archived: bool){ self.archived = archived; }
     fn index(&self) -> Option<String>{ Some("ZZ99".into()) }
     fn prefix(&self) -> Option<String>{ self.index() }
 
@@ -50,7 +52,8 @@ impl Storable for TestProject{
 
     fn open_file(path:&Path) -> Result<Self, Error>{
         Ok(TestProject{
-            file_path: PathBuf::from(path)
+            file_path: PathBuf::from(path),
+            archived: false,
         })
     }
     fn matches_filter(&self, _key: &str, _val: &str) -> bool {false}
@@ -66,76 +69,112 @@ const TEST_PROJECTS:[&str; 4] = [
     "foobar", "ich schreibe viel zu längliche projektnamen!",
 ];
 
+/// Every test below runs once for each of these.
+macro_rules! test_all_backends {
+    ($($test:ident),*) => {
+        mod fs_backend {
+            use super::*;
+            fn setup() -> (TempDir, PathBuf, Storage<TestProject>) {
+                let dir = TempDir::new_in(Path::new("."),"storage_test").unwrap();
+                let storage_path = dir.path().join("storage_test");
+                let storage = Storage::try_new(&storage_path, "working", "archive", "templates").unwrap();
+                (dir, storage_path, storage)
+            }
+            $( #[test] fn $test() { super::$test(setup()) } )*
+        }
 
-fn setup() -> (TempDir, PathBuf, Storage<TestProject>) {
-    let dir = TempDir::new_in(Path::new("."),"storage_test").unwrap();
-    let storage_path = dir.path().join("storage_test");
-    let storage = Storage::try_new(&storage_path, "working", "archive", "templates").unwrap();
-    (dir, storage_path, storage)
+        mod memory_backend {
+            use super::*;
+            fn setup() -> (TempDir, PathBuf, Storage<TestProject, MemoryBackend>) {
+                let dir = TempDir::new_in(Path::new("."),"storage_test").unwrap();
+                let storage_path = dir.path().join("storage_test");
+                let storage = Storage::try_new_with_backend(&storage_path, "working", "archive", "templates", MemoryBackend::new()).unwrap();
+                (dir, storage_path, storage)
+            }
+            $( #[test] fn $test() { super::$test(setup()) } )*
+        }
+
+        mod file_db_backend {
+            use super::*;
+            fn setup() -> (TempDir, PathBuf, Storage<TestProject, FileDbBackend>) {
+                let dir = TempDir::new_in(Path::new("."),"storage_test").unwrap();
+                let storage_path = dir.path().join("storage_test");
+                let backend = FileDbBackend::open(dir.path().join("storage.db")).unwrap();
+                let storage = Storage::try_new_with_backend(&storage_path, "working", "archive", "templates", backend).unwrap();
+                (dir, storage_path, storage)
+            }
+            $( #[test] fn $test() { super::$test(setup()) } )*
+        }
+    }
 }
 
-fn assert_existence(storage_path:&Path) {
-    assert!(storage_path.exists()
-            &&  storage_path.join("working").exists()
-            &&  storage_path.join("archive").exists()
-            &&  storage_path.join("templates").exists());
+test_all_backends!(
+    create_dirs,
+    list_template_files,
+    create_archive,
+    list_archives,
+    create_project,
+    archive_project_by_name,
+    archive_project,
+    unarchive_project_dir,
+    undo_archive_project,
+    open_archived_projects,
+    delete_project_into_trash,
+    transaction_rollback
+);
+
+type Setup<B> = (TempDir, PathBuf, Storage<TestProject, B>);
+
+fn assert_existence<B: Backend>(storage_path:&Path, storage: &Storage<TestProject, B>) {
+    let backend = storage.backend();
+    assert!(backend.exists(storage_path)
+            &&  backend.exists(&storage_path.join("working"))
+            &&  backend.exists(&storage_path.join("archive"))
+            &&  backend.exists(&storage_path.join("templates")));
 }
 
-fn copy_template(target:PathBuf) {
-    fs::copy("./templates/default.tyml", target.join("template1.tyml")).unwrap();
-    fs::copy("./templates/default.tyml", target.join("template2.tyml")).unwrap();
+fn copy_template<B: Backend>(target:PathBuf, storage: &Storage<TestProject, B>) {
+    let template = fs::read_to_string("./templates/default.tyml").unwrap();
+    storage.backend().write(&target.join("template1.tyml"), &template).unwrap();
+    storage.backend().write(&target.join("template2.tyml"), &template).unwrap();
 }
 
-#[test]
-fn create_dirs() {
-    let (dir , storage_path, storage) = setup();
+fn create_dirs<B: Backend>((_dir, storage_path, storage): Setup<B>) {
     storage.create_dirs().unwrap();
-    assert_existence(&storage_path);
+    assert_existence(&storage_path, &storage);
 
     // calling it again does not cause problems
     assert!(storage.create_dirs().is_ok());
-    assert_existence(&storage_path);
-
-    util::ls(&dir.path().to_string_lossy());
+    assert_existence(&storage_path, &storage);
 }
 
-#[test]
-fn list_template_files(){
-    let (_dir , storage_path, storage) = setup();
+fn list_template_files<B: Backend>((_dir, storage_path, storage): Setup<B>) {
     storage.create_dirs().unwrap();
-    assert_existence(&storage_path);
+    assert_existence(&storage_path, &storage);
 
-    copy_template(storage_path.join("templates"));
+    copy_template(storage_path.join("templates"), &storage);
 
     let templates = storage.list_template_files().unwrap();
     println!("{:#?}",templates);
     assert!(templates.len() == 2);
-
-    ////util::freeze();
 }
 
-#[test]
-fn create_archive(){
-    let (_dir , storage_path, storage) = setup();
+fn create_archive<B: Backend>((_dir, storage_path, storage): Setup<B>) {
     assert!(storage.create_dirs().is_ok());
-    assert_existence(&storage_path);
+    assert_existence(&storage_path, &storage);
     storage.create_archive(2001).unwrap();
     storage.create_archive(2002).unwrap();
     storage.create_archive(2002).unwrap(); // should this fail?
-    assert!(storage_path.join("archive").join("2001").exists());
-    assert!(storage_path.join("archive").join("2002").exists());
-    util::ls(&_dir.path().to_string_lossy());
+    assert!(storage.backend().exists(&storage_path.join("archive").join("2001")));
+    assert!(storage.backend().exists(&storage_path.join("archive").join("2002")));
 }
 
-#[test]
-fn list_archives(){
-    let (_dir , storage_path, storage) = setup();
+fn list_archives<B: Backend>((_dir, storage_path, storage): Setup<B>) {
     assert!(storage.create_dirs().is_ok());
-    assert_existence(&storage_path);
+    assert_existence(&storage_path, &storage);
     storage.create_archive(2001).unwrap();
     storage.create_archive(2002).unwrap();
     storage.create_archive(1999).unwrap();
-    util::ls(&_dir.path().to_string_lossy());
 
     let mut archives = storage.list_archives().unwrap();
     let mut years = storage.list_years().unwrap();
@@ -144,11 +183,11 @@ fn list_archives(){
     println!("ARCHIVES\n{:#?}", archives);
 
     assert!(archives[0].ends_with("1999"));
-    assert!(archives[0].is_dir());
+    assert!(storage.backend().is_dir(&archives[0]));
     assert!(archives[1].ends_with("2001"));
-    assert!(archives[1].is_dir());
+    assert!(storage.backend().is_dir(&archives[1]));
     assert!(archives[2].ends_with("2002"));
-    assert!(archives[2].is_dir());
+    assert!(storage.backend().is_dir(&archives[2]));
 
     println!("ARCHIVES\n{:#?}", years);
     assert_eq!(years[0], 1999);
@@ -156,12 +195,10 @@ fn list_archives(){
     assert_eq!(years[2], 2002);
 }
 
-#[test]
-fn create_project(){
-    let (_dir , storage_path, storage) = setup();
+fn create_project<B: Backend>((_dir, storage_path, storage): Setup<B>) {
     assert!(storage.create_dirs().is_ok());
-    assert_existence(&storage_path);
-    copy_template(storage_path.join("templates"));
+    assert_existence(&storage_path, &storage);
+    copy_template(storage_path.join("templates"), &storage);
 
     let templates = storage.list_template_names().unwrap();
 
@@ -169,25 +206,22 @@ fn create_project(){
         let project     = storage.create_project(&test_project, &templates[0], &hashmap!()).unwrap();
         let target_file = project.file();
         let target_path = target_file.parent().unwrap();
-        assert!(target_path.exists());
-        assert!(target_file.exists());
-        util::ls(&target_path.display().to_string());
+        assert!(storage.backend().exists(target_path));
+        assert!(storage.backend().exists(&target_file));
         assert_eq!(target_file, storage.get_project_file(&target_path).unwrap());
 
         let project_dir = storage.get_project_dir(test_project, StorageDir::Working);
-        assert!(project_dir.unwrap().exists());
+        assert!(storage.backend().exists(&project_dir.unwrap()));
 
         let project_dir = storage.get_project_dir(test_project, StorageDir::Working);
         assert_eq!(project_dir.unwrap(), target_path);
     }
 }
 
-#[test]
-fn archive_project_by_name(){
-    let (_dir , storage_path, storage) = setup();
+fn archive_project_by_name<B: Backend>((_dir, storage_path, storage): Setup<B>) {
     assert!(storage.create_dirs().is_ok());
-    assert_existence(&storage_path);
-    copy_template(storage_path.join("templates"));
+    assert_existence(&storage_path, &storage);
+    copy_template(storage_path.join("templates"), &storage);
 
     let templates = storage.list_template_names().unwrap();
     trace!("templates: {:#?}", templates);
@@ -197,7 +231,7 @@ fn archive_project_by_name(){
 
         // the actual tests
         assert!(storage.archive_project_by_name(&test_project, 2015, None).is_ok());
-        assert!(!origin.file().exists());
+        assert!(!storage.backend().exists(&origin.file()));
 
         assert!(storage.get_project_dir(&test_project, StorageDir::Working).is_err());
         assert!(storage.get_project_dir(&test_project, StorageDir::Archive(2015)).is_ok());
@@ -207,12 +241,10 @@ fn archive_project_by_name(){
     }
 }
 
-#[test]
-fn archive_project(){
-    let (_dir , storage_path, storage) = setup();
+fn archive_project<B: Backend>((_dir, storage_path, storage): Setup<B>) {
     assert!(storage.create_dirs().is_ok(), "could not even create storage in {:?}", storage_path);
-    assert_existence(&storage_path);
-    copy_template(storage_path.join("templates"));
+    assert_existence(&storage_path, &storage);
+    copy_template(storage_path.join("templates"), &storage);
 
     let year = Utc::today().year();
 
@@ -222,14 +254,14 @@ fn archive_project(){
         let project = storage.create_project( &test_project_name, &templates[0], &hashmap!{}).unwrap();
 
         // Before archiving
-        assert!(project.file().exists());
+        assert!(storage.backend().exists(&project.file()));
         assert!(storage.get_project_dir(&test_project_name, StorageDir::Working).is_ok());
 
         // ARCHIVING
         assert!(storage.archive_project(&project, project.year().unwrap()).is_ok());
 
         // After archiving
-        assert!(!project.file().exists());
+        assert!(!storage.backend().exists(&project.file()));
         assert!(storage.get_project_dir(&test_project_name, StorageDir::Working).is_err());
         assert!(storage.get_project_dir(&test_project_name, StorageDir::Archive(year)).is_ok());
 
@@ -237,12 +269,28 @@ fn archive_project(){
     }
 }
 
-#[test]
-fn unarchive_project_dir(){
-    let (_dir , storage_path, storage) = setup();
+fn open_archived_projects<B: Backend>((_dir, storage_path, storage): Setup<B>) {
+    assert!(storage.create_dirs().is_ok(), "could not even create storage in {:?}", storage_path);
+    copy_template(storage_path.join("templates"), &storage);
+
+    let year = Utc::today().year();
+    let templates = storage.list_template_names().unwrap();
+    let done = storage.create_project("test1", &templates[0], &hashmap!{}).unwrap();
+    storage.archive_project(&done, year).unwrap();
+    storage.create_project("test2", &templates[0], &hashmap!{}).unwrap();
+
+    let working = storage.open_projects(StorageDir::Working).unwrap();
+    let archived = storage.open_projects(StorageDir::Archive(year)).unwrap();
+    assert_eq!(working.len(), 1);
+    assert_eq!(archived.len(), 1);
+    assert!(!working[0].archived);
+    assert!(archived[0].archived);
+}
+
+fn unarchive_project_dir<B: Backend>((_dir, storage_path, storage): Setup<B>) {
     assert!(storage.create_dirs().is_ok());
-    assert_existence(&storage_path);
-    copy_template(storage_path.join("templates"));
+    assert_existence(&storage_path, &storage);
+    copy_template(storage_path.join("templates"), &storage);
 
     let templates = storage.list_template_names().unwrap();
     for test_project in TEST_PROJECTS.iter() {
@@ -258,3 +306,90 @@ fn unarchive_project_dir(){
         }
     }
 }
+
+fn undo_archive_project<B: Backend>((_dir, storage_path, storage): Setup<B>) {
+    assert!(storage.create_dirs().is_ok());
+    copy_template(storage_path.join("templates"), &storage);
+
+    let templates = storage.list_template_names().unwrap();
+    let project = storage.create_project("test1", &templates[0], &hashmap!{}).unwrap();
+    let year = Utc::today().year();
+
+    storage.archive_project(&project, year).unwrap();
+    assert!(storage.get_project_dir("test1", StorageDir::Working).is_err());
+
+    let moved = storage.undo().unwrap();
+    assert_eq!(moved.len(), 2);
+    assert!(storage.backend().exists(&project.file()));
+    assert!(storage.get_project_dir("test1", StorageDir::Archive(year)).is_err());
+
+    // nothing left to undo
+    assert!(storage.undo().unwrap().is_empty());
+}
+
+fn delete_project_into_trash<B: Backend>((_dir, storage_path, storage): Setup<B>) {
+    assert!(storage.create_dirs().is_ok());
+    copy_template(storage_path.join("templates"), &storage);
+
+    let templates = storage.list_template_names().unwrap();
+    let project = storage.create_project("test1", &templates[0], &hashmap!{}).unwrap();
+
+    storage.delete_project_if(&project, || false).unwrap();
+    assert!(storage.backend().exists(&project.file()));
+
+    storage.delete_project_if(&project, || true).unwrap();
+    assert!(!storage.backend().exists(&project.file()));
+    assert_eq!(storage.backend().list(&storage.trash_dir()).unwrap().len(), 1);
+
+    let gitignore = storage.backend().read(&storage_path.join(transaction::GITIGNORE_FILE)).unwrap();
+    assert_eq!(gitignore, ".journal\ntrash/\n");
+
+    storage.undo().unwrap();
+    assert!(storage.backend().exists(&project.file()));
+}
+
+fn transaction_rollback<B: Backend>((_dir, storage_path, storage): Setup<B>) {
+    assert!(storage.create_dirs().is_ok());
+    let backend = storage.backend();
+
+    let (a, b, c) = (storage_path.join("working/a"), storage_path.join("working/b"), storage_path.join("working/c"));
+    backend.create_dir(&a).unwrap();
+    backend.create_dir(&b).unwrap();
+    backend.create_dir(&c).unwrap();
+
+    let mut transaction = transaction::Transaction::begin(backend, &storage_path, "test");
+    transaction.rename(&a, &storage_path.join("archive/a")).unwrap();
+    // target exists
+    assert!(transaction.rename(&b, &c).is_err());
+    transaction.rollback().unwrap();
+
+    assert!(backend.exists(&a) && backend.exists(&b) && backend.exists(&c));
+    assert!(!backend.exists(&storage_path.join("archive/a")));
+    assert!(!backend.exists(&storage_path.join(transaction::JOURNAL_FILE)));
+}
+
+#[test]
+fn file_db_backend_persists() {
+    let dir = TempDir::new_in(Path::new("."),"storage_test").unwrap();
+    let db_file = dir.path().join("storage.db");
+    let root = dir.path().join("storage_test");
+
+    {
+        let backend = FileDbBackend::open(&db_file).unwrap();
+        backend.create_dir(&root).unwrap();
+        backend.write(&root.join("a.yml"), "a: 1\nb: ünïcödé\n").unwrap();
+        backend.write(&root.join("empty.yml"), "").unwrap();
+
+        let signed = dir.path().join("signed.pdf");
+        std::fs::write(&signed, b"%PDF\n\x00\xff\xfe\n").unwrap();
+        backend.import(&signed, &root.join("signed.pdf")).unwrap();
+    }
+
+    let backend = FileDbBackend::open(&db_file).unwrap();
+    assert!(backend.is_dir(&root));
+    assert_eq!(backend.read(&root.join("a.yml")).unwrap(), "a: 1\nb: ünïcödé\n");
+    assert_eq!(backend.read(&root.join("empty.yml")).unwrap(), "");
+    assert_eq!(backend.read_bytes(&root.join("signed.pdf")).unwrap(), b"%PDF\n\x00\xff\xfe\n");
+    assert!(backend.read(&root.join("signed.pdf")).is_err());
+    assert_eq!(backend.list(&root).unwrap().len(), 3);
+}
@@ -0,0 +1,190 @@
+//! Journaled folder moves.
+//!
+//! Archiving, unarchiving and deleting only ever move project folders around,
+//! deleted projects are moved into the trash folder in the storage root.
+//! A `Transaction` records every move, so a failure half way through can be rolled back completely.
+//! Once committed, the moves are written into a journal in the storage root,
+//! which is what `asciii undo` reverts.
+
+use std::path::{Path, PathBuf};
+
+use failure::{bail, format_err, Error};
+use log::{debug, error, info};
+
+use super::Backend;
+
+/// Name of the journal file inside the storage root.
+pub const JOURNAL_FILE: &str = ".journal";
+
+/// Name of the folder inside the storage root that deleted projects are moved to.
+pub const TRASH_DIR: &str = "trash";
+
+/// Name of the `.gitignore` in the storage root that keeps the journal and the trash out of the repository.
+pub const GITIGNORE_FILE: &str = ".gitignore";
+
+/// A single recorded move.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Move {
+    pub from: PathBuf,
+    pub to: PathBuf,
+}
+
+/// A group of moves that either happen completely or not at all.
+pub struct Transaction<'a, B: Backend> {
+    backend: &'a B,
+    operation: String,
+    root: PathBuf,
+    moves: Vec<Move>,
+}
+
+impl<'a, B: Backend> Transaction<'a, B> {
+    /// Starts a transaction for a storage with `root`, `operation` is just for the journal.
+    pub fn begin(backend: &'a B, root: &Path, operation: &str) -> Self {
+        debug!("beginning transaction {:?}", operation);
+        Transaction {
+            backend,
+            operation: operation.to_owned(),
+            root: root.to_owned(),
+            moves: Vec::new(),
+        }
+    }
+
+    /// Renames `from` to `to` and records the move.
+    pub fn rename(&mut self, from: &Path, to: &Path) -> Result<(), Error> {
+        if self.backend.exists(to) {
+            bail!(format_err!("{} already exists", to.display()));
+        }
+        self.backend.rename(from, to)?;
+        self.moves.push(Move { from: from.to_owned(), to: to.to_owned() });
+        Ok(())
+    }
+
+    /// Moves done so far.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// Reverts all moves, last one first.
+    pub fn rollback(self) -> Result<(), Error> {
+        info!("rolling back {:?}", self.operation);
+        for Move { from, to } in self.moves.into_iter().rev() {
+            debug!("$ mv {} {}", to.display(), from.display());
+            self.backend.rename(&to, &from)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the journal, replacing the previous one, and returns the recorded moves.
+    pub fn commit(self) -> Result<Vec<Move>, Error> {
+        debug!("committing transaction {:?}", self.operation);
+        if !self.moves.is_empty() {
+            let mut journal = format!("{}\n", self.operation);
+            for Move { from, to } in &self.moves {
+                journal.push_str(&format!("{}\t{}\n", self.relative(from).display(), self.relative(to).display()));
+            }
+            ignore_in_git(self.backend, &self.root)?;
+            self.backend.write(&self.root.join(JOURNAL_FILE), &journal)?;
+        }
+        Ok(self.moves)
+    }
+
+    /// Commits if `result` is ok, rolls back otherwise.
+    pub fn finish<T>(self, result: Result<T, Error>) -> Result<T, Error> {
+        match result {
+            Ok(value) => {
+                self.commit()?;
+                Ok(value)
+            },
+            Err(e) => {
+                if let Err(rollback_error) = self.rollback() {
+                    error!("rollback failed: {}", rollback_error);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    fn relative<'p>(&self, path: &'p Path) -> &'p Path {
+        path.strip_prefix(&self.root).unwrap_or(path)
+    }
+}
+
+/// Adds the journal and the trash folder to the `.gitignore` in `root`, unless they are in there already.
+pub fn ignore_in_git<B: Backend>(backend: &B, root: &Path) -> Result<(), Error> {
+    let path = root.join(GITIGNORE_FILE);
+    let mut content = if backend.exists(&path) { backend.read(&path)? } else { String::new() };
+    let trash = format!("{}/", TRASH_DIR);
+    let missing = [JOURNAL_FILE, trash.as_str()].iter()
+                                                .filter(|entry| !content.lines().any(|line| line.trim() == **entry))
+                                                .map(|entry| entry.to_string())
+                                                .collect::<Vec<String>>();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    debug!("adding {:?} to {}", missing, path.display());
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    for entry in missing {
+        content.push_str(&entry);
+        content.push('\n');
+    }
+    backend.write(&path, &content)
+}
+
+/// Reads the journal in `root`, returns the name of the operation and its moves.
+pub fn read_journal<B: Backend>(backend: &B, root: &Path) -> Result<Option<(String, Vec<Move>)>, Error> {
+    let path = root.join(JOURNAL_FILE);
+    if !backend.exists(&path) {
+        return Ok(None);
+    }
+
+    let content = backend.read(&path)?;
+    let mut lines = content.lines();
+    let operation = lines.next().unwrap_or("").to_owned();
+    let moves = lines.filter(|l| !l.is_empty())
+        .map(|line| {
+            let mut parts = line.splitn(2, '\t');
+            match (parts.next(), parts.next()) {
+                (Some(from), Some(to)) => Ok(Move { from: root.join(from), to: root.join(to) }),
+                _ => Err(format_err!("broken journal entry {:?}", line))
+            }
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    Ok(Some((operation, moves)))
+}
+
+/// Reverts the last journaled operation in `root`.
+///
+/// Returns the name of the operation and the moves that were reverted,
+/// or `None` if there is nothing to undo.
+/// Undo is itself transactional and removes the journal when done, so it can't be undone again.
+pub fn undo<B: Backend>(backend: &B, root: &Path) -> Result<Option<(String, Vec<Move>)>, Error> {
+    let (operation, moves) = match read_journal(backend, root)? {
+        Some(journal) => journal,
+        None => return Ok(None)
+    };
+
+    info!("undoing {:?}", operation);
+    let mut tx = Transaction::begin(backend, root, &format!("undo {}", operation));
+    let result = moves.iter()
+        .rev()
+        .map(|m| tx.rename(&m.to, &m.from))
+        .collect::<Result<Vec<()>, Error>>();
+
+    match result {
+        Ok(_) => {
+            let reverted = tx.moves;
+            backend.remove_file(&root.join(JOURNAL_FILE))?;
+            Ok(Some((operation, reverted)))
+        },
+        Err(e) => {
+            if let Err(rollback_error) = tx.rollback() {
+                error!("rollback failed: {}", rollback_error);
+            }
+            Err(e)
+        }
+    }
+}
@@ -0,0 +1,308 @@
+//! Exact decimal numbers for prices, amounts and tax rates.
+//!
+//! `yaml-rust` keeps the source text of real numbers, so `price: 0.35` can be read as exactly
+//! 35 hundredths instead of the closest `f64`.
+//! Nothing is rounded while calculating with `Decimal`s,
+//! only when they are turned into cents, and then commercially:
+//! halves are rounded away from zero, as usual on German invoices.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{Add, Mul, Neg};
+use std::str::FromStr;
+
+use bill::Currency;
+use failure::Fail;
+use yaml_rust::Yaml;
+
+/// A number with a fixed amount of decimal places.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Decimal {
+    /// The value times `10^scale`
+    units: i128,
+    scale: u32,
+}
+
+#[derive(Fail, Debug)]
+#[fail(display = "{:?} is not a decimal number", _0)]
+pub struct ParseDecimalError(String);
+
+fn pow10(exponent: u32) -> i128 {
+    10i128.pow(exponent)
+}
+
+/// Divides and rounds halves away from zero.
+fn div_rounded(numerator: i128, denominator: i128) -> i128 {
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    if remainder.abs() * 2 >= denominator.abs() {
+        quotient + numerator.signum() * denominator.signum()
+    } else {
+        quotient
+    }
+}
+
+impl Decimal {
+    pub fn new(units: i128, scale: u32) -> Self {
+        Decimal { units, scale }.normalized()
+    }
+
+    pub fn from_cents(cents: i64) -> Self {
+        Decimal::new(i128::from(cents), 2)
+    }
+
+    /// Reads integers, reals and numeric strings without going through `f64`.
+    pub fn from_yaml(yaml: &Yaml) -> Option<Self> {
+        match *yaml {
+            Yaml::Integer(integer) => Some(Decimal::from(integer)),
+            Yaml::Real(ref text) | Yaml::String(ref text) => text.trim().parse().ok(),
+            _ => None
+        }
+    }
+
+    /// Converts an `f64` that was itself parsed from a short decimal, e.g. a `BillItem::amount`.
+    ///
+    /// This is exact for up to 15 significant digits,
+    /// because `f64` is displayed as the shortest text that parses back to the same value.
+    pub fn from_f64(float: f64) -> Option<Self> {
+        if !float.is_finite() {
+            return None;
+        }
+        float.to_string().parse().ok()
+    }
+
+    /// Removes trailing zeros from the decimal places
+    fn normalized(mut self) -> Self {
+        while self.scale > 0 && self.units % 10 == 0 {
+            self.units /= 10;
+            self.scale -= 1;
+        }
+        self
+    }
+
+    /// Both values with the same scale
+    fn aligned(self, other: Self) -> (i128, i128, u32) {
+        let scale = self.scale.max(other.scale);
+        (self.units * pow10(scale - self.scale), other.units * pow10(scale - other.scale), scale)
+    }
+
+    pub fn is_zero(self) -> bool {
+        self.units == 0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.units < 0
+    }
+
+    /// Rounds to `scale` decimal places, halves away from zero.
+    pub fn round(self, scale: u32) -> Self {
+        if self.scale <= scale {
+            return self;
+        }
+        Decimal::new(div_rounded(self.units, pow10(self.scale - scale)), scale)
+    }
+
+    /// Divides by `divisor` and rounds the result to `scale` decimal places, `None` when dividing by zero.
+    pub fn divide(self, divisor: Self, scale: u32) -> Option<Self> {
+        if divisor.is_zero() {
+            return None;
+        }
+        let numerator = self.units * pow10(divisor.scale + scale);
+        let denominator = divisor.units * pow10(self.scale);
+        Some(Decimal::new(div_rounded(numerator, denominator), scale))
+    }
+
+    /// Takes a percentage like `19` as the fraction `0.19`.
+    pub fn percent(self) -> Self {
+        Decimal::new(self.units, self.scale + 2)
+    }
+
+    /// The value in whole cents, rounded commercially.
+    pub fn to_cents(self) -> i64 {
+        let rounded = self.round(2);
+        (rounded.units * pow10(2 - rounded.scale)) as i64
+    }
+
+    /// Rounds to cents, in the configured currency.
+    ///
+    /// The symbol is the configured one even for amounts of a project in a foreign currency,
+    /// format those with [`HasCurrency::format_amount()`](../../project/spec/trait.HasCurrency.html#method.format_amount).
+    pub fn to_currency(self) -> Currency {
+        Currency { symbol: crate::CONFIG.get_char("currency"), value: self.to_cents() }
+    }
+
+    pub fn as_f64(self) -> f64 {
+        self.units as f64 / pow10(self.scale) as f64
+    }
+}
+
+impl From<i64> for Decimal {
+    fn from(integer: i64) -> Self {
+        Decimal::new(i128::from(integer), 0)
+    }
+}
+
+impl From<Currency> for Decimal {
+    fn from(currency: Currency) -> Self {
+        Decimal::from_cents(currency.value)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = ParseDecimalError;
+
+    /// Parses `12`, `-0.5` or `+3.10`, but no exponents.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = || ParseDecimalError(text.to_owned());
+        let (negative, digits) = match text.chars().next() {
+            Some('-') => (true, &text[1..]),
+            Some('+') => (false, &text[1..]),
+            _ => (false, text)
+        };
+        let (integer, fraction) = match digits.find('.') {
+            Some(point) => (&digits[..point], &digits[point + 1..]),
+            None => (digits, "")
+        };
+        let is_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if (integer.is_empty() && fraction.is_empty()) || !is_digits(integer) || !is_digits(fraction) {
+            return Err(error());
+        }
+
+        let mut units = 0i128;
+        for digit in integer.chars().chain(fraction.chars()) {
+            units = units.checked_mul(10)
+                         .and_then(|units| units.checked_add(i128::from(digit.to_digit(10).unwrap_or(0))))
+                         .ok_or_else(error)?;
+        }
+        Ok(Decimal::new(if negative { -units } else { units }, fraction.len() as u32))
+    }
+}
+
+/// `{:.2}` rounds or pads to two decimal places.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (units, scale) = match f.precision() {
+            Some(precision) => {
+                let precision = precision as u32;
+                let rounded = self.round(precision);
+                (rounded.units * pow10(precision - rounded.scale), precision)
+            }
+            None => (self.units, self.scale)
+        };
+        let divisor = pow10(scale);
+        let sign = if units < 0 { "-" } else { "" };
+        let integer = (units / divisor).abs();
+        if scale == 0 {
+            write!(f, "{}{}", sign, integer)
+        } else {
+            let fraction = (units % divisor).abs();
+            write!(f, "{}{}.{:0width$}", sign, integer, fraction, width = scale as usize)
+        }
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Self) -> bool {
+        let (a, b, _) = self.aligned(*other);
+        a == b
+    }
+}
+
+impl Eq for Decimal {}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let (a, b, _) = self.aligned(*other);
+        a.cmp(&b)
+    }
+}
+
+impl Add for Decimal {
+    type Output = Decimal;
+    fn add(self, other: Self) -> Self {
+        let (a, b, scale) = self.aligned(other);
+        Decimal::new(a + b, scale)
+    }
+}
+
+impl Mul for Decimal {
+    type Output = Decimal;
+    fn mul(self, other: Self) -> Self {
+        Decimal::new(self.units * other.units, self.scale + other.scale)
+    }
+}
+
+impl Neg for Decimal {
+    type Output = Decimal;
+    fn neg(self) -> Self {
+        Decimal { units: -self.units, scale: self.scale }
+    }
+}
+
+impl std::iter::Sum for Decimal {
+    fn sum<I: Iterator<Item = Decimal>>(iter: I) -> Self {
+        iter.fold(Decimal::default(), Add::add)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(text: &str) -> Decimal {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn parses_exactly() {
+        assert_eq!(dec("0.35").to_string(), "0.35");
+        assert_eq!(dec("3.10").to_string(), "3.1");
+        assert_eq!(dec("-0.5").to_string(), "-0.5");
+        assert_eq!(dec("+12").to_string(), "12");
+        assert_eq!(format!("{:.2}", dec("2.5")), "2.50");
+        assert_eq!(format!("{:.2}", dec("-0.125")), "-0.13");
+        assert_eq!(dec(".5"), dec("0.5"));
+        assert!("1e3".parse::<Decimal>().is_err());
+        assert!("".parse::<Decimal>().is_err());
+        assert!("1.2.3".parse::<Decimal>().is_err());
+        assert!("-".parse::<Decimal>().is_err());
+    }
+
+    #[test]
+    fn reads_yaml_source() {
+        assert_eq!(Decimal::from_yaml(&Yaml::Real("1.005".into())), Some(dec("1.005")));
+        assert_eq!(Decimal::from_yaml(&Yaml::Integer(40)), Some(dec("40")));
+        assert_eq!(Decimal::from_yaml(&Yaml::String(" 2.50 ".into())), Some(dec("2.5")));
+        assert_eq!(Decimal::from_yaml(&Yaml::Boolean(true)), None);
+        assert_eq!(Decimal::from_f64(0.1), Some(dec("0.1")));
+        assert_eq!(Decimal::from_f64(4.4), Some(dec("4.4")));
+    }
+
+    #[test]
+    fn rounds_halves_away_from_zero() {
+        assert_eq!(dec("1.005").to_cents(), 101);
+        assert_eq!(dec("1.0049").to_cents(), 100);
+        assert_eq!(dec("-1.005").to_cents(), -101);
+        assert_eq!(dec("12").to_cents(), 1200);
+        assert_eq!(dec("0.575").to_cents(), 58);
+        assert_eq!(dec("2.5").round(0), dec("3"));
+    }
+
+    #[test]
+    fn calculates_exactly() {
+        // 0.1 + 0.2 != 0.3 in f64
+        assert_eq!(dec("0.1") + dec("0.2"), dec("0.3"));
+        assert_eq!(dec("8.50") * dec("4.4"), dec("37.4"));
+        assert_eq!(dec("19").percent(), dec("0.19"));
+        assert_eq!(Decimal::from_cents(1000).divide(dec("3"), 2), Some(dec("3.33")));
+        assert_eq!(Decimal::from_cents(-1000).divide(dec("6"), 2), Some(dec("-1.67")));
+        assert_eq!(dec("1").divide(dec("0"), 2), None);
+        assert!(dec("-0.01") < dec("0"));
+    }
+}
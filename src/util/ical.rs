@@ -0,0 +1,153 @@
+//! Helpers around the `icalendar` crate.
+//!
+//! `icalendar` can not nest components, so alarms are attached to events and todos as
+//! an [`ALARM`](constant.ALARM.html) property holding the trigger time
+//! and only turned into proper `VALARM`s by [`to_string()`](fn.to_string.html).
+//!
+//! `icalendar` can not read calendars either, [`parse_components()`](fn.parse_components.html)
+//! does just enough of that to import events.
+
+use std::collections::BTreeMap;
+
+use chrono::prelude::*;
+use icalendar::{Calendar, Component};
+
+/// Placeholder property that is replaced by a `VALARM`.
+pub const ALARM: &str = "X-ASCIII-ALARM";
+
+/// Formats a point in time as iCalendar `DATE-TIME` in UTC.
+pub fn format_time(time: &DateTime<Utc>) -> String {
+    time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Lets the calendar client remind about `component` at `time`.
+pub fn add_alarm<C: Component>(component: &mut C, time: &DateTime<Utc>) {
+    component.add_property(ALARM, &format_time(time));
+}
+
+/// Serializes `calendar`, with alarms as `VALARM`s.
+pub fn to_string(calendar: &Calendar) -> String {
+    expand_alarms(&calendar.to_string())
+}
+
+fn expand_alarms(serialized: &str) -> String {
+    let marker = format!("{}:", ALARM);
+    let mut output = String::with_capacity(serialized.len());
+    let mut summary = String::new();
+    let mut alarm: Option<String> = None;
+
+    for line in serialized.lines() {
+        if line.starts_with(&marker) {
+            alarm = Some(line[marker.len()..].to_owned());
+            continue;
+        }
+        if line.starts_with("SUMMARY:") {
+            summary = line["SUMMARY:".len()..].to_owned();
+        }
+        if line == "END:VEVENT" || line == "END:VTODO" {
+            if let Some(trigger) = alarm.take() {
+                output.push_str("BEGIN:VALARM\r\n");
+                output.push_str("ACTION:DISPLAY\r\n");
+                output.push_str(&format!("DESCRIPTION:{}\r\n", summary));
+                output.push_str(&format!("TRIGGER;VALUE=DATE-TIME:{}\r\n", trigger));
+                output.push_str("END:VALARM\r\n");
+            }
+        }
+        output.push_str(line);
+        output.push_str("\r\n");
+    }
+    output
+}
+
+/// Properties of one component, names are upper case and parameters like `TZID` are dropped.
+pub type Properties = BTreeMap<String, String>;
+
+/// Reads all components of one kind, e.g. `"VEVENT"`, from an iCalendar file.
+///
+/// Properties of nested components (e.g. a `VALARM` inside a `VEVENT`) are left out.
+pub fn parse_components(content: &str, kind: &str) -> Vec<Properties> {
+    // unfold continuation lines first
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        if line.starts_with(' ') || line.starts_with('\t') {
+            if let Some(last) = lines.last_mut() {
+                last.push_str(&line[1..]);
+                continue;
+            }
+        }
+        lines.push(line.trim_end().to_owned());
+    }
+
+    let begin = format!("BEGIN:{}", kind);
+    let end = format!("END:{}", kind);
+    let mut components = Vec::new();
+    let mut component: Option<Properties> = None;
+    let mut nested = 0;
+
+    for line in &lines {
+        if *line == begin {
+            component = Some(Properties::new());
+        } else if *line == end {
+            components.extend(component.take());
+        } else if let Some(ref mut properties) = component {
+            if line.starts_with("BEGIN:") {
+                nested += 1;
+            } else if line.starts_with("END:") {
+                nested -= 1;
+            } else if nested == 0 {
+                if let Some(colon) = line.find(':') {
+                    let name = line[..colon].split(';').next().unwrap_or("").to_uppercase();
+                    properties.insert(name, line[colon + 1..].to_owned());
+                }
+            }
+        }
+    }
+    components
+}
+
+/// Undoes the escaping of text values.
+pub fn unescape(value: &str) -> String {
+    value.replace("\\n", "\n")
+         .replace("\\N", "\n")
+         .replace("\\,", ",")
+         .replace("\\;", ";")
+         .replace("\\\\", "\\")
+}
+
+/// Reads a `DATE-TIME` value, times in UTC are converted to local time.
+pub fn parse_date_time(value: &str) -> Option<NaiveDateTime> {
+    if value.ends_with('Z') {
+        let utc = NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S").ok()?;
+        Some(Local.from_utc_datetime(&utc).naive_local())
+    } else {
+        NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok()
+    }
+}
+
+/// Reads a `DATE` value, as used by all day events.
+pub fn parse_date(value: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(value, "%Y%m%d").ok()
+}
+
+#[test]
+fn test_parse_components() {
+    let content = "BEGIN:VCALENDAR\r\nBEGIN:VEVENT\r\nSUMMARY:Birthday\r\n  Party\r\nDTSTART;TZID=Europe/Berlin:20190501T180000\r\n\
+                   DESCRIPTION:cake\\, coffee\\nand tea\r\nBEGIN:VALARM\r\nDESCRIPTION:alarm\r\nEND:VALARM\r\nEND:VEVENT\r\nEND:VCALENDAR\r\n";
+    let events = parse_components(content, "VEVENT");
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0]["SUMMARY"], "Birthday Party");
+    assert_eq!(unescape(&events[0]["DESCRIPTION"]), "cake, coffee\nand tea");
+    assert_eq!(parse_date_time(&events[0]["DTSTART"]), Some(NaiveDate::from_ymd(2019, 5, 1).and_hms(18, 0, 0)));
+    assert_eq!(parse_date("20190501"), Some(NaiveDate::from_ymd(2019, 5, 1)));
+    assert_eq!(parse_date_time("20190501"), None);
+}
+
+#[test]
+fn test_expand_alarms() {
+    let serialized = "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:Pay\r\nX-ASCIII-ALARM:20190615T111000Z\r\nEND:VTODO\r\nBEGIN:VTODO\r\nSUMMARY:Archive\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+    let expanded = expand_alarms(serialized);
+    assert_eq!(expanded,
+               "BEGIN:VCALENDAR\r\nBEGIN:VTODO\r\nSUMMARY:Pay\r\n\
+                BEGIN:VALARM\r\nACTION:DISPLAY\r\nDESCRIPTION:Pay\r\nTRIGGER;VALUE=DATE-TIME:20190615T111000Z\r\nEND:VALARM\r\n\
+                END:VTODO\r\nBEGIN:VTODO\r\nSUMMARY:Archive\r\nEND:VTODO\r\nEND:VCALENDAR\r\n");
+}
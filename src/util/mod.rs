@@ -17,6 +17,8 @@ use log::{info, debug, error, warn};
 use open;
 
 pub mod yaml;
+pub mod ical;
+pub mod decimal;
 
 /// Sets up logging initially.
 ///
@@ -167,8 +169,11 @@ pub fn currency_to_string(currency:&Currency) -> String {
 /// Creates a currency from an `f64`
 ///
 /// This is functionality which was explicitly left out of the `Claude` crate.
+/// Rounds to cents like `decimal::Decimal::to_currency()`, prefer reading a `Decimal` in the first place.
 pub fn to_currency(f: f64) -> Currency {
-    Currency{ symbol: crate::CONFIG.get_char("currency"), value: (f * 1000.0) as i64} / 10
+    decimal::Decimal::from_f64(f)
+        .map(decimal::Decimal::to_currency)
+        .unwrap_or_else(|| Currency{ symbol: crate::CONFIG.get_char("currency"), value: 0 })
 }
 
 /// Changes the extension of a given `Path`
@@ -211,3 +216,44 @@ fn test_naive_time_from_str() {
     assert_eq!(Some(NaiveTime::from_hms(23,0,0)), naive_time_from_str("23:0"));
 }
 
+
+/// Line based diff of two texts, only changed lines are returned.
+///
+/// Removed lines are prefixed with `-`, added lines with `+`, each with its line number.
+pub fn diff_lines(before: &str, after: &str) -> Vec<String> {
+    let old = before.lines().collect::<Vec<_>>();
+    let new = after.lines().collect::<Vec<_>>();
+
+    // longest common subsequence, from the back
+    let mut lcs = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] { lcs[i + 1][j + 1] + 1 }
+                        else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+
+    let mut changes = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            i += 1;
+            j += 1;
+        } else if j < new.len() && (i == old.len() || lcs[i][j + 1] >= lcs[i + 1][j]) {
+            changes.push(format!("{:>4} + {}", j + 1, new[j]));
+            j += 1;
+        } else {
+            changes.push(format!("{:>4} - {}", i + 1, old[i]));
+            i += 1;
+        }
+    }
+    changes
+}
+
+#[test]
+fn test_diff_lines() {
+    let before = "a\nb\nc\n";
+    let after = "a\nB\nc\nd\n";
+    assert_eq!(diff_lines(before, after), vec!["   2 + B", "   2 - b", "   4 + d"]);
+    assert!(diff_lines(before, before).is_empty());
+}
@@ -49,7 +49,7 @@ pub fn parse_dmy_date(date_str:&str) -> Option<Date<Utc>>{
     let date = date_str.split('.')
                        .map(|f|f.parse().unwrap_or(0))
                        .collect::<Vec<i32>>();
-    if date.len() >=2 && date[0] > 0 && date[2] > 1900 {
+    if date.len() >=3 && date[0] > 0 && date[2] > 1900 {
         // XXX this neglects the old "01-05.12.2015" format
         Utc.ymd_opt(date[2], date[1] as u32, date[0] as u32)
         .single()
@@ -108,6 +108,11 @@ pub fn get_f64(yaml:&Yaml, key:&str) -> Option<f64> {
     get(yaml,key).and_then(|y| y.as_f64().or_else(|| y.as_i64().map(|y|y as f64)))
 }
 
+/// Gets a number as exact `Decimal`, from its source text.
+pub fn get_decimal(yaml:&Yaml, key:&str) -> Option<super::decimal::Decimal> {
+    get(yaml,key).and_then(super::decimal::Decimal::from_yaml)
+}
+
 /// Gets an `Int` value.
 ///
 /// Same mentality as `yaml_rust`, only returns `Some`, if it's a `Yaml::Int`.
@@ -198,3 +203,304 @@ fn get_path<'a>(yaml:&'a Yaml, path:&[&str]) -> Option<&'a Yaml>{
     }
     None
 }
+
+/// Quotes a string so it can be put into a yaml document as a scalar.
+pub fn quote(value: &str) -> String {
+    let escaped = value.replace('\\', "\\\\")
+                       .replace('"', "\\\"")
+                       .replace('\n', "\\n");
+    format!("\"{}\"", escaped)
+}
+
+/// Appends `entry` to the top level list `key` of a yaml document.
+///
+/// This works on the text, so comments and anchors of the document survive.
+/// The list is created if it does not exist yet,
+/// `entry` must already be indented as a list item, e.g. `"  - file: receipt.pdf\n"`.
+/// Flow lists like `[a.pdf]` are rejected, only an empty one can be appended to.
+pub fn append_to_list(content: &str, key: &str, entry: &str) -> Result<String, failure::Error> {
+    let mut lines = content.lines().map(ToOwned::to_owned).collect::<Vec<String>>();
+    let entry_lines = entry.lines().map(ToOwned::to_owned).collect::<Vec<String>>();
+    let header = format!("{}:", key);
+    let is_top_level = |line: &str| !line.is_empty() && !line.starts_with(' ') && !line.starts_with('-') && !line.starts_with('#');
+
+    if let Some(start) = lines.iter().position(|l| l.starts_with(&header) && is_top_level(l)) {
+        // `key: []` or `key:` with nothing in it, keeping a trailing comment
+        let rest = lines[start][header.len()..].trim().to_owned();
+        let (value, comment) = match rest.find('#') {
+            Some(hash) => (rest[..hash].trim(), format!(" {}", &rest[hash..])),
+            None => (rest.as_str(), String::new())
+        };
+        if !value.is_empty() && value != "[]" {
+            failure::bail!(failure::format_err!("{:?} is not a block list: {}", key, value));
+        }
+        lines[start] = format!("{}{}", header, comment);
+        let mut end = start + 1;
+        for (index, line) in lines.iter().enumerate().skip(start + 1) {
+            if is_top_level(line) || line == "..." {
+                break;
+            }
+            if !line.trim().is_empty() {
+                end = index + 1;
+            }
+        }
+        lines.splice(end..end, entry_lines);
+    } else {
+        let end = match lines.iter().rposition(|l| !l.trim().is_empty()) {
+            Some(last) if lines[last] == "..." => last,
+            Some(last) => last + 1,
+            None => 0
+        };
+        let mut block = vec![header];
+        block.extend(entry_lines);
+        lines.splice(end..end, block);
+    }
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+    Ok(result)
+}
+
+fn indentation(line: &str) -> usize {
+    line.len() - line.trim_start_matches(' ').len()
+}
+
+fn is_content(line: &str) -> bool {
+    let trimmed = line.trim();
+    !trimmed.is_empty() && !trimmed.starts_with('#')
+}
+
+/// Splits a mapping line like `key: rest` or `"quoted key" : rest` into its key and the rest.
+fn split_key(line: &str) -> Option<(String, &str)> {
+    let line = line.trim_start();
+    let (key, after_key) = if line.starts_with('"') {
+        let close = line[1..].find('"')? + 1;
+        (line[1..close].to_owned(), &line[close + 1..])
+    } else {
+        let colon = line.find(':')?;
+        (line[..colon].trim_end().to_owned(), &line[colon..])
+    };
+    let after_key = after_key.trim_start();
+    if !after_key.starts_with(':') {
+        return None;
+    }
+    let rest = &after_key[1..];
+    if !rest.is_empty() && !rest.starts_with(' ') {
+        return None;
+    }
+    Some((key, rest.trim()))
+}
+
+/// Splits `value # comment` into the value and the comment with its leading space.
+fn split_comment(rest: &str) -> (&str, String) {
+    let mut quote = None;
+    let mut previous = ' ';
+    for (index, c) in rest.char_indices() {
+        match (quote, c) {
+            (None, '#') if previous.is_whitespace() => return (rest[..index].trim_end(), format!(" {}", &rest[index..])),
+            (None, '"') | (None, '\'') => quote = Some(c),
+            (Some(open), _) if c == open => quote = None,
+            _ => {}
+        }
+        previous = c;
+    }
+    (rest, String::new())
+}
+
+/// Writes `key` as a plain scalar if possible, quoted otherwise.
+fn key_to_string(key: &str) -> String {
+    if key.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == ' ') && !key.starts_with(' ') {
+        key.to_owned()
+    } else {
+        quote(key)
+    }
+}
+
+/// Lines of `key: value` at `indent`, a `value` starting with a newline becomes an indented block.
+fn entry_lines(indent: usize, key: &str, value: &str, comment: &str) -> Vec<String> {
+    let prefix = " ".repeat(indent);
+    if value.starts_with('\n') {
+        let mut block = vec![format!("{}{}:{}", prefix, key_to_string(key), comment)];
+        block.extend(value[1..].lines().map(|line| format!("{}  {}", prefix, line)));
+        block
+    } else {
+        vec![format!("{}{}: {}{}", prefix, key_to_string(key), value, comment)]
+    }
+}
+
+/// Sets the value at `path` (e.g. `hours/wages_date`) in a yaml document.
+///
+/// Like `append_to_list()` this works on the text, so comments survive.
+/// Missing keys are created, `value` is put into the document verbatim
+/// and replaces whatever block was there before.
+/// A `value` starting with a newline is written as a block below the key, e.g. `"\n- a\n- b"`.
+/// Only block mappings can be descended into, flow mappings like `{ a: 1 }` are rejected.
+pub fn set_value(content: &str, path: &str, value: &str) -> Result<String, failure::Error> {
+    let keys = path.split(|c| c == '/' || c == '.')
+                   .filter(|k| !k.is_empty())
+                   .collect::<Vec<&str>>();
+    set_value_at(content, &keys, value)
+}
+
+/// Same as `set_value()`, but takes the keys one by one, so they may contain `/` and `.`.
+pub fn set_value_at(content: &str, keys: &[&str], value: &str) -> Result<String, failure::Error> {
+    replace_at(content, keys, Some(value))
+}
+
+/// Removes the key at `keys` together with its value, comments elsewhere survive.
+///
+/// A key that does not exist is not an error, the document is returned unchanged.
+pub fn remove_value_at(content: &str, keys: &[&str]) -> Result<String, failure::Error> {
+    replace_at(content, keys, None)
+}
+
+fn replace_at(content: &str, keys: &[&str], value: Option<&str>) -> Result<String, failure::Error> {
+    let mut lines = content.lines().map(ToOwned::to_owned).collect::<Vec<String>>();
+
+    let mut start = 0;
+    let mut end = lines.iter().position(|l| l == "...").unwrap_or_else(|| lines.len());
+    let mut parent_indent = None;
+
+    for (depth, key) in keys.iter().enumerate() {
+        let is_last = depth + 1 == keys.len();
+        let child_indent = match parent_indent {
+            None => 0,
+            Some(parent) => lines[start..end].iter()
+                                             .find(|l| is_content(l))
+                                             .map(|l| indentation(l))
+                                             .unwrap_or(parent + 2)
+        };
+        let found = (start..end).find(|&i| indentation(&lines[i]) == child_indent
+                                           && split_key(&lines[i]).map_or(false, |(k, _)| k == *key));
+
+        if let Some(index) = found {
+            let rest = split_key(&lines[index]).map(|(_, rest)| rest.to_owned()).unwrap_or_default();
+            if is_last {
+                let (previous, mut comment) = split_comment(&rest);
+                // a previous block value goes away, including sequences that are not indented
+                let block_end = (index + 1..end)
+                    .take_while(|&i| !is_content(&lines[i])
+                                     || indentation(&lines[i]) > child_indent
+                                     || (indentation(&lines[i]) == child_indent && lines[i].trim_start().starts_with('-')))
+                    .filter(|&i| is_content(&lines[i]))
+                    .last()
+                    .map_or(index + 1, |i| i + 1);
+                let replacement = match value {
+                    // aliases elsewhere in the document still refer to the anchor
+                    Some(value) => match previous.split_whitespace().next().filter(|t| t.starts_with('&')) {
+                        Some(anchor) if value.starts_with('\n') => {
+                            comment = format!(" {}{}", anchor, comment);
+                            entry_lines(child_indent, key, value, &comment)
+                        },
+                        Some(anchor) => entry_lines(child_indent, key, &format!("{} {}", anchor, value), &comment),
+                        None => entry_lines(child_indent, key, value, &comment)
+                    },
+                    None => Vec::new()
+                };
+                lines.splice(index..block_end, replacement);
+                break;
+            }
+            if !rest.is_empty() && !rest.starts_with('#') {
+                failure::bail!(failure::format_err!("{:?} is not a block mapping", keys[..=depth].join("/")));
+            }
+            start = index + 1;
+            end = (start..end).find(|&i| is_content(&lines[i]) && indentation(&lines[i]) <= child_indent)
+                              .unwrap_or(end);
+            parent_indent = Some(child_indent);
+        } else if let Some(value) = value {
+            let insert_at = (start..end).rev()
+                                        .find(|&i| is_content(&lines[i]))
+                                        .map_or(start, |i| i + 1);
+            let missing = keys[depth..].iter()
+                .enumerate()
+                .flat_map(|(level, key)| {
+                    let indent = child_indent + 2 * level;
+                    if depth + level + 1 == keys.len() {
+                        entry_lines(indent, key, value, "")
+                    } else {
+                        vec![format!("{}{}:", " ".repeat(indent), key_to_string(key))]
+                    }
+                })
+                .collect::<Vec<String>>();
+            lines.splice(insert_at..insert_at, missing);
+            break;
+        } else {
+            break;
+        }
+    }
+
+    let mut result = lines.join("\n");
+    result.push('\n');
+    Ok(result)
+}
+
+#[test]
+fn test_set_value() {
+    let doc = "hours:\n  salary: 8.0\n  caterers:\n    Alice: 3\n\n  wages_date: # when\n\ntax: 0.19\n...\n";
+
+    let set = set_value(doc, "hours/wages_date", "01.02.2019").unwrap();
+    assert_eq!(set, "hours:\n  salary: 8.0\n  caterers:\n    Alice: 3\n\n  wages_date: 01.02.2019 # when\n\ntax: 0.19\n...\n");
+
+    let nested = set_value(doc, "hours/caterers/Bob", "2").unwrap();
+    assert_eq!(get_int(&parse(&nested).unwrap(), "hours/caterers/Bob"), Some(2));
+    assert_eq!(get_int(&parse(&nested).unwrap(), "hours/caterers/Alice"), Some(3));
+
+    let created = set_value(doc, "offer/accepted", "yes").unwrap();
+    assert_eq!(get_bool(&parse(&created).unwrap(), "offer/accepted"), Some(true));
+    assert!(created.ends_with("offer:\n  accepted: yes\n...\n"));
+
+    let top = set_value(doc, "tax", "0.07").unwrap();
+    assert_eq!(get_f64(&parse(&top).unwrap(), "tax"), Some(0.07));
+
+    assert!(set_value("hours: { salary: 8 }\n", "hours/salary", "9").is_err());
+
+    let quoted = "hours:\n  caterers:\n    \"unknown\" : 0\n";
+    let replaced = set_value_at(quoted, &["hours", "caterers", "unknown"], "3").unwrap();
+    assert_eq!(replaced, "hours:\n  caterers:\n    unknown: 3\n");
+    let added = set_value_at(quoted, &["hours", "caterers", "hendrik s."], "2").unwrap();
+    assert_eq!(get_int(&parse(&added).unwrap(), "hours/caterers/unknown"), Some(0));
+    assert!(added.contains("    \"hendrik s.\": 2\n"));
+
+    let event = "event:\n  dates:\n  - begin: 01.05.2019\n    #end:\n    times:\n    - begin: \"18:00\"\n\n  location: here\n";
+    let dates = set_value(event, "event/dates", "\n- begin: 02.05.2019\n  end: 03.05.2019").unwrap();
+    assert_eq!(dates, "event:\n  dates:\n    - begin: 02.05.2019\n      end: 03.05.2019\n\n  location: here\n");
+    let scalar = set_value(event, "event/dates", "[]").unwrap();
+    assert_eq!(scalar, "event:\n  dates: []\n\n  location: here\n");
+
+    let anchored = "defaults: &defaults\n  price: 2\nrate: &rate 8.0 # per hour\nother: *rate\n";
+    let rate = set_value(anchored, "rate", "9.0").unwrap();
+    assert_eq!(rate, "defaults: &defaults\n  price: 2\nrate: &rate 9.0 # per hour\nother: *rate\n");
+    assert_eq!(get_f64(&parse(&rate).unwrap(), "other"), Some(9.0));
+    let block = set_value(anchored, "defaults", "\nprice: 3").unwrap();
+    assert!(block.starts_with("defaults: &defaults\n  price: 3\nrate:"));
+}
+
+#[test]
+fn test_remove_value() {
+    let doc = "hours:\n  # who worked\n  caterers:\n    Alice: 3\n    Bob: 2\n  salary: 8.0\n";
+    let removed = remove_value_at(doc, &["hours", "caterers"]).unwrap();
+    assert_eq!(removed, "hours:\n  # who worked\n  salary: 8.0\n");
+    let bob = remove_value_at(doc, &["hours", "caterers", "Bob"]).unwrap();
+    assert_eq!(bob, "hours:\n  # who worked\n  caterers:\n    Alice: 3\n  salary: 8.0\n");
+    assert_eq!(remove_value_at(doc, &["hours", "wages_date"]).unwrap(), doc);
+}
+
+#[test]
+fn test_append_to_list() {
+    let doc = "name: foo\n# comment\nlist:\n  - a: 1\n\nother: 2\n...\n";
+    let appended = append_to_list(doc, "list", "  - a: 2\n").unwrap();
+    assert_eq!(appended, "name: foo\n# comment\nlist:\n  - a: 1\n  - a: 2\n\nother: 2\n...\n");
+    assert_eq!(get_int(&parse(&appended).unwrap(), "list/1/a"), Some(2));
+
+    let created = append_to_list("name: foo\n...\n", "list", "  - a: 1\n").unwrap();
+    assert_eq!(created, "name: foo\nlist:\n  - a: 1\n...\n");
+
+    let emptied = append_to_list("list: [] # none yet\nname: foo\n", "list", "  - a: 1\n").unwrap();
+    assert_eq!(emptied, "list: # none yet\n  - a: 1\nname: foo\n");
+    assert_eq!(get_int(&parse(&emptied).unwrap(), "list/0/a"), Some(1));
+
+    assert!(append_to_list("list: [a.pdf]\n", "list", "  - b.pdf\n").is_err());
+    assert!(append_to_list("list: a.pdf # one\n", "list", "  - b.pdf\n").is_err());
+
+    assert_eq!(parse(&quote("say \"hi\"")).unwrap().as_str(), Some("say \"hi\""));
+}
@@ -85,3 +85,48 @@ tax: 0.19"#;
         );
     }
 }
+
+#[cfg(test)]
+mod totals {
+    use super::*;
+    use asciii::project::spec::Redeemable;
+    use asciii::project::totals::{Rounding, Totals};
+
+    const PROJECTS: &[&str] = &["current.yml", "inline.yml", "tax.yml", "tax_inline.yml"];
+
+    /// Exact sums may not differ from what `bill` calculated for any of the test projects.
+    #[test]
+    fn match_previous_totals() {
+        for name in PROJECTS {
+            let project = Project::open(&format!("./tests/test_projects/{}", name)).unwrap();
+            let (offer, invoice) = project.bills().unwrap();
+            for bill in &[offer, invoice] {
+                let previous = bill.iter()
+                                   .map(|(_, list)| (list.gross_sum().value, list.tax_sum().value))
+                                   .collect::<Vec<_>>();
+                for &rounding in &[Rounding::Line, Rounding::Total] {
+                    let totals = Totals::with_rounding(bill, rounding);
+                    let exact = totals.groups
+                                      .iter()
+                                      .map(|group| (group.before_tax.value, group.tax_amount.value))
+                                      .collect::<Vec<_>>();
+                    assert_eq!(exact, previous, "{} ({:?})", name, rounding);
+                    assert_eq!(totals.before_tax().value, bill.gross_total().value, "{} ({:?})", name, rounding);
+                    assert_eq!(totals.after_tax().value, bill.net_total().value, "{} ({:?})", name, rounding);
+                }
+            }
+        }
+    }
+
+    /// The dues are what the documents in `expected_exports` say.
+    #[test]
+    fn match_documents() {
+        for name in &["current.yml", "inline.yml"] {
+            let project = Project::open(&format!("./tests/test_projects/{}", name)).unwrap();
+            let (offer, _) = project.bills().unwrap();
+            assert_eq!(Totals::of(&offer).after_tax().value, 371_540);
+            assert_eq!(project.sum_sold().unwrap().value, 319_620);
+            assert_eq!(project.sum_final().unwrap().value, 319_620);
+        }
+    }
+}